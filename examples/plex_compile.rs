@@ -1086,7 +1086,7 @@ impl State {
             _ => return
         };
 
-        let map = match gpos.source_map.get(&pos.0) {
+        let map = match gpos.source_map.get(&(0, pos.0)) {
             Some(m) => match m.values().nth(pos.1) {
                 Some(CompiledEntry::I16(o)) => *o,
                 None => {
@@ -1,6 +1,7 @@
 use std::io::prelude::*;
-use std::fs::File;
+use std::fs::{self, File};
 use std::env;
+use std::path::PathBuf;
 
 use otf_fea_rs::{
     GlyphOrder,
@@ -51,25 +52,39 @@ fn fealib_builder_glyph_order() -> GlyphOrder {
 }
 
 fn main() {
-    let (in_path, out_path) = {
-        let mut a = env::args().skip(1).take(2);
+    let (in_path, out_path, include_dirs) = {
+        let mut a = env::args().skip(1);
 
         match (a.next(), a.next()) {
-            (Some(inp), Some(out)) => (inp, out),
+            (Some(inp), Some(out)) => (inp, out, a.map(PathBuf::from).collect::<Vec<_>>()),
             _ => {
-                eprintln!("usage: compile <input> <output>");
+                eprintln!("usage: compile <input> <output> [include dir...]");
                 ::std::process::exit(1);
             }
         }
     };
 
-    let f = File::open(&in_path).unwrap();
-
     let glyph_order = fealib_builder_glyph_order();
 
     println!();
     println!("parsing...");
-    let parsed = parser::parse_file(f).unwrap();
+    let (parsed, errors) = parser::parse_path_recovering(&in_path, &include_dirs);
+
+    if !errors.is_empty() {
+        for e in errors.iter() {
+            let source = e.file.as_ref()
+                .and_then(|path| fs::read_to_string(path).ok());
+
+            match source {
+                Some(source) => eprintln!("{}", e.render(&source)),
+                None => eprintln!("{}", e)
+            }
+        }
+
+        ::std::process::exit(1);
+    }
+
+    let parsed = parsed.statements;
     println!("    parsed successfully!");
     println!();
 
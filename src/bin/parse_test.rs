@@ -1,25 +1,29 @@
 use std::env;
-use std::fs::File;
+use std::fs;
 
 fn main() {
     let path = env::args().skip(1).next()
         .expect("need a path");
 
-    let f = File::open(&path).unwrap();
+    let (parsed, errors) = otf_fea_rs::parser::parse_path_recovering(&path, &[]);
 
-    match otf_fea_rs::parser::parse_all(f) {
-        Ok(definitions) => {
-            if env::var("FEA_RS_NO_PRINT").is_err() {
-                println!();
+    if env::var("FEA_RS_NO_PRINT").is_err() {
+        println!();
 
-                for d in definitions {
-                    println!("{:#?}\n", d);
-                }
-            }
+        for d in parsed.statements {
+            println!("{:#?}\n", d);
+        }
+    }
 
-            std::process::exit(0)
-        },
+    for e in errors.iter() {
+        let source = e.file.as_ref()
+            .and_then(|path| fs::read_to_string(path).ok());
 
-        Err(_) => std::process::exit(1)
+        match source {
+            Some(source) => eprintln!("{}", e.render(&source)),
+            None => eprintln!("{}", e)
+        }
     }
+
+    std::process::exit(if errors.is_empty() { 0 } else { 1 })
 }
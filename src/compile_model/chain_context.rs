@@ -0,0 +1,528 @@
+use std::vec;
+use std::collections::{
+    BTreeMap,
+    HashMap
+};
+
+use endian_codec::{PackedSize, EncodeBE, DecodeBE};
+
+use crate::compile_model::util::encode::*;
+use crate::compile_model::util::*;
+use crate::compile_model::class_def::*;
+use crate::compile_model::coverage::*;
+use crate::compile_model::lookup::*;
+
+
+// shared between GSUB (lookup types 5/6) and GPOS (lookup types 7/8), which have identical
+// layouts for their (chained) sequence context subtables.
+
+#[derive(Debug, Clone, PartialEq, Eq, PackedSize, EncodeBE, DecodeBE)]
+pub struct SequenceLookup {
+    pub sequence_index: u16,
+    pub lookup_index: u16
+}
+
+#[derive(Debug)]
+pub struct ContextRule {
+    // all three sequences are stored in logical (left-to-right) order. backtrack is reversed
+    // when encoding.
+    pub backtrack: Vec<ClassDef>,
+    pub input: Vec<ClassDef>,
+    pub lookahead: Vec<ClassDef>,
+
+    pub lookups: Vec<SequenceLookup>
+}
+
+impl ContextRule {
+    fn is_chained(&self) -> bool {
+        !self.backtrack.is_empty() || !self.lookahead.is_empty()
+    }
+
+    fn is_all_single_glyphs(&self) -> bool {
+        self.backtrack.iter()
+            .chain(self.input.iter())
+            .chain(self.lookahead.iter())
+            .all(|cls| cls.len() == 1)
+    }
+
+    pub fn referenced_lookups(&self) -> impl Iterator<Item = u16> + '_ {
+        self.lookups.iter()
+            .map(|l| l.lookup_index)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ChainContext {
+    pub rules: Vec<ContextRule>
+}
+
+impl ChainContext {
+    #[inline]
+    pub fn add_rule(&mut self, rule: ContextRule) {
+        self.rules.push(rule);
+    }
+
+    pub fn is_chained(&self) -> bool {
+        self.rules.iter().any(ContextRule::is_chained)
+    }
+
+    pub fn offset_lookup_indices(&mut self, offset: u16) {
+        for lookup in self.rules.iter_mut().flat_map(|r| r.lookups.iter_mut()) {
+            lookup.lookup_index += offset;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Sequence {
+    Backtrack,
+    Input,
+    Lookahead
+}
+
+#[derive(Debug, PackedSize, EncodeBE, DecodeBE)]
+struct ContextFormat1Header {
+    format: u16,
+    coverage_offset: u16,
+    rule_set_count: u16
+}
+
+#[derive(Debug, PackedSize, EncodeBE, DecodeBE)]
+struct ContextFormat2Header {
+    format: u16,
+    coverage_offset: u16,
+    class_def_offset: u16,
+    rule_set_count: u16
+}
+
+#[derive(Debug, PackedSize, EncodeBE, DecodeBE)]
+struct ChainContextFormat2Header {
+    format: u16,
+    coverage_offset: u16,
+    backtrack_class_def_offset: u16,
+    input_class_def_offset: u16,
+    lookahead_class_def_offset: u16,
+    rule_set_count: u16
+}
+
+// format 2 assigns each distinct glyph class in a sequence its own class id, which only works if
+// the classes used in that sequence never partially overlap.
+struct SequenceClasses<'a> {
+    backtrack: Vec<&'a ClassDef>,
+    input: Vec<&'a ClassDef>,
+    lookahead: Vec<&'a ClassDef>
+}
+
+impl<'a> SequenceClasses<'a> {
+    fn collect<I>(classes: I) -> Option<Vec<&'a ClassDef>>
+        where I: Iterator<Item = &'a ClassDef>
+    {
+        let mut collected: Vec<&ClassDef> = Vec::new();
+
+        for cls in classes {
+            if collected.contains(&cls) {
+                continue;
+            }
+
+            if collected.iter().any(|existing| !existing.is_disjoint(cls)) {
+                return None;
+            }
+
+            collected.push(cls);
+        }
+
+        Some(collected)
+    }
+
+    fn for_rules(rules: &'a [ContextRule]) -> Option<Self> {
+        Some(Self {
+            backtrack: Self::collect(rules.iter().flat_map(|r| r.backtrack.iter()))?,
+            input: Self::collect(rules.iter().flat_map(|r| r.input.iter()))?,
+            lookahead: Self::collect(rules.iter().flat_map(|r| r.lookahead.iter()))?
+        })
+    }
+
+    fn class_id(&self, sequence: Sequence, cls: &ClassDef) -> u16 {
+        let classes = match sequence {
+            Sequence::Backtrack => &self.backtrack,
+            Sequence::Input => &self.input,
+            Sequence::Lookahead => &self.lookahead
+        };
+
+        // class 0 is reserved for glyphs which aren't in any class
+        classes.iter()
+            .position(|c| *c == cls)
+            .map(|idx| idx as u16 + 1)
+            .unwrap_or(0)
+    }
+}
+
+#[inline]
+fn first_glyph(cls: &ClassDef) -> u16 {
+    cls.iter().next().copied().unwrap_or(0)
+}
+
+fn encode_rule<F>(buf: &mut EncodeBuf, rule: &ContextRule, chained: bool, id_for: &F)
+        -> EncodeResult<()>
+    where F: Fn(Sequence, &ClassDef) -> u16
+{
+    let input_count: u16 = rule.input.len().checked_into("ContextRule", "input glyph count")?;
+    let lookup_count: u16 = rule.lookups.len().checked_into("ContextRule", "lookup count")?;
+
+    // the first input glyph is implied by the coverage table (format 1) or by the rule set
+    // (format 2), so it isn't written into the rule.
+    let input = rule.input.iter().skip(1);
+
+    if chained {
+        let backtrack_count: u16 =
+            rule.backtrack.len().checked_into("ContextRule", "backtrack glyph count")?;
+        let lookahead_count: u16 =
+            rule.lookahead.len().checked_into("ContextRule", "lookahead glyph count")?;
+
+        buf.append(&backtrack_count)?;
+
+        for cls in rule.backtrack.iter().rev() {
+            buf.append(&id_for(Sequence::Backtrack, cls))?;
+        }
+
+        buf.append(&input_count)?;
+
+        for cls in input {
+            buf.append(&id_for(Sequence::Input, cls))?;
+        }
+
+        buf.append(&lookahead_count)?;
+
+        for cls in &rule.lookahead {
+            buf.append(&id_for(Sequence::Lookahead, cls))?;
+        }
+
+        buf.append(&lookup_count)?;
+    } else {
+        buf.append(&input_count)?;
+        buf.append(&lookup_count)?;
+
+        for cls in input {
+            buf.append(&id_for(Sequence::Input, cls))?;
+        }
+    }
+
+    for lookup in &rule.lookups {
+        buf.append(lookup)?;
+    }
+
+    Ok(())
+}
+
+// rule set offsets are written at `offsets_at`, relative to the start of the subtable (which is
+// always the start of `buf`). empty rule sets get a NULL offset.
+fn encode_rule_sets<'a, I, F>(buf: &mut EncodeBuf, mut offsets_at: usize, sets: I, chained: bool,
+    id_for: &F) -> EncodeResult<()>
+
+    where I: Iterator<Item = &'a Vec<&'a ContextRule>>,
+          F: Fn(Sequence, &ClassDef) -> u16
+{
+    for set in sets {
+        if !set.is_empty() {
+            let set_start = buf.bytes.len();
+            let set_offset: u16 = set_start.checked_into("ContextRuleSet", "offset")?;
+
+            let rule_count: u16 = set.len().checked_into("ContextRuleSet", "rule count")?;
+            buf.append(&rule_count)?;
+
+            let mut rule_offset_at = buf.bytes.len();
+            buf.reserve_bytes(set.len() * u16::PACKED_LEN);
+
+            for rule in set {
+                let rule_offset: u16 = (buf.bytes.len() - set_start)
+                    .checked_into("ContextRuleSet", "rule offset")?;
+
+                encode_rule(buf, rule, chained, id_for)?;
+
+                buf.encode_at(&rule_offset, rule_offset_at)?;
+                rule_offset_at += u16::PACKED_LEN;
+            }
+
+            buf.encode_at(&set_offset, offsets_at)?;
+        }
+
+        offsets_at += u16::PACKED_LEN;
+    }
+
+    Ok(())
+}
+
+fn reserve_coverage_offsets<'a, I>(buf: &mut EncodeBuf, offsets: &mut Vec<(usize, &'a ClassDef)>,
+    classes: I)
+    where I: Iterator<Item = &'a ClassDef>
+{
+    for cls in classes {
+        offsets.push((buf.bytes.len(), cls));
+        buf.reserve_bytes(u16::PACKED_LEN);
+    }
+}
+
+impl ChainContext {
+    fn new_scratch_buf(optimize_filesize: bool) -> EncodeBuf {
+        let mut buf = EncodeBuf::new();
+        buf.should_optimize_filesize = optimize_filesize;
+        buf
+    }
+
+    // format 1: rules keyed by individual glyph ids. only possible if every position of every
+    // rule is a single glyph.
+    fn encode_format_1(&self, chained: bool, optimize_filesize: bool) -> EncodeResult<EncodeBuf> {
+        let mut buf = Self::new_scratch_buf(optimize_filesize);
+
+        let mut sets: BTreeMap<u16, Vec<&ContextRule>> = BTreeMap::new();
+
+        for rule in &self.rules {
+            sets.entry(first_glyph(&rule.input[0]))
+                .or_default()
+                .push(rule);
+        }
+
+        buf.reserve_bytes(ContextFormat1Header::PACKED_LEN + (sets.len() * u16::PACKED_LEN));
+
+        encode_rule_sets(&mut buf, ContextFormat1Header::PACKED_LEN, sets.values(), chained,
+            &|_, cls| first_glyph(cls))?;
+
+        let header = ContextFormat1Header {
+            format: 1,
+            coverage_offset: CoverageLookup::<()>::encode(sets.keys().copied(), &mut buf)?
+                .checked_into("ChainContext", "coverage offset")?,
+            rule_set_count: sets.len().checked_into("ChainContext", "rule set count")?
+        };
+
+        buf.encode_at(&header, 0)?;
+        Ok(buf)
+    }
+
+    // format 2: rules keyed by glyph class ids.
+    fn encode_format_2(&self, classes: &SequenceClasses, chained: bool, optimize_filesize: bool)
+            -> EncodeResult<EncodeBuf> {
+        let mut buf = Self::new_scratch_buf(optimize_filesize);
+
+        // one rule set for every input class, plus one for class 0.
+        let mut sets: Vec<Vec<&ContextRule>> = vec![Vec::new(); classes.input.len() + 1];
+        let mut coverage = CoverageLookup::<()>::new();
+
+        for rule in &self.rules {
+            let first = &rule.input[0];
+
+            for glyph in first.iter() {
+                coverage.insert(*glyph, ());
+            }
+
+            sets[classes.class_id(Sequence::Input, first) as usize].push(rule);
+        }
+
+        let header_len = if chained {
+            ChainContextFormat2Header::PACKED_LEN
+        } else {
+            ContextFormat2Header::PACKED_LEN
+        };
+
+        buf.reserve_bytes(header_len + (sets.len() * u16::PACKED_LEN));
+
+        encode_rule_sets(&mut buf, header_len, sets.iter(), chained,
+            &|seq, cls| classes.class_id(seq, cls))?;
+
+        let rule_set_count: u16 = sets.len().checked_into("ChainContext", "rule set count")?;
+        let coverage_offset: u16 = buf.append(&coverage)?
+            .checked_into("ChainContext", "coverage offset")?;
+
+        let input_class_def_offset: u16 = classes.input.ttf_encode(&mut buf, false)?
+            .checked_into("ChainContext", "input class def offset")?;
+
+        if chained {
+            let header = ChainContextFormat2Header {
+                format: 2,
+                coverage_offset,
+
+                backtrack_class_def_offset: classes.backtrack.ttf_encode(&mut buf, false)?
+                    .checked_into("ChainContext", "backtrack class def offset")?,
+                input_class_def_offset,
+                lookahead_class_def_offset: classes.lookahead.ttf_encode(&mut buf, false)?
+                    .checked_into("ChainContext", "lookahead class def offset")?,
+
+                rule_set_count
+            };
+
+            buf.encode_at(&header, 0)?;
+        } else {
+            let header = ContextFormat2Header {
+                format: 2,
+                coverage_offset,
+                class_def_offset: input_class_def_offset,
+                rule_set_count
+            };
+
+            buf.encode_at(&header, 0)?;
+        }
+
+        Ok(buf)
+    }
+
+    // format 3: a coverage table for every position, which means a separate subtable per rule.
+    fn encode_format_3(rule: &ContextRule, chained: bool, optimize_filesize: bool)
+            -> EncodeResult<EncodeBuf> {
+        let mut buf = Self::new_scratch_buf(optimize_filesize);
+        let mut coverage_offsets = Vec::new();
+
+        let input_count: u16 = rule.input.len().checked_into("ContextRule", "input glyph count")?;
+        let lookup_count: u16 = rule.lookups.len().checked_into("ContextRule", "lookup count")?;
+
+        buf.append(&3u16)?;
+
+        if chained {
+            let backtrack_count: u16 =
+                rule.backtrack.len().checked_into("ContextRule", "backtrack glyph count")?;
+            let lookahead_count: u16 =
+                rule.lookahead.len().checked_into("ContextRule", "lookahead glyph count")?;
+
+            buf.append(&backtrack_count)?;
+            reserve_coverage_offsets(&mut buf, &mut coverage_offsets, rule.backtrack.iter().rev());
+
+            buf.append(&input_count)?;
+            reserve_coverage_offsets(&mut buf, &mut coverage_offsets, rule.input.iter());
+
+            buf.append(&lookahead_count)?;
+            reserve_coverage_offsets(&mut buf, &mut coverage_offsets, rule.lookahead.iter());
+
+            buf.append(&lookup_count)?;
+        } else {
+            buf.append(&input_count)?;
+            buf.append(&lookup_count)?;
+            reserve_coverage_offsets(&mut buf, &mut coverage_offsets, rule.input.iter());
+        }
+
+        for lookup in &rule.lookups {
+            buf.append(lookup)?;
+        }
+
+        // the same class will often appear at several positions, so share the coverage tables.
+        let mut encoded: HashMap<&ClassDef, u16> = HashMap::new();
+
+        for (offset_at, cls) in coverage_offsets {
+            let offset = match encoded.get(cls) {
+                Some(offset) => *offset,
+                None => {
+                    let offset: u16 =
+                        CoverageLookup::<()>::encode(cls.iter().copied(), &mut buf)?
+                        .checked_into("ChainContext", "coverage offset")?;
+
+                    encoded.insert(cls, offset);
+                    offset
+                }
+            };
+
+            buf.encode_at(&offset, offset_at)?;
+        }
+
+        Ok(buf)
+    }
+
+    fn encode_smallest(&self, chained: bool, optimize_filesize: bool)
+            -> EncodeResult<Vec<EncodeBuf>> {
+        let mut candidates: Vec<Vec<EncodeBuf>> = vec![
+            self.rules.iter()
+                .map(|rule| Self::encode_format_3(rule, chained, optimize_filesize))
+                .collect::<EncodeResult<_>>()?
+        ];
+
+        if !self.rules.is_empty() && self.rules.iter().all(ContextRule::is_all_single_glyphs) {
+            candidates.push(vec![self.encode_format_1(chained, optimize_filesize)?]);
+        }
+
+        if !self.rules.is_empty() {
+            if let Some(classes) = SequenceClasses::for_rules(&self.rules) {
+                candidates.push(vec![self.encode_format_2(&classes, chained, optimize_filesize)?]);
+            }
+        }
+
+        // every subtable also costs an offset in the lookup. ties go to the earlier candidate,
+        // so format 3 wins unless one of the others is actually smaller.
+        Ok(candidates.into_iter()
+            .min_by_key(|subtables| subtables.iter()
+                .map(|st| st.bytes.len() + u16::PACKED_LEN)
+                .sum::<usize>())
+            .unwrap_or_default())
+    }
+}
+
+pub struct ChainContextEncoder<'a> {
+    subtable: &'a ChainContext,
+    chained: bool,
+
+    encoded: Option<vec::IntoIter<EncodeBuf>>
+}
+
+impl<'a> TTFSubtableEncoder<'a> for ChainContextEncoder<'a> {
+    fn encode_next_subtable(&mut self, buf: &mut EncodeBuf) -> Option<EncodeResult<usize>> {
+        if self.encoded.is_none() {
+            match self.subtable.encode_smallest(self.chained, buf.should_optimize_filesize()) {
+                Ok(encoded) => self.encoded = Some(encoded.into_iter()),
+                Err(e) => return Some(Err(e))
+            }
+        }
+
+        self.encoded.as_mut()?
+            .next()
+            .map(|subtable| buf.append(&subtable))
+    }
+}
+
+impl<'a> TTFSubtableEncode<'a> for ChainContext {
+    type Encoder = ChainContextEncoder<'a>;
+
+    fn ttf_subtable_encoder(&'a self) -> Self::Encoder {
+        ChainContextEncoder {
+            subtable: self,
+            chained: true,
+
+            encoded: None
+        }
+    }
+}
+
+// view used to encode a lookup in which no rule has any backtrack or lookahead as the smaller,
+// non-chained context lookup type.
+#[derive(Debug)]
+pub struct UnchainedContext<'a>(&'a ChainContext);
+
+impl<'a> TTFSubtableEncode<'a> for UnchainedContext<'a> {
+    type Encoder = ChainContextEncoder<'a>;
+
+    fn ttf_subtable_encoder(&'a self) -> Self::Encoder {
+        ChainContextEncoder {
+            subtable: self.0,
+            chained: false,
+
+            encoded: None
+        }
+    }
+}
+
+impl Lookup<ChainContext> {
+    pub fn ttf_encode_with_context_lookup_types(&self, buf: &mut EncodeBuf,
+        context_type: u16, chain_context_type: u16, extension_type: u16) -> EncodeResult<usize> {
+
+        if self.subtables.iter().any(ChainContext::is_chained) {
+            return self.ttf_encode_with_lookup_type(buf, chain_context_type, extension_type);
+        }
+
+        let unchained = Lookup {
+            lookup_flags: self.lookup_flags,
+            mark_filtering_set: self.mark_filtering_set,
+
+            use_extension: self.use_extension,
+
+            subtables: self.subtables.iter()
+                .map(UnchainedContext)
+                .collect()
+        };
+
+        unchained.ttf_encode_with_lookup_type(buf, context_type, extension_type)
+    }
+}
@@ -1,13 +1,16 @@
 use std::ops;
-use std::collections::BTreeSet;
+use std::collections::{
+    BTreeSet,
+    BTreeMap
+};
 
 use endian_codec::{PackedSize, EncodeBE, DecodeBE};
 
 use crate::glyph_class::*;
 use crate::glyph_order::*;
 
-use crate::compile_model::compiler_state::*;
 use crate::compile_model::util::encode::*;
+use crate::compile_model::util::*;
 use crate::compile_model::error::*;
 
 use crate::util::*;
@@ -90,12 +93,12 @@ impl ClassDef {
         let header = Format1Header {
             format: 1,
             start_glyph_id: first,
-            glyph_count: last - first + 1
+            glyph_count: glyph_span(first, last).checked_into("ClassDefFormat1", "glyph count")?
         };
 
         buf.append(&header)?;
 
-        for id in first..(last + 1) {
+        for id in first..=last {
             if self.0.contains(&id) {
                 buf.append(&1u16)?;
             } else {
@@ -106,21 +109,22 @@ impl ClassDef {
         Ok(start)
     }
 
-    fn encode_format_2(buf: &mut EncodeBuf, records: &[(u16, ops::Range<u16>)]) -> EncodeResult<usize> {
+    // `records` are (class id, (first glyph, last glyph)), both ends inclusive.
+    fn encode_format_2(buf: &mut EncodeBuf, records: &[(u16, (u16, u16))]) -> EncodeResult<usize> {
         let start = buf.bytes.len();
 
         let header = Format2Header {
             format: 2,
-            class_range_count: records.len() as u16
+            class_range_count: records.len().checked_into("ClassDefFormat2", "class range count")?
         };
 
         buf.append(&header)?;
 
-        for (class_id, range) in records {
+        for &(class_id, (start_glyph_id, end_glyph_id)) in records {
             let range_record = ClassRangeRecord {
-                start_glyph_id: range.start,
-                end_glyph_id: range.end,
-                class_id: *class_id
+                start_glyph_id,
+                end_glyph_id,
+                class_id
             };
 
             buf.append(&range_record)?;
@@ -133,7 +137,7 @@ impl ClassDef {
         let (first, last) = self.first_and_last_glyphs();
 
         Format1Header::PACKED_LEN
-            + ((last - first + 1) as usize * u16::PACKED_LEN)
+            + (glyph_span(first, last) * u16::PACKED_LEN)
     }
 
     fn format_2_size(range_count: usize) -> usize {
@@ -157,7 +161,7 @@ impl TTFEncode for ClassDef {
         let ranges: Vec<_> = self.0.iter()
             .map(|x| *x)
             .contiguous_ranges()
-            .map(|(start, end)| (1u16, (start..end)))
+            .map(|range| (1u16, range))
             .collect();
 
         if self.format_1_size() < Self::format_2_size(ranges.len()) {
@@ -168,6 +172,54 @@ impl TTFEncode for ClassDef {
     }
 }
 
+// encodes an arbitrary glyph id -> class id mapping (such as the GDEF glyph class and mark
+// attachment class definitions), in whichever format ends up smaller.
+pub fn encode_class_mapping(buf: &mut EncodeBuf, classes: &BTreeMap<u16, u16>) -> EncodeResult<usize> {
+    let mut ranges: Vec<(u16, (u16, u16))> = Vec::new();
+
+    for (&glyph, &class_id) in classes {
+        match ranges.last_mut() {
+            Some((id, (_, end))) if *id == class_id && *end as usize + 1 == glyph as usize =>
+                *end = glyph,
+
+            _ => ranges.push((class_id, (glyph, glyph)))
+        }
+    }
+
+    let (first, last) = classes.keys().next()
+        .zip(classes.keys().next_back())
+        .map(|(a, b)| (*a, *b))
+        .unwrap_or((0, 0));
+
+    let format_1_size = Format1Header::PACKED_LEN
+        + (glyph_span(first, last) * u16::PACKED_LEN);
+
+    if classes.is_empty() || format_1_size >= ClassDef::format_2_size(ranges.len()) {
+        return ClassDef::encode_format_2(buf, &ranges);
+    }
+
+    let start = buf.bytes.len();
+
+    buf.append(&Format1Header {
+        format: 1,
+        start_glyph_id: first,
+        glyph_count: glyph_span(first, last).checked_into("ClassDefFormat1", "glyph count")?
+    })?;
+
+    for id in first..=last {
+        buf.append(classes.get(&id).unwrap_or(&0))?;
+    }
+
+    Ok(start)
+}
+
+// number of glyphs from `first` to `last` inclusive, which doesn't fit in a u16 for a class
+// spanning every glyph id.
+#[inline]
+fn glyph_span(first: u16, last: u16) -> usize {
+    last as usize - first as usize + 1
+}
+
 // separate trait because we need the additional `use_zero_class` parameter
 pub trait ClassDefTTFEncode {
     fn ttf_encode(&self, buf: &mut EncodeBuf, use_zero_class: bool) -> EncodeResult<usize>;
@@ -198,7 +250,7 @@ impl<'a, T> ClassDefTTFEncode for T
             cls.iter()
                 .map(|x| *x)
                 .contiguous_ranges()
-                .map(|(start, end)| (cls_id as u16 + 1, (start..end)))
+                .map(|range| (cls_id as u16 + 1, range))
                 .for_each(|r| ranges.push(r));
         }
 
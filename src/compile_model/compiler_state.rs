@@ -1,20 +1,38 @@
-use std::collections::HashMap;
+use std::collections::{
+    HashMap,
+    BTreeMap
+};
 use std::convert::TryInto;
 
 use crate::GlyphOrder;
+use crate::FeatureTag;
 
 use crate::glyph_class::*;
 
+use crate::compile_model::util::*;
+use crate::compile_model::class_def::*;
+use crate::compile_model::script_list::LanguageSystem;
 use crate::compile_model::error::*;
 use crate::parse_model as pm;
 
 use super::tables::gpos::Anchor;
+use super::tables::gdef::GlyphType;
 use super::tables;
 
 
 pub type MarkClassGlyphClass = (GlyphClass, Anchor);
 pub type MarkClassData = Vec<MarkClassGlyphClass>;
-pub type NamedGlyphClassTable = HashMap<GlyphClassName, GlyphClass>;
+pub type NamedValueRecordTable = HashMap<pm::ValueRecordName, pm::ValueRecord>;
+pub use crate::glyph_class::NamedGlyphClassTable;
+
+// `aalt` is only built once everything else has been compiled, from its own single and alternate
+// substitutions followed by those of the features it references, in the order they're referenced.
+#[derive(Default)]
+pub struct AaltFeature {
+    pub language_systems: Vec<LanguageSystem>,
+    pub features: Vec<FeatureTag>,
+    pub alternates: BTreeMap<u16, Vec<u16>>
+}
 
 pub struct CompilerState {
     pub glyph_order: GlyphOrder,
@@ -22,6 +40,15 @@ pub struct CompilerState {
     pub head: Option<tables::Head>,
     pub gpos: Option<tables::GPOS>,
     pub gsub: Option<tables::GSUB>,
+    pub gdef: Option<tables::GDEF>,
+    pub name: Option<tables::Name>,
+    pub hhea: Option<tables::HheaOverrides>,
+    pub vhea: Option<tables::VheaOverrides>,
+    pub os2: Option<tables::Os2Overrides>,
+
+    // from `languagesystem` statements, in order. feature blocks register their lookups under all
+    // of these unless narrowed down with `script` and `language`.
+    pub language_systems: Vec<LanguageSystem>,
 
     // Note: All mark class definition statements must precede any use of a mark class in the
     // feature file. Once any position statement has referenced a mark class, no more mark
@@ -31,6 +58,18 @@ pub struct CompilerState {
 
     pub anchor_table: HashMap<pm::AnchorName, Anchor>,
     pub glyph_class_table: NamedGlyphClassTable,
+    pub value_record_table: NamedValueRecordTable,
+
+    // referenced by `lookupflag MarkAttachmentType` and `lookupflag UseMarkFilteringSet`
+    // respectively, in order of first reference. both end up in GDEF.
+    pub mark_attach_classes: Vec<ClassDef>,
+    pub mark_glyph_sets: Vec<ClassDef>,
+
+    // glyph classes as used by mark attachment lookups. only used for GDEF when there's no
+    // explicit `GlyphClassDef`.
+    pub inferred_glyph_classes: BTreeMap<u16, GlyphType>,
+
+    pub aalt: Option<AaltFeature>,
 }
 
 pub struct CompilerOutput {
@@ -38,7 +77,12 @@ pub struct CompilerOutput {
 
     pub head: Option<tables::Head>,
     pub gpos: Option<tables::GPOS>,
-    pub gsub: Option<tables::GSUB>
+    pub gsub: Option<tables::GSUB>,
+    pub gdef: Option<tables::GDEF>,
+    pub name: Option<tables::Name>,
+    pub hhea: Option<tables::HheaOverrides>,
+    pub vhea: Option<tables::VheaOverrides>,
+    pub os2: Option<tables::Os2Overrides>
 }
 
 impl From<CompilerState> for CompilerOutput {
@@ -49,6 +93,11 @@ impl From<CompilerState> for CompilerOutput {
             head: state.head,
             gpos: state.gpos,
             gsub: state.gsub,
+            gdef: state.gdef,
+            name: state.name,
+            hhea: state.hhea,
+            vhea: state.vhea,
+            os2: state.os2
         }
     }
 }
@@ -61,12 +110,27 @@ impl CompilerState {
             head: None,
             gpos: None,
             gsub: None,
+            gdef: None,
+            name: None,
+            hhea: None,
+            vhea: None,
+            os2: None,
+
+            language_systems: Vec::new(),
 
             mark_class_statements_allowed: true,
             mark_class_table: HashMap::new(),
 
             anchor_table: HashMap::new(),
-            glyph_class_table: HashMap::new(),
+            glyph_class_table: NamedGlyphClassTable::new(),
+            value_record_table: HashMap::new(),
+
+            mark_attach_classes: Vec::new(),
+            mark_glyph_sets: Vec::new(),
+
+            inferred_glyph_classes: BTreeMap::new(),
+
+            aalt: None,
         }
     }
 
@@ -81,4 +145,46 @@ impl CompilerState {
             anchor => anchor.try_into()
         }
     }
+
+    pub fn mark_attach_class_id(&mut self, class: ClassDef) -> CompileResult<u16> {
+        // class 0 is every glyph which isn't in a mark attachment class.
+        if let Some(idx) = self.mark_attach_classes.iter().position(|c| *c == class) {
+            return Ok(idx as u16 + 1);
+        }
+
+        // unlike mark glyph sets, a glyph can only have a single mark attachment class.
+        let conflict = self.mark_attach_classes.iter()
+            .flat_map(|c| c.iter())
+            .find(|glyph| class.contains(glyph));
+
+        if let Some(glyph) = conflict {
+            return Err(CompileError::MarkAttachClassConflict(*glyph));
+        }
+
+        let id: u8 = (self.mark_attach_classes.len() + 1)
+            .checked_into("GDEF", "mark attachment class")?;
+
+        self.mark_attach_classes.push(class);
+        Ok(id.into())
+    }
+
+    pub fn mark_glyph_set_index(&mut self, set: ClassDef) -> CompileResult<u16> {
+        if let Some(idx) = self.mark_glyph_sets.iter().position(|s| *s == set) {
+            return Ok(idx as u16);
+        }
+
+        let idx = CheckedInto::<u16, CompileError>::checked_into(self.mark_glyph_sets.len(),
+            "GDEF", "mark glyph set")?;
+
+        self.mark_glyph_sets.push(set);
+        Ok(idx)
+    }
+
+    pub fn infer_glyph_class(&mut self, glyph_class: &GlyphClass, glyph_type: GlyphType) -> CompileResult<()> {
+        for glyph in glyph_class.iter_glyphs(&self.glyph_order, &self.glyph_class_table) {
+            self.inferred_glyph_classes.insert(glyph?, glyph_type);
+        }
+
+        Ok(())
+    }
 }
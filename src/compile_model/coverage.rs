@@ -45,7 +45,7 @@ fn decode_coverage<'a>(bytes: &'a [u8]) -> DecodeResult<impl Iterator<Item = u16
     Ok(glyphs_iter)
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct CoverageLookup<T>(pub BTreeMap<u16, T>);
 
 impl<T> Default for CoverageLookup<T> {
@@ -88,6 +88,27 @@ impl<T> CoverageLookup<T> {
     }
 }
 
+impl<T: Clone> CoverageLookup<T> {
+    // divides the covered glyphs into two halves of (nearly) equal size, for splitting a subtable
+    // which has grown too large. returns None if there's fewer than two glyphs to divide.
+    pub fn split(&self) -> Option<(Self, Self)> {
+        if self.len() < 2 {
+            return None
+        }
+
+        let mid = self.len() / 2;
+        let halves = (
+            self.iter().take(mid),
+            self.iter().skip(mid)
+        );
+
+        Some((
+            CoverageLookup(halves.0.map(|(glyph, v)| (*glyph, v.clone())).collect()),
+            CoverageLookup(halves.1.map(|(glyph, v)| (*glyph, v.clone())).collect())
+        ))
+    }
+}
+
 impl<Any> CoverageLookup<Any> {
     #[inline]
     fn format_1_size<'a, I>(iter: &'a I) -> usize
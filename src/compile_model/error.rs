@@ -16,7 +16,9 @@ pub enum CompileError {
         ty: &'static str,
         scope: String,
         item: &'static str,
-        value: MaybePositioned<isize>
+
+        // boxed, since the span would otherwise make every CompileResult carry a large error.
+        value: Box<MaybePositioned<isize>>
     },
 
     #[error("mark classes cannot be defined or amended after first reference in a position statement")]
@@ -28,12 +30,40 @@ pub enum CompileError {
     #[error("unknown glyph class \"{0}\"")]
     UnknownGlyphClass(String),
 
+    #[error("glyph class definitions form a cycle: {0}")]
+    GlyphClassCycle(String),
+
+    #[error("invalid glyph range [{start} - {end}]: {reason}")]
+    InvalidGlyphRange {
+        start: String,
+        end: String,
+        reason: &'static str
+    },
+
     #[error("tried to compile an invalid anchor type {0}")]
     InvalidAnchor(&'static str),
 
+    #[error("substitution expected {0} replacement glyphs, but found {1}")]
+    SubstitutionLengthMismatch(usize, usize),
+
+    #[error("glyph {0} is already in a different mark attachment class")]
+    MarkAttachClassConflict(u16),
+
+    #[error("glyph {0} was already assigned to a different class in GlyphClassDef")]
+    GlyphClassDefConflict(u16),
+
+    #[error("\"{0}\" statements are not allowed in standalone lookup blocks")]
+    NotAllowedInLookupBlock(&'static str),
+
+    #[error("\"{0}\" statements are only allowed in {1}")]
+    OnlyAllowedIn(&'static str, &'static str),
+
     #[error("undefined {0} {1}")]
     UndefinedReference(&'static str, String),
 
+    #[error("{1} is not a valid {0}")]
+    InvalidValue(&'static str, usize),
+
     #[error(transparent)]
     GlyphOrderError(#[from] GlyphOrderError),
 
@@ -73,5 +103,11 @@ pub enum EncodeError {
     TagNotInFeatureList(&'static str, FeatureTag),
 
     #[error("tried to encode a {0}, but the buffer was too small")]
-    BufferTooSmallForType(&'static str)
+    BufferTooSmallForType(&'static str),
+
+    #[error("couldn't decode the font's existing {0} table: {1}")]
+    ExistingTable(&'static str, DecodeError),
+
+    #[error("{1} is not a valid {0}")]
+    InvalidValue(&'static str, usize)
 }
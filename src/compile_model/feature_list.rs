@@ -1,40 +1,52 @@
-use std::collections::BTreeMap;
+use std::collections::{
+    BTreeMap,
+    BTreeSet
+};
 
 use endian_codec::{PackedSize, EncodeBE, DecodeBE};
 
+use crate::compile_model::script_list::ScriptList;
+use crate::compile_model::feature_params::FeatureParams;
 use crate::compile_model::util::decode::*;
 use crate::compile_model::util::encode::*;
 
 use crate::FeatureTag;
 
-type LookupIndices = Vec<u16>;
+pub type LookupIndices = Vec<u16>;
+pub type FeatureParamsTable = BTreeMap<FeatureTag, FeatureParams>;
 
+// the same feature tag can appear more than once, when language systems don't all share the same
+// lookups for it. the feature list is derived from the script list when encoding, since that's
+// where the lookups for each language system are tracked. feature params are per tag, so every
+// feature with that tag points at the same ones.
 #[derive(Debug)]
-pub struct FeatureList(pub BTreeMap<FeatureTag, LookupIndices>);
+pub struct FeatureList {
+    pub features: Vec<(FeatureTag, LookupIndices)>,
+    pub params: FeatureParamsTable
+}
 
 impl FeatureList {
     pub fn new() -> Self {
-        Self(BTreeMap::new())
-    }
-
-    #[inline]
-    pub fn indices_for_tag(&self, tag: &FeatureTag) -> &[u16] {
-        match self.0.get(tag) {
-            Some(i) => i,
-            None => &[]
+        Self {
+            features: Vec::new(),
+            params: BTreeMap::new()
         }
     }
 
-    #[inline]
-    pub fn indices_for_tag_mut(&mut self, tag: &FeatureTag) -> &mut LookupIndices {
-        self.0.entry(*tag)
-            .or_default()
-    }
+    pub fn from_script_list(script_list: &ScriptList, params: &FeatureParamsTable) -> Self {
+        // feature records have to be sorted by tag, and a BTreeSet also takes care of merging
+        // identical features from different language systems.
+        let features: BTreeSet<_> = script_list.iter_lang_sys()
+            .flat_map(|(_, lang_sys)| lang_sys.features.iter())
+            .collect();
 
-    #[inline]
-    pub fn add_lookup_index(&mut self, tag: &FeatureTag, index: u16) {
-        let indices = self.indices_for_tag_mut(tag);
-        indices.push(index);
+        Self {
+            features: features.into_iter()
+                .map(|(tag, lookup_indices)| (*tag, lookup_indices.clone()))
+                .collect(),
+
+            params: params.clone()
+        }
     }
 }
 
@@ -42,27 +54,43 @@ impl TTFDecode for FeatureList {
     #[inline]
     fn ttf_decode(bytes: &[u8]) -> DecodeResult<Self> {
         let records_count = decode_u16_be(bytes, 0);
-        let records = decode_from_pool(records_count, &bytes[2..]);
+        let records = decode_from_pool::<FeatureRecord>(records_count, &bytes[2..]);
+
+        let mut list = Self::new();
 
-        let features = records.map(|r: FeatureRecord| {
+        for r in records {
             let table = &bytes[r.feature_offset as usize..];
-            let lookup_index_count = decode_u16_be(table, 2);
+            let header: FeatureTable = decode_from_slice(table);
+
+            let params = Some(header.params)
+                .filter(|offset| *offset != 0)
+                .and_then(|offset| FeatureParams::ttf_decode(&r.tag, &table[offset as usize..]));
+
+            if let Some(params) = params {
+                list.params.insert(r.tag, params);
+            }
 
-            (r.tag,
-             decode_from_pool(lookup_index_count, &table[4..])
-                .collect())
-        });
+            list.features.push((r.tag,
+                decode_from_pool(header.lookup_index_count, &table[4..])
+                    .collect()));
+        }
 
-        Ok(Self(features.collect()))
+        Ok(list)
     }
 }
 
-fn encode_feature_table(buf: &mut EncodeBuf, lookup_indices: &[u16]) -> EncodeResult<usize>
+fn encode_feature_table(buf: &mut EncodeBuf, lookup_indices: &[u16],
+    params: Option<&FeatureParams>) -> EncodeResult<usize>
 {
     let start = buf.bytes.len();
 
+    // the params come straight after the lookup indices.
+    let params_offset = params
+        .map(|_| FeatureTable::PACKED_LEN + (lookup_indices.len() * u16::PACKED_LEN))
+        .unwrap_or(0);
+
     let header = FeatureTable {
-        params: 0,
+        params: params_offset as u16,
         lookup_index_count: lookup_indices.len() as u16
     };
 
@@ -72,22 +100,27 @@ fn encode_feature_table(buf: &mut EncodeBuf, lookup_indices: &[u16]) -> EncodeRe
         buf.append(lookup_index)?;
     }
 
+    if let Some(params) = params {
+        params.ttf_encode(buf)?;
+    }
+
     Ok(start)
 }
 
 impl TTFEncode for FeatureList {
     fn ttf_encode(&self, buf: &mut EncodeBuf) -> EncodeResult<usize> {
         let start = buf.bytes.len();
-        let len = self.0.len();
+        let len = self.features.len();
 
         buf.append(&(len as u16))?;
 
-        buf.encode_pool(start, self.0.iter(),
-            |feature_offset, &(&tag, _)| FeatureRecord {
-                tag,
+        buf.encode_pool(start, self.features.iter(),
+            |feature_offset, (tag, _)| FeatureRecord {
+                tag: *tag,
                 feature_offset,
             },
-            |buf, &(_, lookup_indices)| encode_feature_table(buf, lookup_indices))?;
+            |buf, (tag, lookup_indices)|
+                encode_feature_table(buf, lookup_indices, self.params.get(tag)))?;
 
         Ok(start)
     }
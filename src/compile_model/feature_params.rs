@@ -0,0 +1,222 @@
+use endian_codec::{PackedSize, EncodeBE, DecodeBE};
+
+use crate::compile_model::util::decode::*;
+use crate::compile_model::util::encode::*;
+use crate::compile_model::util::*;
+
+use crate::*;
+
+// extra data for a handful of features, pointed to by the `params` offset in their feature table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FeatureParams {
+    Size(SizeParams),
+    StylisticSet(StylisticSetParams),
+    CharacterVariant(CharacterVariantParams)
+}
+
+// design size and range are in decipoints. the subfamily name ID is 0 unless the feature has
+// `sizemenuname` entries.
+#[derive(Debug, Default, Clone, PartialEq, Eq, PackedSize, EncodeBE, DecodeBE)]
+pub struct SizeParams {
+    pub design_size: u16,
+    pub subfamily_id: u16,
+    pub subfamily_name_id: u16,
+    pub range_start: u16,
+    pub range_end: u16
+}
+
+// the UI name ID is 0 unless the feature has a `featureNames` block.
+#[derive(Debug, Default, Clone, PartialEq, Eq, PackedSize, EncodeBE, DecodeBE)]
+pub struct StylisticSetParams {
+    pub version: u16,
+    pub ui_name_id: u16
+}
+
+// name IDs are 0 where the `cvParameters` block leaves them out. the named parameters take
+// consecutive name IDs starting at `first_param_ui_label_name_id`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CharacterVariantParams {
+    pub ui_label_name_id: u16,
+    pub tooltip_text_name_id: u16,
+    pub sample_text_name_id: u16,
+    pub num_named_parameters: u16,
+    pub first_param_ui_label_name_id: u16,
+    pub characters: Vec<u32>
+}
+
+#[derive(Debug, PackedSize, EncodeBE, DecodeBE)]
+struct CharacterVariantHeader {
+    format: u16,
+    ui_label_name_id: u16,
+    tooltip_text_name_id: u16,
+    sample_text_name_id: u16,
+    num_named_parameters: u16,
+    first_param_ui_label_name_id: u16,
+    char_count: u16
+}
+
+// characters are stored as uint24, though they only go up to the last Unicode code point.
+const CHARACTER_LEN: usize = 3;
+pub(crate) const MAX_CHARACTER: u32 = 0x10FFFF;
+
+// ss01 through ss20 and cv01 through cv99, where `prefix` is the first two letters.
+fn numbered_feature(tag: &FeatureTag, prefix: &[u8; 2], max: u8) -> bool {
+    let bytes: Vec<u8> = tag.0.iter().map(|ch| ch.as_byte()).collect();
+
+    if bytes[..2] != prefix[..] || !bytes[2..].iter().all(u8::is_ascii_digit) {
+        return false;
+    }
+
+    let n = (bytes[2] - b'0') * 10 + (bytes[3] - b'0');
+    (1..=max).contains(&n)
+}
+
+pub(crate) fn is_stylistic_set(tag: &FeatureTag) -> bool {
+    numbered_feature(tag, b"ss", 20)
+}
+
+pub(crate) fn is_character_variant(tag: &FeatureTag) -> bool {
+    numbered_feature(tag, b"cv", 99)
+}
+
+impl CharacterVariantParams {
+    fn ttf_decode(bytes: &[u8]) -> Self {
+        let header: CharacterVariantHeader = decode_from_slice(bytes);
+        let chars_start = CharacterVariantHeader::PACKED_LEN;
+
+        let characters = (0..header.char_count as usize)
+            .map(|i| {
+                let start = chars_start + i * CHARACTER_LEN;
+                let c = &bytes[start..start + CHARACTER_LEN];
+
+                (c[0] as u32) << 16 | (c[1] as u32) << 8 | c[2] as u32
+            })
+            .collect();
+
+        Self {
+            ui_label_name_id: header.ui_label_name_id,
+            tooltip_text_name_id: header.tooltip_text_name_id,
+            sample_text_name_id: header.sample_text_name_id,
+            num_named_parameters: header.num_named_parameters,
+            first_param_ui_label_name_id: header.first_param_ui_label_name_id,
+            characters
+        }
+    }
+}
+
+impl TTFEncode for CharacterVariantParams {
+    fn ttf_encode(&self, buf: &mut EncodeBuf) -> EncodeResult<usize> {
+        let header = CharacterVariantHeader {
+            format: 0,
+            ui_label_name_id: self.ui_label_name_id,
+            tooltip_text_name_id: self.tooltip_text_name_id,
+            sample_text_name_id: self.sample_text_name_id,
+            num_named_parameters: self.num_named_parameters,
+            first_param_ui_label_name_id: self.first_param_ui_label_name_id,
+            char_count: self.characters.len().checked_into("CharacterVariantParams", "char_count")?
+        };
+
+        let start = buf.append(&header)?;
+
+        for c in &self.characters {
+            if *c > MAX_CHARACTER {
+                return Err(EncodeError::InvalidValue("Unicode character", *c as usize));
+            }
+
+            buf.bytes.extend_from_slice(&c.to_be_bytes()[1..]);
+        }
+
+        Ok(start)
+    }
+}
+
+impl FeatureParams {
+    // there's nothing in the params themselves to say which kind they are, so we go by the tag of
+    // the feature they belong to. params for any other feature are skipped.
+    pub fn ttf_decode(tag: &FeatureTag, bytes: &[u8]) -> Option<Self> {
+        match tag {
+            feature_tag!(s,i,z,e) => Some(FeatureParams::Size(decode_from_slice(bytes))),
+
+            tag if is_stylistic_set(tag) =>
+                Some(FeatureParams::StylisticSet(decode_from_slice(bytes))),
+            tag if is_character_variant(tag) =>
+                Some(FeatureParams::CharacterVariant(CharacterVariantParams::ttf_decode(bytes))),
+
+            _ => None
+        }
+    }
+
+    // every name ID that the params refer to, leaving out the zeroes for names they don't have.
+    pub(crate) fn name_ids(&self) -> Vec<u16> {
+        let ids = match self {
+            FeatureParams::Size(params) => vec![params.subfamily_name_id],
+            FeatureParams::StylisticSet(params) => vec![params.ui_name_id],
+
+            FeatureParams::CharacterVariant(params) => {
+                let named_parameters = (0..params.num_named_parameters)
+                    .map(|i| params.first_param_ui_label_name_id + i);
+
+                [params.ui_label_name_id, params.tooltip_text_name_id, params.sample_text_name_id]
+                    .iter()
+                    .copied()
+                    .chain(named_parameters)
+                    .collect()
+            }
+        };
+
+        ids.into_iter()
+            .filter(|id| *id != 0)
+            .collect()
+    }
+
+    fn with_name_id_offset(&self, offset: u16) -> EncodeResult<Self> {
+        let name_id = |id: u16| -> EncodeResult<u16> {
+            match id {
+                0 => Ok(0),
+                id => (id as usize + offset as usize).checked_into("FeatureParams", "name ID")
+            }
+        };
+
+        Ok(match self {
+            FeatureParams::Size(params) =>
+                FeatureParams::Size(SizeParams {
+                    subfamily_name_id: name_id(params.subfamily_name_id)?,
+                    ..params.clone()
+                }),
+
+            FeatureParams::StylisticSet(params) =>
+                FeatureParams::StylisticSet(StylisticSetParams {
+                    ui_name_id: name_id(params.ui_name_id)?,
+                    ..params.clone()
+                }),
+
+            FeatureParams::CharacterVariant(params) =>
+                FeatureParams::CharacterVariant(CharacterVariantParams {
+                    ui_label_name_id: name_id(params.ui_label_name_id)?,
+                    tooltip_text_name_id: name_id(params.tooltip_text_name_id)?,
+                    sample_text_name_id: name_id(params.sample_text_name_id)?,
+                    first_param_ui_label_name_id: name_id(params.first_param_ui_label_name_id)?,
+                    ..params.clone()
+                })
+        })
+    }
+}
+
+impl TTFEncode for FeatureParams {
+    fn ttf_encode(&self, buf: &mut EncodeBuf) -> EncodeResult<usize> {
+        let moved;
+        let params =
+            if buf.name_id_offset != 0 {
+                moved = self.with_name_id_offset(buf.name_id_offset)?;
+                &moved
+            } else {
+                self
+            };
+
+        match params {
+            FeatureParams::Size(params) => params.ttf_encode(buf),
+            FeatureParams::StylisticSet(params) => params.ttf_encode(buf),
+            FeatureParams::CharacterVariant(params) => params.ttf_encode(buf)
+        }
+    }
+}
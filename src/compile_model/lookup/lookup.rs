@@ -27,6 +27,9 @@ pub struct Lookup<T> {
     pub lookup_flags: LookupFlags,
     pub mark_filtering_set: Option<u16>,
 
+    // extension lookups reach their subtables through 32-bit offsets (GSUB type 7, GPOS type 9).
+    pub use_extension: bool,
+
     pub subtables: Vec<T>,
 }
 
@@ -36,6 +39,8 @@ impl<T> Lookup<T> {
             lookup_flags: LookupFlags::empty(),
             mark_filtering_set: None,
 
+            use_extension: false,
+
             subtables: Vec::new()
         }
     }
@@ -133,9 +138,23 @@ struct LookupTableHeader {
     pub subtable_count: u16
 }
 
-impl<T: TTFDecode> TTFDecode for Lookup<T> {
-    #[inline]
-    fn ttf_decode(bytes: &[u8]) -> DecodeResult<Self> {
+#[derive(Debug, PackedSize, EncodeBE, DecodeBE)]
+struct ExtensionSubtableHeader {
+    format: u16,
+    extension_lookup_type: u16,
+    extension_offset: u32
+}
+
+// the lookup type of an extension lookup's subtables, which has to be the same for all of them.
+pub fn extension_lookup_type(bytes: &[u8]) -> u16 {
+    let first_subtable = decode_u16_be(bytes, LookupTableHeader::PACKED_LEN) as usize;
+    decode_u16_be(bytes, first_subtable + u16::PACKED_LEN)
+}
+
+impl<T: TTFDecode> Lookup<T> {
+    fn ttf_decode_subtables<F>(bytes: &[u8], use_extension: bool, subtable_start: F) -> DecodeResult<Self>
+        where F: Fn(usize) -> usize
+    {
         let header = decode_from_slice::<LookupTableHeader>(bytes);
 
         let lookup_flags = LookupFlags::from_bits_truncate(header.lookup_flags);
@@ -143,7 +162,7 @@ impl<T: TTFDecode> TTFDecode for Lookup<T> {
         let subtables =
             decode_from_pool(header.subtable_count, &bytes[LookupTableHeader::PACKED_LEN..])
             .map(|offset: u16|
-                T::ttf_decode(&bytes[offset as usize..]))
+                T::ttf_decode(&bytes[subtable_start(offset as usize)..]))
             .collect::<DecodeResult<_>>()?;
 
         let mark_filtering_set =
@@ -159,48 +178,136 @@ impl<T: TTFDecode> TTFDecode for Lookup<T> {
             lookup_flags,
             mark_filtering_set,
 
+            use_extension,
+
             subtables
         })
     }
+
+    pub fn ttf_decode_extension(bytes: &[u8]) -> DecodeResult<Self> {
+        Self::ttf_decode_subtables(bytes, true, |offset| {
+            let ext: ExtensionSubtableHeader = decode_from_slice(&bytes[offset..]);
+            offset + ext.extension_offset as usize
+        })
+    }
+}
+
+impl<T: TTFDecode> TTFDecode for Lookup<T> {
+    #[inline]
+    fn ttf_decode(bytes: &[u8]) -> DecodeResult<Self> {
+        Self::ttf_decode_subtables(bytes, false, |offset| offset)
+    }
+}
+
+// subtables of extension lookups are kept back and placed after everything else in the table,
+// since the whole point of them is to be out of reach of the 16-bit offsets.
+pub struct ExtensionSubtables {
+    lookup_type: u16,
+    pool: EncodeBuf,
+
+    // (start of the extension subtable, offset of the subtable it points to within `pool`)
+    records: Vec<(usize, usize)>
+}
+
+pub(crate) fn encode_extension_subtables(buf: &mut EncodeBuf) -> EncodeResult<()> {
+    for ext in std::mem::take(&mut buf.extension_subtables) {
+        let pool_start = buf.append(&ext.pool)?;
+
+        for (ext_start, offset) in ext.records {
+            let header = ExtensionSubtableHeader {
+                format: 1,
+                extension_lookup_type: ext.lookup_type,
+                extension_offset: (pool_start + offset - ext_start) as u32
+            };
+
+            buf.encode_at(&header, ext_start)?;
+        }
+    }
+
+    Ok(())
 }
 
 impl<'a, T: TTFSubtableEncode<'a> + ::std::fmt::Debug> Lookup<T> {
-    pub fn ttf_encode_with_lookup_type(&'a self, buf: &mut EncodeBuf, lookup_type: u16) -> EncodeResult<usize> {
+    pub fn ttf_encode_with_lookup_type(&'a self, buf: &mut EncodeBuf, lookup_type: u16,
+        extension_type: u16) -> EncodeResult<usize> {
         let start = buf.bytes.len();
         let mut flags = self.lookup_flags;
 
         flags.set(LookupFlags::USE_MARK_FILTERING_SET,
             self.mark_filtering_set.is_some());
 
+        // subtables are encoded into a separate pool first, since a splitting encoder can produce
+        // more subtables than we have in self.subtables, and we need to know the final count to
+        // lay out the offset array.
+        let mut pool = EncodeBuf::new();
+        pool.should_optimize_filesize = buf.should_optimize_filesize;
+
+        let mut subtable_offsets = Vec::with_capacity(self.subtables.len());
+
+        for subtable in &self.subtables {
+            let mut encoder = subtable.ttf_subtable_encoder();
+
+            while let Some(offset) = encoder.encode_next_subtable(&mut pool) {
+                subtable_offsets.push(offset?);
+            }
+        }
+
         buf.reserve_bytes(LookupTableHeader::PACKED_LEN);
 
-        let mut subtable_offset_start = buf.bytes.len();
-        buf.bytes.resize(subtable_offset_start + (u16::PACKED_LEN * self.subtables.len()), 0u8);
+        let subtable_offset_start = buf.bytes.len();
+        buf.reserve_bytes(u16::PACKED_LEN * subtable_offsets.len());
 
         if let Some(mfs) = self.mark_filtering_set {
             buf.append(&mfs)?;
         }
 
-        let mut subtable_count = 0usize;
+        let use_extension = self.use_extension || buf.use_extension_lookups;
 
-        for subtable in &self.subtables {
-            let mut encoder = subtable.ttf_subtable_encoder();
+        let subtable_starts: Vec<usize> =
+            if use_extension {
+                let mut records = Vec::with_capacity(subtable_offsets.len());
 
-            while let Some(offset) = encoder.encode_next_subtable(buf) {
-                let offset: u16 = (offset? - start)
-                    .checked_into("Lookup", "subtable offset")?;
+                for offset in &subtable_offsets {
+                    // the real offset is only known once the subtables have been placed. until
+                    // then it's a placeholder which is unique within the table, so that the
+                    // lookup list never mistakes two extension lookups for duplicates.
+                    let ext_start = buf.append(&ExtensionSubtableHeader {
+                        format: 1,
+                        extension_lookup_type: lookup_type,
+                        extension_offset: (buf.extension_subtables.len() + 1) as u32
+                    })?;
 
-                buf.encode_at(&offset, subtable_offset_start)?;
+                    records.push((ext_start, *offset));
+                }
 
-                subtable_count += 1;
-                subtable_offset_start += u16::PACKED_LEN;
-            }
+                let starts = records.iter().map(|(ext_start, _)| *ext_start).collect();
+
+                buf.extension_subtables.push(ExtensionSubtables {
+                    lookup_type,
+                    pool,
+                    records
+                });
+
+                starts
+            } else {
+                let pool_start = buf.append(&pool)?;
+
+                subtable_offsets.iter()
+                    .map(|offset| pool_start + offset)
+                    .collect()
+            };
+
+        for (i, subtable_start) in subtable_starts.iter().enumerate() {
+            let offset: u16 = (subtable_start - start)
+                .checked_into("Lookup", "subtable offset")?;
+
+            buf.encode_at(&offset, subtable_offset_start + (i * u16::PACKED_LEN))?;
         }
 
         let header = LookupTableHeader {
-            lookup_type: lookup_type,
-            lookup_flags: self.lookup_flags.bits(),
-            subtable_count: subtable_count
+            lookup_type: if use_extension { extension_type } else { lookup_type },
+            lookup_flags: flags.bits(),
+            subtable_count: subtable_offsets.len()
                 .checked_into("Lookup", "subtable count")?
         };
 
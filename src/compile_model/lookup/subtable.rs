@@ -48,26 +48,62 @@ pub trait TTFSubtableEncoder<'a> {
     fn encode_next_subtable(&mut self, buf: &mut EncodeBuf) -> Option<EncodeResult<usize>>;
 }
 
-// blanket impl for subtables which don't support splitting
-pub struct SingularSubtableEncoder<'a, T: TTFEncode> {
-    subtable: Option<&'a T>
+// subtables which can be divided into two smaller subtables of the same type, used to recover
+// when a subtable grows too large for its 16-bit offsets.
+pub trait TTFSubtableSplit: TTFEncode + Sized {
+    // returns None if the subtable can't be divided any further, e.g. if it only covers a single
+    // glyph.
+    fn split(&self) -> Option<(Self, Self)>;
 }
 
-impl<'a, T: TTFEncode + 'a> TTFSubtableEncode<'a> for T {
-    type Encoder = SingularSubtableEncoder<'a, T>;
+// blanket impl for subtables which are split in half whenever encoding overflows an offset.
+// halving doesn't always give the fewest possible subtables, but it needs no knowledge of the
+// encoded layout of each subtable type.
+pub struct SplittingSubtableEncoder<'a, T: TTFSubtableSplit> {
+    subtable: Option<&'a T>,
+
+    // halves still to be encoded, with the next one on top of the stack
+    pending: Vec<T>
+}
+
+impl<'a, T: TTFSubtableSplit + 'a> TTFSubtableEncode<'a> for T {
+    type Encoder = SplittingSubtableEncoder<'a, T>;
 
     #[inline]
     fn ttf_subtable_encoder(&'a self) -> Self::Encoder {
-        SingularSubtableEncoder {
-            subtable: Some(self)
+        SplittingSubtableEncoder {
+            subtable: Some(self),
+            pending: Vec::new()
         }
     }
 }
 
-impl<'a, T: TTFEncode> TTFSubtableEncoder<'a> for SingularSubtableEncoder<'a, T> {
-    #[inline]
+impl<'a, T: TTFSubtableSplit> TTFSubtableEncoder<'a> for SplittingSubtableEncoder<'a, T> {
     fn encode_next_subtable(&mut self, buf: &mut EncodeBuf) -> Option<EncodeResult<usize>> {
-        self.subtable.take()
-            .map(|st| st.ttf_encode(buf))
+        loop {
+            let popped;
+            let subtable = match self.subtable.take() {
+                Some(subtable) => subtable,
+                None => {
+                    popped = self.pending.pop()?;
+                    &popped
+                }
+            };
+
+            let start = buf.bytes.len();
+            let res = subtable.ttf_encode(buf);
+
+            if let Err(EncodeError::U16Overflow { .. }) = res {
+                if let Some((first, second)) = subtable.split() {
+                    buf.truncate(start);
+
+                    self.pending.push(second);
+                    self.pending.push(first);
+                    continue;
+                }
+            }
+
+            return Some(res);
+        }
     }
 }
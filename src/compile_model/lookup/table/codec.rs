@@ -5,6 +5,8 @@ use endian_codec::{PackedSize, DecodeBE};
 use crate::compile_model::util::decode::*;
 use crate::compile_model::util::encode::*;
 
+use crate::compile_model::feature_list::FeatureList;
+
 use super::*;
 use super::header::*;
 
@@ -25,10 +27,12 @@ impl<L: TTFDecode> TTFDecode for LookupTable<L> {
         let feature_bytes = &bytes[offsets.feature..];
         let lookup_bytes = &bytes[offsets.lookup..];
 
+        let feature_list = FeatureList::ttf_decode(feature_bytes)?;
+
         Ok(LookupTable {
-            script_list: ScriptList::ttf_decode(script_bytes, feature_bytes)?,
-            feature_list: FeatureList::ttf_decode(feature_bytes)?,
+            script_list: ScriptList::ttf_decode(script_bytes, &feature_list)?,
             lookup_list: LookupList::ttf_decode(lookup_bytes)?,
+            feature_params: feature_list.params,
             feature_variations: offsets.feature_variations,
 
             named_lookups: HashMap::new()
@@ -46,13 +50,17 @@ impl<L: TTFEncode> TTFEncode for LookupTable<L> {
         let start = buf.bytes.len();
         buf.bytes.resize(header_size, 0u8);
 
+        let feature_list = FeatureList::from_script_list(&self.script_list, &self.feature_params);
+
         let offsets = Offsets {
-            script: self.script_list.ttf_encode(buf, &self.feature_list)?,
-            feature: self.feature_list.ttf_encode(buf)?,
+            script: self.script_list.ttf_encode(buf, &feature_list)?,
+            feature: feature_list.ttf_encode(buf)?,
             lookup: self.lookup_list.ttf_encode(buf)?,
             feature_variations: None
         };
 
+        encode_extension_subtables(buf)?;
+
         let header: Header_1_0 = offsets.into();
         buf.encode_at(&header, start)?;
 
@@ -1,11 +1,14 @@
-use std::collections::HashMap;
+use std::collections::{
+    BTreeMap,
+    HashMap
+};
 
 use crate::*;
 
 use crate::compile_model::util::decode::*;
 use crate::compile_model::util::encode::*;
-use crate::compile_model::feature_list::*;
 use crate::compile_model::script_list::*;
+use crate::compile_model::feature_list::FeatureParamsTable;
 use crate::compile_model::lookup::*;
 
 use crate::parse_model::LookupName;
@@ -19,8 +22,8 @@ mod codec;
 pub struct LookupTable<L: Sized>
 {
     pub script_list: ScriptList,
-    pub feature_list: FeatureList,
     pub lookup_list: LookupList<L>,
+    pub feature_params: FeatureParamsTable,
     pub feature_variations: Option<usize>,
 
     pub named_lookups: HashMap<LookupName, Vec<u16>>
@@ -30,50 +33,137 @@ impl<L> LookupTable<L> {
     pub fn new() -> Self {
         Self {
             script_list: ScriptList::new(),
-            feature_list: FeatureList::new(),
             lookup_list: LookupList::new(),
+            feature_params: BTreeMap::new(),
             feature_variations: None,
 
             named_lookups: HashMap::new()
         }
     }
 
-    pub fn lookup_index_for_type<T, I>(&self, indices: I) -> Option<usize>
+    // rules are only ever added to the current (i.e. most recently added) lookup, and only when
+    // its flags are identical, so a `lookupflag` statement which changes the flags starts a new
+    // lookup even if an earlier one had the same flags.
+    pub fn lookup_index_for_type<T, I>(&self, indices: I, flags: LookupFlags,
+        mark_filtering_set: Option<u16>) -> Option<usize>
         where T: LookupSubtable<L>,
               I: IntoIterator<Item = usize>
     {
-        for i in indices {
-            if let Some(_) = self.lookup_list.0.get(i).map(T::get_lookup_variant) {
-                return Some(i);
+        indices.into_iter()
+            .find(|i| {
+                self.lookup_list.0.get(*i)
+                    .and_then(T::get_lookup_variant)
+                    .map(|lookup| lookup.lookup_flags == flags
+                        && lookup.mark_filtering_set == mark_filtering_set)
+                    .unwrap_or(false)
+            })
+    }
+
+    pub fn lookup<T>(&self, idx: u16) -> Option<&Lookup<T>>
+        where T: LookupSubtable<L>
+    {
+        self.lookup_list.0.get(idx as usize)
+            .and_then(T::get_lookup_variant)
+    }
+
+    pub fn lookup_mut<T>(&mut self, idx: u16) -> Option<&mut Lookup<T>>
+        where T: LookupSubtable<L>
+    {
+        self.lookup_list.0.get_mut(idx as usize)
+            .and_then(T::get_lookup_variant_mut)
+    }
+
+    // anonymous lookups aren't referenced by any feature or named lookup block. they only exist
+    // to be referenced by index from contextual rules.
+    pub fn insert_anonymous_lookup<T>(&mut self, flags: LookupFlags,
+        mark_filtering_set: Option<u16>) -> (u16, &mut Lookup<T>)
+        where T: LookupSubtable<L>
+    {
+        let idx = self.lookup_list.0.len();
+        self.lookup_list.0.push(T::new_lookup());
+
+        (idx as u16, self.lookup_with_flags_mut(idx, flags, mark_filtering_set))
+    }
+
+    fn lookup_with_flags_mut<T>(&mut self, idx: usize, flags: LookupFlags,
+        mark_filtering_set: Option<u16>) -> &mut Lookup<T>
+        where T: LookupSubtable<L>
+    {
+        // unwrap() is fine here since we've either already succeeded with T::get_lookup_variant()
+        // in find_lookup() or T::new_lookup() has inserted a valid lookup.
+        //
+        // it's possible for T::new_lookup() to create a lookup which is not then matched by
+        // T::get_lookup_variant_mut(), but that's a programmer error that the panic from unwrap
+        // will direct the programmer to fix the issue.
+        let lookup = T::get_lookup_variant_mut(&mut self.lookup_list.0[idx]).unwrap();
+
+        lookup.lookup_flags = flags;
+        lookup.mark_filtering_set = mark_filtering_set;
+
+        lookup
+    }
+
+    pub fn is_named_lookup(&self, idx: u16) -> bool {
+        self.named_lookups.values()
+            .any(|indices| indices.contains(&idx))
+    }
+}
+
+// implemented by lookup types which refer to other lookups by index, i.e. contextual lookups.
+pub trait OffsetLookupIndices {
+    fn offset_lookup_indices(&mut self, offset: u16);
+}
+
+impl<L: OffsetLookupIndices> LookupTable<L> {
+    // the new lookups take the first indices in the lookup list, so everything which refers to an
+    // existing lookup by index has to be shifted along to match.
+    pub fn prepend_lookups(&mut self, lookups: Vec<L>) {
+        let offset = lookups.len() as u16;
+
+        let feature_indices = self.script_list.iter_lang_sys_mut()
+            .flat_map(|lang_sys| lang_sys.features.values_mut());
+
+        for indices in feature_indices.chain(self.named_lookups.values_mut()) {
+            for idx in indices.iter_mut() {
+                *idx += offset;
             }
         }
 
-        None
+        for lookup in self.lookup_list.0.iter_mut() {
+            lookup.offset_lookup_indices(offset);
+        }
+
+        self.lookup_list.0.splice(0..0, lookups);
     }
 }
 
 pub trait KeyedLookups<K, L> {
-    fn find_lookup<T>(&mut self, lookup_key: &K) -> Option<usize>
+    fn find_lookup<T>(&mut self, lookup_key: &K, flags: LookupFlags,
+        mark_filtering_set: Option<u16>) -> Option<usize>
         where T: LookupSubtable<L>;
 
-    fn find_or_insert_lookup<'a, T>(&'a mut self, lookup_key: &K) -> &'a mut Lookup<T>
+    fn find_or_insert_lookup<'a, T>(&'a mut self, lookup_key: &K, flags: LookupFlags,
+        mark_filtering_set: Option<u16>) -> &'a mut Lookup<T>
         where T: LookupSubtable<L>;
 }
 
 impl<L> KeyedLookups<LookupName, L> for LookupTable<L> {
-    fn find_lookup<T>(&mut self, lookup_name: &LookupName) -> Option<usize>
+    fn find_lookup<T>(&mut self, lookup_name: &LookupName, flags: LookupFlags,
+        mark_filtering_set: Option<u16>) -> Option<usize>
         where T: LookupSubtable<L>
     {
         self.named_lookups.get(lookup_name)
             .and_then(|indices| {
-                self.lookup_index_for_type::<T, _> (indices.iter().map(|x| *x as usize))
+                self.lookup_index_for_type::<T, _> (indices.last().map(|x| *x as usize),
+                    flags, mark_filtering_set)
             })
     }
 
-    fn find_or_insert_lookup<'a, T>(&'a mut self, lookup_name: &LookupName) -> &'a mut Lookup<T>
+    fn find_or_insert_lookup<'a, T>(&'a mut self, lookup_name: &LookupName, flags: LookupFlags,
+        mark_filtering_set: Option<u16>) -> &'a mut Lookup<T>
         where T: LookupSubtable<L>
     {
-        let idx = match self.find_lookup::<T>(lookup_name) {
+        let idx = match self.find_lookup::<T>(lookup_name, flags, mark_filtering_set) {
             Some(idx) => idx,
             None => {
                 let idx = self.lookup_list.0.len();
@@ -87,46 +177,75 @@ impl<L> KeyedLookups<LookupName, L> for LookupTable<L> {
             }
         };
 
-        // unwrap() is fine here since we've either already succeeded with T::get_lookup_variant()
-        // in find_lookup() or T::new_lookup() has inserted a valid lookup.
-        //
-        // it's possible for T::new_lookup() to create a lookup which is not then matched by
-        // T::get_lookup_variant_mut(), but that's a programmer error that the panic from unwrap
-        // will direct the programmer to fix the issue.
-        T::get_lookup_variant_mut(&mut self.lookup_list.0[idx]).unwrap()
+        self.lookup_with_flags_mut(idx, flags, mark_filtering_set)
     }
 }
 
-impl<L> KeyedLookups<FeatureTag, L> for LookupTable<L> {
-    fn find_lookup<T>(&mut self, feature_tag: &FeatureTag) -> Option<usize>
+// a feature's lookups are registered under every language system in the scope. for a feature block
+// that's every `languagesystem` to begin with, narrowed down by `script` and `language` statements.
+pub struct FeatureScope<'a> {
+    pub tag: &'a FeatureTag,
+    pub language_systems: &'a [LanguageSystem]
+}
+
+impl<L> LookupTable<L> {
+    // registers the feature even if it never gets any lookups, which is the case for features
+    // like `size` which only exist for their params.
+    pub fn add_feature(&mut self, scope: &FeatureScope) {
+        for language_system in scope.language_systems {
+            self.script_list.lang_sys_mut(language_system)
+                .features
+                .entry(*scope.tag)
+                .or_default();
+        }
+    }
+
+    pub fn add_feature_lookup(&mut self, scope: &FeatureScope, lookup_index: u16) {
+        for language_system in scope.language_systems {
+            self.script_list.lang_sys_mut(language_system)
+                .features
+                .entry(*scope.tag)
+                .or_default()
+                .push(lookup_index);
+        }
+    }
+}
+
+impl<'s, L> KeyedLookups<FeatureScope<'s>, L> for LookupTable<L> {
+    fn find_lookup<T>(&mut self, scope: &FeatureScope<'s>, flags: LookupFlags,
+        mark_filtering_set: Option<u16>) -> Option<usize>
         where T: LookupSubtable<L>
     {
-        self.lookup_index_for_type::<T, _>(
-            self.feature_list.indices_for_tag(feature_tag).iter()
-                .map(|x| *x as usize))
+        // the current lookup can only take more rules if doing so won't leak them into language
+        // systems outside of the current scope, or into a named lookup referenced by the feature.
+        let current = scope.language_systems.first()
+            .and_then(|language_system| self.script_list.lang_sys(language_system))
+            .and_then(|lang_sys| lang_sys.features.get(scope.tag))
+            .and_then(|lookup_indices| lookup_indices.last())
+            .filter(|idx| !self.is_named_lookup(**idx))
+            .filter(|idx| self.script_list.lookup_is_exclusive_to(scope.tag, **idx,
+                    scope.language_systems))
+            .map(|idx| *idx as usize);
+
+        self.lookup_index_for_type::<T, _>(current, flags, mark_filtering_set)
     }
 
-    fn find_or_insert_lookup<'a, T>(&'a mut self, feature_tag: &FeatureTag) -> &'a mut Lookup<T>
+    fn find_or_insert_lookup<'a, T>(&'a mut self, scope: &FeatureScope<'s>, flags: LookupFlags,
+        mark_filtering_set: Option<u16>) -> &'a mut Lookup<T>
         where T: LookupSubtable<L>
     {
-        let idx = match self.find_lookup::<T>(feature_tag) {
+        let idx = match self.find_lookup::<T>(scope, flags, mark_filtering_set) {
             Some(idx) => idx,
             None => {
                 let idx = self.lookup_list.0.len();
 
-                self.feature_list.add_lookup_index(feature_tag, idx as u16);
+                self.add_feature_lookup(scope, idx as u16);
                 self.lookup_list.0.push(T::new_lookup());
 
                 idx
             }
         };
 
-        // unwrap() is fine here since we've either already succeeded with T::get_lookup_variant()
-        // in find_lookup() or T::new_lookup() has inserted a valid lookup.
-        //
-        // it's possible for T::new_lookup() to create a lookup which is not then matched by
-        // T::get_lookup_variant_mut(), but that's a programmer error that the panic from unwrap
-        // will direct the programmer to fix the issue.
-        T::get_lookup_variant_mut(&mut self.lookup_list.0[idx]).unwrap()
+        self.lookup_with_flags_mut(idx, flags, mark_filtering_set)
     }
 }
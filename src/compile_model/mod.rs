@@ -2,6 +2,13 @@
 
 pub mod tables;
 
+mod chain_context;
+pub use chain_context::{
+    ChainContext,
+    ContextRule,
+    SequenceLookup
+};
+
 mod class_def;
 pub use class_def::ClassDef;
 
@@ -18,6 +25,21 @@ mod feature_list;
 pub use feature_list::{
     FeatureList,
     FeatureRecord,
+    FeatureParamsTable,
+};
+
+mod feature_params;
+pub use feature_params::{
+    FeatureParams,
+    SizeParams,
+    StylisticSetParams,
+    CharacterVariantParams,
+};
+
+pub(crate) use feature_params::{
+    is_stylistic_set,
+    is_character_variant,
+    MAX_CHARACTER
 };
 
 pub mod lookup;
@@ -33,6 +55,8 @@ mod script_list;
 pub use script_list::{
     ScriptList,
     Script,
+    LangSys,
+    LanguageSystem,
 };
 
 #[macro_use]
@@ -52,7 +76,10 @@ mod source_map;
 pub use source_map::*;
 
 pub(crate) mod compiler_state;
-pub(crate) use compiler_state::CompilerState;
+pub(crate) use compiler_state::{
+    CompilerState,
+    AaltFeature
+};
 pub use compiler_state::CompilerOutput;
 
 mod encoded_tables;
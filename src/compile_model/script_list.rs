@@ -1,6 +1,6 @@
 use std::collections::{
     HashMap,
-    BTreeSet
+    BTreeMap
 };
 
 use endian_codec::{PackedSize, EncodeBE, DecodeBE};
@@ -8,7 +8,7 @@ use endian_codec::{PackedSize, EncodeBE, DecodeBE};
 use crate::*;
 
 use crate::compile_model::feature_list::{
-    FeatureRecord,
+    LookupIndices,
     FeatureList
 };
 
@@ -16,12 +16,42 @@ use crate::compile_model::util::decode::*;
 use crate::compile_model::util::encode::*;
 use crate::compile_model::util::*;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LanguageSystem {
+    pub script: ScriptTag,
+    pub language: LanguageTag
+}
+
+impl LanguageSystem {
+    pub fn new(script: ScriptTag, language: LanguageTag) -> Self {
+        Self {
+            script,
+            language
+        }
+    }
+
+    pub fn default_for_script(script: ScriptTag) -> Self {
+        Self::new(script, language_tag!(d,f,l,t))
+    }
+
+    #[inline]
+    pub fn is_default_language(&self) -> bool {
+        self.language == language_tag!(d,f,l,t)
+    }
+}
+
+impl Default for LanguageSystem {
+    fn default() -> Self {
+        Self::default_for_script(script_tag!(D,F,L,T))
+    }
+}
+
 #[derive(Debug)]
-pub struct ScriptList(HashMap<ScriptTag, Script>);
+pub struct ScriptList(BTreeMap<ScriptTag, Script>);
 
 impl ScriptList {
     pub fn new() -> Self {
-        Self(HashMap::new())
+        Self(BTreeMap::new())
     }
 
     #[inline]
@@ -33,26 +63,101 @@ impl ScriptList {
     pub fn script_for_tag_mut(&mut self, tag: &ScriptTag) -> &mut Script {
         self.0.entry(*tag)
             .or_insert_with(|| Script {
-                default_lang_sys: LangSys {
-                    required_feature: None,
-                    features: BTreeSet::new()
-                },
+                default_lang_sys: LangSys::new(),
+                lang_sys: BTreeMap::new()
+            })
+    }
+
+    pub fn lang_sys(&self, language_system: &LanguageSystem) -> Option<&LangSys> {
+        let script = self.script_for_tag(&language_system.script)?;
+
+        if language_system.is_default_language() {
+            Some(&script.default_lang_sys)
+        } else {
+            script.lang_sys.get(&language_system.language)
+        }
+    }
+
+    pub fn lang_sys_mut(&mut self, language_system: &LanguageSystem) -> &mut LangSys {
+        let script = self.script_for_tag_mut(&language_system.script);
+
+        if language_system.is_default_language() {
+            &mut script.default_lang_sys
+        } else {
+            script.lang_sys.entry(language_system.language)
+                .or_insert_with(LangSys::new)
+        }
+    }
 
-                lang_sys: HashMap::new()
+    pub fn iter_lang_sys(&self) -> impl Iterator<Item = (LanguageSystem, &LangSys)> {
+        self.0.iter()
+            .flat_map(|(script_tag, script)| {
+                let script_tag = *script_tag;
+
+                std::iter::once((LanguageSystem::default_for_script(script_tag),
+                        &script.default_lang_sys))
+                    .chain(script.lang_sys.iter()
+                        .map(move |(language, lang_sys)|
+                            (LanguageSystem::new(script_tag, *language), lang_sys)))
             })
     }
+
+    pub fn iter_lang_sys_mut(&mut self) -> impl Iterator<Item = &mut LangSys> {
+        self.0.values_mut()
+            .flat_map(|script| std::iter::once(&mut script.default_lang_sys)
+                .chain(script.lang_sys.values_mut()))
+    }
+
+    // whether `lookup` is used by `feature` in exactly the given language systems and no others.
+    // only then can more rules be added to the lookup without affecting other language systems.
+    pub fn lookup_is_exclusive_to(&self, feature: &FeatureTag, lookup: u16,
+        language_systems: &[LanguageSystem]) -> bool {
+        let mut count = 0;
+
+        for (language_system, lang_sys) in self.iter_lang_sys() {
+            let uses_lookup = lang_sys.features.get(feature)
+                .map(|lookups| lookups.contains(&lookup))
+                .unwrap_or(false);
+
+            if !uses_lookup {
+                continue;
+            }
+
+            if !language_systems.contains(&language_system) {
+                return false;
+            }
+
+            count += 1;
+        }
+
+        count == language_systems.len()
+    }
 }
 
 #[derive(Debug)]
 pub struct Script {
     pub default_lang_sys: LangSys,
-    pub lang_sys: HashMap<Tag, LangSys>
+    pub lang_sys: BTreeMap<LanguageTag, LangSys>
 }
 
 #[derive(Debug)]
 pub struct LangSys {
     pub required_feature: Option<FeatureTag>,
-    pub features: BTreeSet<FeatureTag>
+    pub features: BTreeMap<FeatureTag, LookupIndices>
+}
+
+impl LangSys {
+    pub fn new() -> Self {
+        Self {
+            required_feature: None,
+            features: BTreeMap::new()
+        }
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.features.is_empty()
+    }
 }
 
 #[derive(Debug, PackedSize, EncodeBE, DecodeBE)]
@@ -69,7 +174,7 @@ struct ScriptTable {
 
 #[derive(Debug, PackedSize, EncodeBE, DecodeBE)]
 struct LangSysRecord {
-    tag: Tag,
+    tag: LanguageTag,
     lang_sys_offset: u16
 }
 
@@ -80,66 +185,72 @@ struct LangSysTable {
     feature_index_count: u16
 }
 
-type FeatureIndexToTag = HashMap<u16, FeatureTag>;
-type TagToFeatureIndex = HashMap<FeatureTag, u16>;
+type FeatureIndexToFeature<'a> = &'a [(FeatureTag, LookupIndices)];
+type FeatureToFeatureIndex<'a> = HashMap<(&'a FeatureTag, &'a LookupIndices), u16>;
 
 impl LangSys {
-    fn ttf_decode(bytes: &[u8], feature_index_to_tag: &FeatureIndexToTag) -> DecodeResult<Self> {
+    fn ttf_decode(bytes: &[u8], features: FeatureIndexToFeature) -> DecodeResult<Self> {
         let table: LangSysTable = decode_from_slice(bytes);
 
+        let feature = |field, idx: u16| {
+            features.get(idx as usize)
+                .cloned()
+                .ok_or(DecodeError::UndefinedFeature(field, idx))
+        };
+
         let required_feature =
             match table.required_feature_index {
                 0xFFFF => None,
-                idx => {
-                    let tag = feature_index_to_tag.get(&idx)
-                        .ok_or_else(||
-                            DecodeError::UndefinedFeature("LangSys.required_feature_index", idx))?;
-
-                    Some(tag.clone())
-                }
+                idx => Some(feature("LangSys.required_feature_index", idx)?)
             };
 
-        decode_from_pool(table.feature_index_count, &bytes[LangSysTable::PACKED_LEN..])
-            .map(|feature_index: u16| {
-                Ok(feature_index_to_tag.get(&feature_index)
-                    .ok_or_else(||
-                        DecodeError::UndefinedFeature("LangSys.features", feature_index))?
-                    .clone())
-            })
-            .collect::<DecodeResult<_>>()
-            .map(|features| LangSys {
-                required_feature,
-                features,
-            })
+        let mut lang_sys = LangSys {
+            required_feature: required_feature.as_ref().map(|(tag, _)| *tag),
+            features: decode_from_pool(table.feature_index_count,
+                    &bytes[LangSysTable::PACKED_LEN..])
+                .map(|feature_index: u16| feature("LangSys.features", feature_index))
+                .collect::<DecodeResult<_>>()?
+        };
+
+        if let Some((tag, lookup_indices)) = required_feature {
+            lang_sys.features.insert(tag, lookup_indices);
+        }
+
+        Ok(lang_sys)
     }
 }
 
 impl LangSys {
-    fn ttf_encode(&self, buf: &mut EncodeBuf, tag_to_feature_index: &TagToFeatureIndex) -> EncodeResult<usize> {
+    fn ttf_encode(&self, buf: &mut EncodeBuf, feature_indices: &FeatureToFeatureIndex) -> EncodeResult<usize> {
         let start = buf.bytes.len();
 
-        let required_feature_index = self.required_feature
-            .map(|tag| tag_to_feature_index.get(&tag)
-                .ok_or_else(||
-                    EncodeError::TagNotInFeatureList("LangSys.required_feature", tag.clone()))
-                .map(|idx| *idx))
+        let feature_index = |field, tag: &FeatureTag| {
+            self.features.get(tag)
+                .and_then(|lookup_indices| feature_indices.get(&(tag, lookup_indices)))
+                .copied()
+                .ok_or(EncodeError::TagNotInFeatureList(field, *tag))
+        };
+
+        let required_feature_index = self.required_feature.as_ref()
+            .map(|tag| feature_index("LangSys.required_feature", tag))
             .unwrap_or(Ok(0xFFFF))?;
 
+        // the required feature is only referenced through required_feature_index.
+        let features: Vec<_> = self.features.keys()
+            .filter(|tag| Some(*tag) != self.required_feature.as_ref())
+            .collect();
+
         let table = LangSysTable {
             lookup_order: 0,
             required_feature_index,
             feature_index_count:
-                self.features.len().checked_into("LangSys", "feature_index_count")?,
+                features.len().checked_into("LangSys", "feature_index_count")?,
         };
 
         buf.append(&table)?;
 
-        for tag in &self.features {
-            let idx = tag_to_feature_index.get(&tag)
-                .ok_or_else(||
-                    EncodeError::TagNotInFeatureList("LangSys.features", tag.clone()))?;
-
-            buf.append(idx)?;
+        for tag in features {
+            buf.append(&feature_index("LangSys.features", tag)?)?;
         }
 
         Ok(start)
@@ -147,19 +258,21 @@ impl LangSys {
 }
 
 impl Script {
-    fn ttf_decode(bytes: &[u8], feature_index_to_tag: &FeatureIndexToTag) -> DecodeResult<Self> {
+    fn ttf_decode(bytes: &[u8], features: FeatureIndexToFeature) -> DecodeResult<Self> {
         let table: ScriptTable = decode_from_slice(bytes);
 
         let lang_sys_records = decode_from_pool(
             table.lang_sys_count,
             &bytes[ScriptTable::PACKED_LEN..]);
 
-        let default_lang_sys = LangSys::ttf_decode(&bytes[table.default_lang_sys as usize..],
-            feature_index_to_tag)?;
+        let default_lang_sys = match table.default_lang_sys {
+            0 => LangSys::new(),
+            offset => LangSys::ttf_decode(&bytes[offset as usize..], features)?
+        };
 
         let lang_sys = lang_sys_records
             .map(|lsr: LangSysRecord|
-                LangSys::ttf_decode(&bytes[lsr.lang_sys_offset as usize..], feature_index_to_tag)
+                LangSys::ttf_decode(&bytes[lsr.lang_sys_offset as usize..], features)
                     .map(|sys| (lsr.tag, sys)))
             .collect::<DecodeResult<_>>()?;
 
@@ -171,59 +284,53 @@ impl Script {
 }
 
 impl Script {
-    fn ttf_encode(&self, buf: &mut EncodeBuf, tag_to_feature_index: &TagToFeatureIndex) -> EncodeResult<usize> {
+    fn ttf_encode(&self, buf: &mut EncodeBuf, feature_indices: &FeatureToFeatureIndex) -> EncodeResult<usize> {
         let start = buf.bytes.len();
 
-        buf.bytes.resize(start + ScriptTable::PACKED_LEN, 0u8);
-
-        let table = ScriptTable {
-            default_lang_sys:
-                (&self.default_lang_sys.ttf_encode(buf, tag_to_feature_index)? - start)
-                    .checked_into("ScriptTable", "default_lang_sys")?,
-            lang_sys_count:
-                self.lang_sys.len().checked_into("ScriptTable", "lang_sys_count")?
-        };
-
-        buf.encode_at(&table, start)?;
-
-        for (tag, lang_sys) in &self.lang_sys {
-            let record = LangSysRecord {
-                tag: *tag,
-                lang_sys_offset:
-                    (lang_sys.ttf_encode(buf, tag_to_feature_index)? - start)
-                        .checked_into(format!("LangSysRecord[{}]", tag), "lang_sys_offset")?,
-            };
-
-            buf.append(&record)?;
-        }
+        let lang_sys: Vec<_> = self.lang_sys.iter()
+            .filter(|(_, lang_sys)| !lang_sys.is_empty())
+            .collect();
+
+        buf.encode_pool_with_header(
+            |buf| Ok(ScriptTable {
+                // a script which only has non-default language systems has no default LangSys.
+                default_lang_sys:
+                    if self.default_lang_sys.is_empty() {
+                        0
+                    } else {
+                        (self.default_lang_sys.ttf_encode(buf, feature_indices)? - start)
+                            .checked_into("ScriptTable", "default_lang_sys")?
+                    },
+
+                lang_sys_count: lang_sys.len()
+                    .checked_into("ScriptTable", "lang_sys_count")?
+            }),
+
+            lang_sys.iter(),
+
+            |lang_sys_offset, (tag, _)| LangSysRecord {
+                tag: **tag,
+                lang_sys_offset
+            },
 
-        Ok(start)
+            |buf, (_, lang_sys)| lang_sys.ttf_encode(buf, feature_indices))
     }
 }
 
 impl ScriptList {
     #[inline]
-    pub fn ttf_decode(bytes: &[u8], feature_list_bytes: &[u8]) -> DecodeResult<Self> {
+    // the feature list is needed to map feature indices to features and their lookups.
+    pub fn ttf_decode(bytes: &[u8], feature_list: &FeatureList) -> DecodeResult<Self> {
         let records = decode_from_pool(decode_u16_be(bytes, 0), &bytes[2..]);
 
-        // we need this so we can map feature indices to tags
-        let feature_index_to_tag: FeatureIndexToTag = {
-            let feature_records_count = decode_u16_be(feature_list_bytes, 0);
-
-            decode_from_pool(feature_records_count, &feature_list_bytes[2..])
-                .enumerate()
-                .map(|(i, r): (_, FeatureRecord)| (i as u16, r.tag))
-                .collect()
-        };
-
         records
             .map(|sr: ScriptRecord| {
                 let table_data = &bytes[sr.script_offset as usize..];
 
-                Script::ttf_decode(table_data, &feature_index_to_tag)
+                Script::ttf_decode(table_data, &feature_list.features)
                     .map(|script| (sr.tag, script))
             })
-            .collect::<DecodeResult<HashMap<_, _>>>()
+            .collect::<DecodeResult<BTreeMap<_, _>>>()
             .map(Self)
     }
 }
@@ -232,43 +339,29 @@ impl ScriptList {
     pub fn ttf_encode(&self, buf: &mut EncodeBuf, feature_list: &FeatureList) -> EncodeResult<usize> {
         let start = buf.bytes.len();
 
-        buf.append(
-            &u16::checked_from("ScriptList", "record_count", self.0.len())?)?;
-
-        let tag_to_feature_index: TagToFeatureIndex = 
-            feature_list.0.keys().enumerate()
-                .map(|(i, tag)| (tag.clone(), i as u16))
+        let feature_indices: FeatureToFeatureIndex =
+            feature_list.features.iter().enumerate()
+                .map(|(i, (tag, lookup_indices))| ((tag, lookup_indices), i as u16))
                 .collect();
 
-        // this block is a hack to put the default script tag first
-        // unfortunately, the chain results in an iterator which doesn't implement
-        // ExactSizeIterator, which doesn't work with encode_pool().
-        // might be able to do a hack wrapping struct here.
-        //
-        // let dflt = script_tag!(D,F,L,T);
-        //
-        // let scripts =
-        //     if let Some(script) = self.0.get(&dflt) {
-        //         Either2::A(iter::once((&dflt, script))
-        //             .chain(self.0.iter()
-        //                 .filter_map(|(tag, script)|
-        //                     if tag == &dflt {
-        //                         None
-        //                     } else {
-        //                         Some((tag, script))
-        //                     }))
-        //         )
-        //     } else {
-        //         Either2::B(self.0.iter())
-        //     };
-
-        buf.encode_pool(start, self.0.iter(),
-            |script_offset, &(&tag, _)| ScriptRecord {
-                tag,
+        // scripts without any features at all (e.g. from a `language` statement which excluded
+        // the default lookups and then didn't add any of its own) are left out entirely.
+        let scripts: Vec<_> = self.0.iter()
+            .filter(|(_, script)| !script.default_lang_sys.is_empty()
+                || script.lang_sys.values().any(|lang_sys| !lang_sys.is_empty()))
+            .collect();
+
+        buf.append(
+            &u16::checked_from("ScriptList", "record_count", scripts.len())?)?;
+
+        // script records are sorted by tag, so DFLT always comes first.
+        buf.encode_pool(start, scripts.into_iter(),
+            |script_offset, (tag, _)| ScriptRecord {
+                tag: **tag,
                 script_offset,
             },
-            |buf, &(_, script)|
-                script.ttf_encode(buf, &tag_to_feature_index))?;
+            |buf, (_, script)|
+                script.ttf_encode(buf, &feature_indices))?;
 
         Ok(start)
     }
@@ -3,6 +3,8 @@ use std::collections::{
     BTreeMap,
 };
 
+use crate::FileId;
+
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum CompiledEntry {
@@ -11,4 +13,6 @@ pub enum CompiledEntry {
 
 // FIXME: should be BTreeSet<CompiledEntry> in the case of one source location mapped to multiple
 // compiled representations.
-pub type SourceMap = HashMap<usize, BTreeMap<usize, CompiledEntry>>;
+//
+// keyed by (file, line), then column.
+pub type SourceMap = HashMap<(FileId, usize), BTreeMap<usize, CompiledEntry>>;
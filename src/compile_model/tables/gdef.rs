@@ -1,5 +1,15 @@
+use std::collections::{
+    BTreeMap,
+    BTreeSet
+};
+
 use endian_codec::{PackedSize, EncodeBE, DecodeBE};
 
+use crate::compile_model::util::encode::*;
+use crate::compile_model::class_def::*;
+use crate::compile_model::coverage::*;
+use crate::compile_model::util::*;
+
 #[derive(Debug, Copy, Clone, PackedSize, EncodeBE, DecodeBE)]
 struct Header_1_0 {
     major: u16,
@@ -21,14 +31,258 @@ struct Header_1_2 {
     mark_glyph_sets_def_offset: u16
 }
 
-#[derive(Debug, Copy, Clone, PackedSize, EncodeBE, DecodeBE)]
-struct Header_1_3 {
-    major: u16,
-    minor: u16,
-    glyph_class_def_offset: u16,
-    attach_list_offset: u16,
-    lig_caret_list_offset: u16,
-    mark_attach_class_def_offset: u16,
-    mark_glyph_sets_def_offset: u16,
-    item_var_store_offset: u16
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GlyphType {
+    Base = 1,
+    Ligature = 2,
+    Mark = 3,
+    Component = 4
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CaretValue {
+    Coordinate(i16),
+    ContourPoint(u16)
+}
+
+#[derive(Debug)]
+pub struct GDEF {
+    pub glyph_classes: BTreeMap<u16, GlyphType>,
+    pub attach_points: CoverageLookup<BTreeSet<u16>>,
+    pub ligature_carets: CoverageLookup<Vec<CaretValue>>,
+
+    // class ids start at 1, so the class at index 0 here is mark attachment class 1.
+    pub mark_attach_classes: Vec<ClassDef>,
+    pub mark_glyph_sets: Vec<ClassDef>
+}
+
+impl GDEF {
+    pub fn new() -> Self {
+        Self {
+            glyph_classes: BTreeMap::new(),
+            attach_points: CoverageLookup::new(),
+            ligature_carets: CoverageLookup::new(),
+
+            mark_attach_classes: Vec::new(),
+            mark_glyph_sets: Vec::new()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.glyph_classes.is_empty()
+            && self.attach_points.is_empty()
+            && self.ligature_carets.is_empty()
+            && self.mark_attach_classes.is_empty()
+            && self.mark_glyph_sets.is_empty()
+    }
+}
+
+impl Default for GDEF {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, PackedSize, EncodeBE, DecodeBE)]
+struct AttachListHeader {
+    coverage_offset: u16,
+    glyph_count: u16
+}
+
+#[derive(Debug, PackedSize, EncodeBE, DecodeBE)]
+struct LigCaretListHeader {
+    coverage_offset: u16,
+    lig_glyph_count: u16
+}
+
+#[derive(Debug, PackedSize, EncodeBE, DecodeBE)]
+struct CaretValueFormat1 {
+    format: u16,
+    coordinate: i16
+}
+
+#[derive(Debug, PackedSize, EncodeBE, DecodeBE)]
+struct CaretValueFormat2 {
+    format: u16,
+    caret_value_point_index: u16
+}
+
+#[derive(Debug, PackedSize, EncodeBE, DecodeBE)]
+struct MarkGlyphSetsHeader {
+    format: u16,
+    mark_glyph_set_count: u16
+}
+
+impl TTFEncode for CaretValue {
+    fn ttf_encode(&self, buf: &mut EncodeBuf) -> EncodeResult<usize> {
+        match *self {
+            CaretValue::Coordinate(coordinate) =>
+                buf.append(&CaretValueFormat1 {
+                    format: 1,
+                    coordinate
+                }),
+
+            CaretValue::ContourPoint(caret_value_point_index) =>
+                buf.append(&CaretValueFormat2 {
+                    format: 2,
+                    caret_value_point_index
+                })
+        }
+    }
+}
+
+// subtables which are empty aren't encoded at all, and get a NULL offset in the header.
+fn optional_offset<F>(buf: &mut EncodeBuf, table_start: usize, is_empty: bool, item: &'static str,
+    encode: F) -> EncodeResult<u16>
+    where F: FnOnce(&mut EncodeBuf) -> EncodeResult<usize>
+{
+    if is_empty {
+        return Ok(0);
+    }
+
+    (encode(buf)? - table_start)
+        .checked_into("GDEF", item)
+}
+
+impl GDEF {
+    fn encode_glyph_class_def(&self, buf: &mut EncodeBuf) -> EncodeResult<usize> {
+        let classes = self.glyph_classes.iter()
+            .map(|(glyph, class)| (*glyph, *class as u16))
+            .collect();
+
+        encode_class_mapping(buf, &classes)
+    }
+
+    fn encode_attach_list(&self, buf: &mut EncodeBuf) -> EncodeResult<usize> {
+        let start = buf.bytes.len();
+
+        buf.encode_pool_with_header(
+            |buf| Ok(AttachListHeader {
+                coverage_offset: (buf.append(&self.attach_points)? - start)
+                    .checked_into("AttachList", "coverage_offset")?,
+                glyph_count: self.attach_points.len()
+                    .checked_into("AttachList", "glyph_count")?
+            }),
+
+            self.attach_points.values(),
+
+            |offset, _| offset,
+
+            |buf, points| {
+                let start = buf.bytes.len();
+
+                buf.append(&u16::checked_from("AttachPoint", "point_count", points.len())?)?;
+
+                for point in points.iter() {
+                    buf.append(point)?;
+                }
+
+                Ok(start)
+            })
+    }
+
+    fn encode_lig_caret_list(&self, buf: &mut EncodeBuf) -> EncodeResult<usize> {
+        let start = buf.bytes.len();
+
+        buf.encode_pool_with_header(
+            |buf| Ok(LigCaretListHeader {
+                coverage_offset: (buf.append(&self.ligature_carets)? - start)
+                    .checked_into("LigCaretList", "coverage_offset")?,
+                lig_glyph_count: self.ligature_carets.len()
+                    .checked_into("LigCaretList", "lig_glyph_count")?
+            }),
+
+            self.ligature_carets.values(),
+
+            |offset, _| offset,
+
+            |buf, carets| {
+                let start = buf.bytes.len();
+
+                buf.append(&u16::checked_from("LigGlyph", "caret_count", carets.len())?)?;
+                buf.encode_pool(start, carets.iter(),
+                    |offset, _| offset,
+                    |buf, caret| buf.append(*caret))?;
+
+                Ok(start)
+            })
+    }
+
+    fn encode_mark_attach_class_def(&self, buf: &mut EncodeBuf) -> EncodeResult<usize> {
+        let mut classes = BTreeMap::new();
+
+        for (i, class) in self.mark_attach_classes.iter().enumerate() {
+            let class_id = u16::checked_from("GDEF", "mark attachment class", i + 1)?;
+            classes.extend(class.iter().map(|glyph| (*glyph, class_id)));
+        }
+
+        encode_class_mapping(buf, &classes)
+    }
+
+    fn encode_mark_glyph_sets(&self, buf: &mut EncodeBuf) -> EncodeResult<usize> {
+        let start = buf.bytes.len();
+
+        // unlike nearly everything else, the coverage offsets here are 32-bit.
+        buf.encode_pool_2_with_header(
+            |_| Ok(MarkGlyphSetsHeader {
+                format: 1,
+                mark_glyph_set_count: self.mark_glyph_sets.len()
+                    .checked_into("MarkGlyphSets", "mark_glyph_set_count")?
+            }),
+
+            self.mark_glyph_sets.iter(),
+
+            |coverage_start, _| (coverage_start - start) as u32,
+
+            |buf, set| CoverageLookup::<()>::encode(set.iter().copied(), buf))
+    }
+}
+
+impl TTFEncode for GDEF {
+    fn ttf_encode(&self, buf: &mut EncodeBuf) -> EncodeResult<usize> {
+        let start = buf.bytes.len();
+
+        macro_rules! offset {
+            ($buf:ident, $is_empty:expr, $item:expr, $encode:ident) => {
+                optional_offset($buf, start, $is_empty, $item, |buf| self.$encode(buf))?
+            }
+        }
+
+        // mark glyph sets were added in version 1.2, so only use it if we have to.
+        if self.mark_glyph_sets.is_empty() {
+            buf.defer_header_encode(
+                |buf| Ok(Header_1_0 {
+                    major: 1,
+                    minor: 0,
+                    glyph_class_def_offset: offset!(buf, self.glyph_classes.is_empty(),
+                        "glyph_class_def_offset", encode_glyph_class_def),
+                    attach_list_offset: offset!(buf, self.attach_points.is_empty(),
+                        "attach_list_offset", encode_attach_list),
+                    lig_caret_list_offset: offset!(buf, self.ligature_carets.is_empty(),
+                        "lig_caret_list_offset", encode_lig_caret_list),
+                    mark_attach_class_def_offset: offset!(buf, self.mark_attach_classes.is_empty(),
+                        "mark_attach_class_def_offset", encode_mark_attach_class_def)
+                }),
+
+                |_| Ok(()))
+        } else {
+            buf.defer_header_encode(
+                |buf| Ok(Header_1_2 {
+                    major: 1,
+                    minor: 2,
+                    glyph_class_def_offset: offset!(buf, self.glyph_classes.is_empty(),
+                        "glyph_class_def_offset", encode_glyph_class_def),
+                    attach_list_offset: offset!(buf, self.attach_points.is_empty(),
+                        "attach_list_offset", encode_attach_list),
+                    lig_caret_list_offset: offset!(buf, self.ligature_carets.is_empty(),
+                        "lig_caret_list_offset", encode_lig_caret_list),
+                    mark_attach_class_def_offset: offset!(buf, self.mark_attach_classes.is_empty(),
+                        "mark_attach_class_def_offset", encode_mark_attach_class_def),
+                    mark_glyph_sets_def_offset: offset!(buf, false,
+                        "mark_glyph_sets_def_offset", encode_mark_glyph_sets)
+                }),
+
+                |_| Ok(()))
+        }
+    }
 }
@@ -1,11 +1,12 @@
 use endian_codec::{PackedSize, EncodeBE, DecodeBE};
 
 use crate::compile_model::util::encode::*;
+use crate::compile_model::util::*;
 use crate::compile_model::tables::gpos::*;
 use crate::compile_model::coverage::*;
 
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Anchors {
     entry: Anchor,
     exit: Anchor
@@ -43,8 +44,9 @@ impl TTFEncode for Cursive {
         buf.encode_pool_2_with_header(
             |buf| Ok(CursivePosFormat1Header {
                 format: 1,
-                coverage_offset: (self.0.ttf_encode(buf)? - start) as u16,
-                entry_exit_count: self.0.len() as u16
+                coverage_offset: (self.0.ttf_encode(buf)? - start)
+                    .checked_into("CursivePos", "coverage offset")?,
+                entry_exit_count: self.0.len().checked_into("CursivePos", "entry/exit count")?
             }),
 
             self.0.values(),
@@ -68,9 +70,16 @@ impl TTFEncode for Cursive {
                 };
 
                 Ok((
-                    entry as u16,
-                    exit as u16
+                    entry.checked_into("CursivePos", "entry anchor offset")?,
+                    exit.checked_into("CursivePos", "exit anchor offset")?
                 ))
             })
     }
 }
+
+impl TTFSubtableSplit for Cursive {
+    fn split(&self) -> Option<(Self, Self)> {
+        self.0.split()
+            .map(|(a, b)| (Cursive(a), Cursive(b)))
+    }
+}
@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+
+use endian_codec::{PackedSize, EncodeBE, DecodeBE};
+
+use crate::glyph_class::*;
+use crate::glyph_order::*;
+
+use crate::compile_model::compiler_state::*;
+use crate::compile_model::tables::gpos::*;
+use crate::compile_model::util::encode::*;
+use crate::compile_model::util::*;
+use crate::compile_model::coverage::*;
+use crate::compile_model::error::*;
+
+use crate::parse_model::MarkClassName;
+
+
+// mark-to-base and mark-to-mark subtables have an identical layout. the only difference is
+// whether the glyphs the marks attach to are bases or other marks.
+#[derive(Debug, Default)]
+pub struct MarkAttachment {
+    marks: MarkClasses,
+    bases: CoverageLookup<HashMap<u16, Anchor>>,
+}
+
+impl MarkAttachment {
+    pub fn add_mark_class(&mut self, glyph_order: &GlyphOrder, gc_table: &NamedGlyphClassTable,
+        base_class: &GlyphClass, anchor: &Anchor, name: &MarkClassName,
+        mark_class: &[MarkClassGlyphClass]) -> CompileResult<()>
+    {
+        let class_id = self.marks.class_id(glyph_order, gc_table, name, mark_class)?;
+
+        for base_glyph in base_class.iter_glyphs(glyph_order, gc_table) {
+            self.bases.entry(base_glyph?)
+                .or_default()
+                .insert(class_id, anchor.clone());
+        }
+
+        Ok(())
+    }
+
+    fn encode_base_array(&self, buf: &mut EncodeBuf) -> EncodeResult<usize> {
+        let start = buf.bytes.len();
+
+        let class_id_range = 0..(self.marks.class_count() as u16);
+        let nrecords = self.marks.class_count() * self.bases.len();
+
+        let base_count: u16 = self.bases.len().checked_into("MarkAttachPos", "base count")?;
+        buf.append(&base_count)?;
+
+        let mut record_offset = buf.bytes.len();
+        buf.bytes.resize(record_offset + (nrecords * u16::PACKED_LEN), 0u8);
+
+        // FIXME: use buf.encode_pool() for dedup
+        for base in self.bases.values() {
+            for class_id in class_id_range.clone() {
+                let mark_anchor_offset = match base.get(&class_id) {
+                    Some(anchor) => buf.append(anchor)? - start,
+                    None => 0
+                };
+
+                let mark_anchor_offset: u16 =
+                    mark_anchor_offset.checked_into("MarkAttachPos", "base anchor offset")?;
+
+                buf.encode_at(&mark_anchor_offset, record_offset)?;
+                record_offset += u16::PACKED_LEN;
+            }
+        }
+
+        Ok(start)
+    }
+
+    // splits along the base coverage. every base can attach any of the marks, so both halves keep
+    // the full set of mark classes.
+    pub(crate) fn split(&self) -> Option<(Self, Self)> {
+        self.bases.split()
+            .map(|(a, b)| (
+                MarkAttachment {
+                    marks: self.marks.clone(),
+                    bases: a
+                },
+
+                MarkAttachment {
+                    marks: self.marks.clone(),
+                    bases: b
+                }))
+    }
+}
+
+// MarkBasePosFormat1 and MarkMarkPosFormat1. for mark-to-mark, "marks" are mark1 and "bases" are
+// mark2.
+#[derive(PackedSize, DecodeBE, EncodeBE)]
+struct MarkAttachmentFormat1Header {
+    format: u16,
+    mark_coverage_offset: u16,
+    base_coverage_offset: u16,
+    mark_class_count: u16,
+    mark_array_offset: u16,
+    base_array_offset: u16
+}
+
+impl TTFEncode for MarkAttachment {
+    fn ttf_encode(&self, buf: &mut EncodeBuf) -> EncodeResult<usize> {
+        let start = buf.bytes.len();
+
+        let marks = self.marks.records().values();
+
+        buf.defer_header_encode(
+            move |buf| Ok(MarkAttachmentFormat1Header {
+                format: 1,
+                mark_coverage_offset: (buf.append(self.marks.records())? - start)
+                    .checked_into("MarkAttachPos", "mark coverage offset")?,
+                base_coverage_offset: (buf.append(&self.bases)? - start)
+                    .checked_into("MarkAttachPos", "base coverage offset")?,
+                mark_class_count: self.marks.class_count()
+                    .checked_into("MarkAttachPos", "mark class count")?,
+                mark_array_offset: (marks.ttf_encode_mark_array(buf)? - start)
+                    .checked_into("MarkAttachPos", "mark array offset")?,
+                base_array_offset: (self.encode_base_array(buf)? - start)
+                    .checked_into("MarkAttachPos", "base array offset")?
+            }),
+
+            |_| {
+                // we don't actually have any fixed items after the header for mark attachment.
+                // all the data is referenced by offsets in the header.
+
+                Ok(())
+            })
+    }
+}
@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+
+use endian_codec::{PackedSize, EncodeBE, DecodeBE};
+
+use crate::glyph_class::*;
+use crate::glyph_order::*;
+
+use crate::compile_model::compiler_state::*;
+use crate::compile_model::tables::gpos::*;
+use crate::compile_model::util::encode::*;
+use crate::compile_model::util::*;
+use crate::compile_model::coverage::*;
+use crate::compile_model::error::*;
+
+use crate::parse_model::MarkClassName;
+
+
+// anchors for each component of a ligature, keyed by mark class id. a component with no anchors
+// (i.e. `<anchor NULL>`) is an empty map.
+type ComponentAnchors = Vec<HashMap<u16, Anchor>>;
+
+#[derive(Debug, Default)]
+pub struct MarkToLigature {
+    marks: MarkClasses,
+    ligatures: CoverageLookup<ComponentAnchors>,
+}
+
+pub struct LigatureComponentMark<'a> {
+    pub anchor: Anchor,
+    pub class_name: &'a MarkClassName,
+    pub mark_class: &'a [MarkClassGlyphClass]
+}
+
+impl MarkToLigature {
+    pub fn add_ligature(&mut self, glyph_order: &GlyphOrder, gc_table: &NamedGlyphClassTable,
+        ligature_class: &GlyphClass, components: &[Vec<LigatureComponentMark>]) -> CompileResult<()>
+    {
+        let mut component_anchors: ComponentAnchors = Vec::with_capacity(components.len());
+
+        for component in components {
+            let mut anchors = HashMap::new();
+
+            for mark in component {
+                let class_id = self.marks.class_id(glyph_order, gc_table,
+                    mark.class_name, mark.mark_class)?;
+
+                anchors.insert(class_id, mark.anchor.clone());
+            }
+
+            component_anchors.push(anchors);
+        }
+
+        // a ligature can be given anchors for different mark classes across several statements,
+        // so merge them component by component.
+        for ligature_glyph in ligature_class.iter_glyphs(glyph_order, gc_table) {
+            let existing = self.ligatures.entry(ligature_glyph?)
+                .or_default();
+
+            if existing.len() < component_anchors.len() {
+                existing.resize_with(component_anchors.len(), HashMap::new);
+            }
+
+            for (existing, anchors) in existing.iter_mut().zip(&component_anchors) {
+                existing.extend(anchors.iter()
+                    .map(|(class_id, anchor)| (*class_id, anchor.clone())));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn encode_ligature_attach(&self, buf: &mut EncodeBuf, components: &ComponentAnchors)
+            -> EncodeResult<usize> {
+        let start = buf.bytes.len();
+        let class_count = self.marks.class_count();
+
+        let component_count: u16 =
+            components.len().checked_into("MarkLigPos", "component count")?;
+        buf.append(&component_count)?;
+
+        let mut record_offset = buf.bytes.len();
+        buf.reserve_bytes(components.len() * class_count * u16::PACKED_LEN);
+
+        for component in components {
+            for class_id in 0..(class_count as u16) {
+                let anchor_offset = match component.get(&class_id) {
+                    Some(anchor) => buf.append(anchor)? - start,
+                    None => 0
+                };
+
+                let anchor_offset: u16 =
+                    anchor_offset.checked_into("MarkLigPos", "ligature anchor offset")?;
+
+                buf.encode_at(&anchor_offset, record_offset)?;
+                record_offset += u16::PACKED_LEN;
+            }
+        }
+
+        Ok(start)
+    }
+
+    fn encode_ligature_array(&self, buf: &mut EncodeBuf) -> EncodeResult<usize> {
+        let start = buf.bytes.len();
+
+        let ligature_count: u16 =
+            self.ligatures.len().checked_into("MarkLigPos", "ligature count")?;
+        buf.append(&ligature_count)?;
+
+        buf.encode_pool(start, self.ligatures.values(),
+            |offset, _| offset,
+            |buf, components| self.encode_ligature_attach(buf, components))?;
+
+        Ok(start)
+    }
+}
+
+#[derive(PackedSize, DecodeBE, EncodeBE)]
+struct MarkLigPosFormat1Header {
+    format: u16,
+    mark_coverage_offset: u16,
+    ligature_coverage_offset: u16,
+    mark_class_count: u16,
+    mark_array_offset: u16,
+    ligature_array_offset: u16
+}
+
+impl TTFEncode for MarkToLigature {
+    fn ttf_encode(&self, buf: &mut EncodeBuf) -> EncodeResult<usize> {
+        let start = buf.bytes.len();
+
+        let marks = self.marks.records().values();
+
+        buf.defer_header_encode(
+            move |buf| Ok(MarkLigPosFormat1Header {
+                format: 1,
+                mark_coverage_offset: (buf.append(self.marks.records())? - start)
+                    .checked_into("MarkLigPos", "mark coverage offset")?,
+                ligature_coverage_offset: (buf.append(&self.ligatures)? - start)
+                    .checked_into("MarkLigPos", "ligature coverage offset")?,
+                mark_class_count: self.marks.class_count()
+                    .checked_into("MarkLigPos", "mark class count")?,
+                mark_array_offset: (marks.ttf_encode_mark_array(buf)? - start)
+                    .checked_into("MarkLigPos", "mark array offset")?,
+                ligature_array_offset: (self.encode_ligature_array(buf)? - start)
+                    .checked_into("MarkLigPos", "ligature array offset")?
+            }),
+
+            |_| {
+                // as with mark attachment, everything after the header is referenced by offset.
+                Ok(())
+            })
+    }
+}
+
+impl TTFSubtableSplit for MarkToLigature {
+    // as with mark attachment, both halves keep the full set of mark classes.
+    fn split(&self) -> Option<(Self, Self)> {
+        self.ligatures.split()
+            .map(|(a, b)| (
+                MarkToLigature {
+                    marks: self.marks.clone(),
+                    ligatures: a
+                },
+
+                MarkToLigature {
+                    marks: self.marks.clone(),
+                    ligatures: b
+                }))
+    }
+}
@@ -1,17 +1,39 @@
-use std::collections::HashMap;
+use std::ops;
 
-use crate::compile_model::tables::gpos::*;
-use crate::glyph_class::*;
-use crate::glyph_order::*;
+use crate::compile_model::util::encode::*;
+use crate::compile_model::lookup::*;
+
+use super::mark_attachment::*;
 
-use crate::parse_model::MarkClassName;
 
 #[derive(Debug, Default)]
-pub struct MarkToMark {
-    base_marks: HashMap<(u16, u16), Anchor>
+pub struct MarkToMark(pub MarkAttachment);
+
+impl ops::Deref for MarkToMark {
+    type Target = MarkAttachment;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl ops::DerefMut for MarkToMark {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl TTFEncode for MarkToMark {
+    #[inline]
+    fn ttf_encode(&self, buf: &mut EncodeBuf) -> EncodeResult<usize> {
+        self.0.ttf_encode(buf)
+    }
 }
 
-impl MarkToMark {
-    pub fn add_mark(&mut self, _glyph_order: &GlyphOrder, _base_mark: &GlyphClass, _mark: (&Anchor, &MarkClassName)) {
+impl TTFSubtableSplit for MarkToMark {
+    #[inline]
+    fn split(&self) -> Option<(Self, Self)> {
+        self.0.split()
+            .map(|(a, b)| (MarkToMark(a), MarkToMark(b)))
     }
 }
@@ -1,6 +1,7 @@
 use crate::compile_model::util::decode::*;
 use crate::compile_model::util::encode::*;
 use crate::compile_model::lookup::*;
+use crate::compile_model::chain_context::*;
 
 
 mod single_array;
@@ -20,13 +21,22 @@ pub use pair::*;
 pub mod cursive;
 pub use cursive::Cursive;
 
+mod mark_attachment;
+pub use mark_attachment::*;
+
 mod mark_to_base;
 pub use mark_to_base::*;
 
+mod mark_to_ligature;
+pub use mark_to_ligature::*;
+
 mod mark_to_mark;
 pub use mark_to_mark::*;
 
 
+// lookup type for extension lookups, which wrap subtables of any of the other types.
+const EXTENSION: u16 = 9;
+
 macro_rules! impl_subtable_for {
     ($ty:ident) => {
         $crate::impl_lookup_subtable_for!(GPOSLookup, $ty, $ty);
@@ -39,14 +49,18 @@ pub enum GPOSLookup {
     Pair(Lookup<Pair>),
     Cursive(Lookup<Cursive>),
     MarkToBase(Lookup<MarkToBase>),
+    MarkToLigature(Lookup<MarkToLigature>),
     MarkToMark(Lookup<MarkToMark>),
+    ChainContext(Lookup<ChainContext>),
 }
 
 impl_subtable_for!(Single);
 impl_subtable_for!(Pair);
 impl_subtable_for!(Cursive);
 impl_subtable_for!(MarkToBase);
+impl_subtable_for!(MarkToLigature);
 impl_subtable_for!(MarkToMark);
+impl_subtable_for!(ChainContext);
 
 impl TTFDecode for GPOSLookup {
     fn ttf_decode(bytes: &[u8]) -> DecodeResult<Self> {
@@ -54,6 +68,12 @@ impl TTFDecode for GPOSLookup {
 
         match lookup_type {
             2 => Lookup::ttf_decode(bytes).map(GPOSLookup::Pair),
+
+            EXTENSION => match extension_lookup_type(bytes) {
+                2 => Lookup::ttf_decode_extension(bytes).map(GPOSLookup::Pair),
+                _ => Err(DecodeError::InvalidValue("extension_lookup_type", "GPOS Lookup".into()))
+            },
+
             _ => Err(DecodeError::InvalidValue("lookup_type", "GPOS Lookup".into()))
         }
     }
@@ -62,12 +82,16 @@ impl TTFDecode for GPOSLookup {
 impl TTFEncode for GPOSLookup {
     fn ttf_encode(&self, buf: &mut EncodeBuf) -> EncodeResult<usize> {
         match self {
-            GPOSLookup::Single(lookup) => lookup.ttf_encode_with_lookup_type(buf, 1),
+            GPOSLookup::Single(lookup) => lookup.ttf_encode_with_lookup_type(buf, 1, EXTENSION),
+
+            GPOSLookup::Pair(lookup) => lookup.ttf_encode_with_lookup_type(buf, 2, EXTENSION),
+            GPOSLookup::Cursive(lookup) => lookup.ttf_encode_with_lookup_type(buf, 3, EXTENSION),
+            GPOSLookup::MarkToBase(lookup) => lookup.ttf_encode_with_lookup_type(buf, 4, EXTENSION),
+            GPOSLookup::MarkToLigature(lookup) => lookup.ttf_encode_with_lookup_type(buf, 5, EXTENSION),
+            GPOSLookup::MarkToMark(lookup) => lookup.ttf_encode_with_lookup_type(buf, 6, EXTENSION),
 
-            GPOSLookup::Pair(lookup) => lookup.ttf_encode_with_lookup_type(buf, 2),
-            GPOSLookup::Cursive(lookup) => lookup.ttf_encode_with_lookup_type(buf, 3),
-            GPOSLookup::MarkToBase(lookup) => lookup.ttf_encode_with_lookup_type(buf, 4),
-            l => panic!("unimplemented encode for {:?}", l)
+            GPOSLookup::ChainContext(lookup) =>
+                lookup.ttf_encode_with_context_lookup_types(buf, 7, 8, EXTENSION)
         }
     }
 }
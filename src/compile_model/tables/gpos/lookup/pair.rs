@@ -30,7 +30,7 @@ impl TTFDecode for Pair {
 
 pub enum PairSubtableEncoder<'a> {
     Glyphs(PairGlyphsSplittingEncoder<'a>),
-    Class(SingularSubtableEncoder<'a, PairClass>)
+    Class(SplittingSubtableEncoder<'a, PairClass>)
 }
 
 impl<'a> TTFSubtableEncoder<'a> for PairSubtableEncoder<'a> {
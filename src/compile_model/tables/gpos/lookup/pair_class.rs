@@ -7,13 +7,15 @@ use thiserror::Error;
 use endian_codec::{PackedSize, EncodeBE, DecodeBE};
 
 use crate::compile_model::util::encode::*;
+use crate::compile_model::util::*;
+use crate::compile_model::lookup::*;
 use crate::compile_model::value_record::*;
 use crate::compile_model::class_def::*;
 use crate::compile_model::coverage::*;
 use crate::compile_model::error::*;
 
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct PairClassIntersect(pub ValueRecord, pub ValueRecord);
 
 #[derive(Debug, Default)]
@@ -38,20 +40,14 @@ impl PairClass {
             self.glyphs.1.is_disjoint(&pair.1)
         );
 
-        if glyphs_disjoint == (true, true) {
-            return true
-        }
-
         let classes_present = (
             self.classes.0.contains(&pair.0),
             self.classes.1.contains(&pair.1)
         );
 
-        if (!glyphs_disjoint.0 && classes_present.0) && (!glyphs_disjoint.1 && classes_present.1) {
-            return true
-        }
-
-        false
+        // each side has to be either a new class or one we already have. a class which only
+        // partially overlaps an existing one can't be represented in the class definitions.
+        (glyphs_disjoint.0 || classes_present.0) && (glyphs_disjoint.1 || classes_present.1)
     }
 
     pub fn add_pair(&mut self, pair: (ClassDef, ClassDef), value_records: (ValueRecord, ValueRecord))
@@ -133,21 +129,26 @@ impl TTFEncode for PairClass {
         buf.defer_header_encode(
             |buf| Ok(PairPosFormat2Header {
                 format: 2,
-                coverage_offset: (buf.append(&coverage)? - start) as u16,
+                coverage_offset: (buf.append(&coverage)? - start)
+                    .checked_into("PairClass", "coverage offset")?,
 
                 value_format_1: value_formats.0,
                 value_format_2: value_formats.1,
 
-                class_def_1_offset: (classes.0.ttf_encode(buf, true)? - start) as u16,
-                class_def_2_offset: (classes.1.ttf_encode(buf, false)? - start) as u16,
+                class_def_1_offset: (classes.0.ttf_encode(buf, true)? - start)
+                    .checked_into("PairClass", "class def 1 offset")?,
+                class_def_2_offset: (classes.1.ttf_encode(buf, false)? - start)
+                    .checked_into("PairClass", "class def 2 offset")?,
 
-                class_1_count: classes.0.len() as u16,
-                class_2_count: (classes.1.len() + 1) as u16
+                class_1_count: classes.0.len().checked_into("PairClass", "class 1 count")?,
+                class_2_count: (classes.1.len() + 1).checked_into("PairClass", "class 2 count")?
             }),
 
             |buf| {
                 let mut vr_offset = buf.bytes.len();
-                buf.reserve_bytes(classes.0.len() * classes.1.len() * (vr_sizes.0 + vr_sizes.1));
+                // every row starts with the implicit class 2 id 0.
+                let row_len = classes.1.len() + 1;
+                buf.reserve_bytes(classes.0.len() * row_len * (vr_sizes.0 + vr_sizes.1));
 
                 for x in &classes.0 {
                     // class 2 id 0
@@ -174,3 +175,139 @@ impl TTFEncode for PairClass {
             })
     }
 }
+
+impl TTFSubtableSplit for PairClass {
+    // splits along the rows of the class 1 x class 2 value record matrix, which is what grows
+    // fastest. both halves keep all of the class 2 definitions, since most rows use most of them.
+    fn split(&self) -> Option<(Self, Self)> {
+        if self.classes.0.len() < 2 {
+            return None
+        }
+
+        let mut rows = self.classes.0.iter().collect::<Vec<_>>();
+        rows.sort();
+
+        let (first, second) = rows.split_at(rows.len() / 2);
+
+        let half = |rows: &[&ClassDef]| {
+            let classes: HashSet<ClassDef> = rows.iter()
+                .map(|cls| (*cls).clone())
+                .collect();
+
+            let glyphs = ClassDef(classes.iter()
+                .flat_map(|cls| cls.iter().copied())
+                .collect());
+
+            let pairs = self.pairs.iter()
+                .filter(|((first, _), _)| classes.contains(first))
+                .map(|(pair, intersect)| (pair.clone(), intersect.clone()))
+                .collect();
+
+            PairClass {
+                glyphs: (glyphs, self.glyphs.1.clone()),
+                classes: (classes, self.classes.1.clone()),
+                pairs
+            }
+        };
+
+        Some((half(first), half(second)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+
+    use crate::compile_model::util::decode::decode_u16_be;
+
+    // one row per first class, each with a full value record against one of the second classes.
+    // with 100 of each, the value record matrix alone is about 80K.
+    fn large_pair_class() -> PairClass {
+        let mut subtable = PairClass::default();
+
+        let value_record = ValueRecord {
+            x_placement: 1.into(),
+            y_placement: 2.into(),
+            x_advance: 3.into(),
+            y_advance: 4.into(),
+
+            ..ValueRecord::zero()
+        };
+
+        for i in 0..100 {
+            let pair = (
+                ClassDef([i * 2, i * 2 + 1].iter().copied().collect()),
+                ClassDef([1000 + i].iter().copied().collect())
+            );
+
+            subtable.add_pair(pair, (value_record.clone(), ValueRecord::zero())).unwrap();
+        }
+
+        subtable
+    }
+
+    fn coverage_glyphs(subtable: &[u8]) -> Vec<u16> {
+        let coverage = &subtable[decode_u16_be(subtable, 2) as usize..];
+        let count = decode_u16_be(coverage, 2) as usize;
+
+        match decode_u16_be(coverage, 0) {
+            1 => (0..count)
+                .map(|i| decode_u16_be(coverage, 4 + i * 2))
+                .collect(),
+
+            2 => (0..count)
+                .flat_map(|i| decode_u16_be(coverage, 4 + i * 6)
+                    ..=decode_u16_be(coverage, 6 + i * 6))
+                .collect(),
+
+            format => panic!("invalid coverage format {}", format)
+        }
+    }
+
+    fn encoded_len(subtable: &PairClass) -> usize {
+        let mut buf = EncodeBuf::new();
+        subtable.ttf_encode(&mut buf).unwrap();
+        buf.bytes.len()
+    }
+
+    #[test]
+    fn test_overflowing_subtable_is_split() {
+        let subtable = large_pair_class();
+
+        match subtable.ttf_encode(&mut EncodeBuf::new()) {
+            Err(EncodeError::U16Overflow { .. }) => (),
+            res => panic!("expected the subtable to overflow, got {:?}", res)
+        }
+
+        let mut buf = EncodeBuf::new();
+        let mut encoder = subtable.ttf_subtable_encoder();
+        let mut starts = Vec::new();
+
+        while let Some(start) = encoder.encode_next_subtable(&mut buf) {
+            starts.push(start.unwrap());
+        }
+
+        // nothing from the attempt which overflowed is left behind, so the halves are encoded
+        // back to back from the start of the buffer.
+        let halves = subtable.split().unwrap();
+        let half_lens = (encoded_len(&halves.0), encoded_len(&halves.1));
+
+        assert_eq!(starts, [0, half_lens.0]);
+        assert_eq!(buf.bytes.len(), half_lens.0 + half_lens.1);
+
+        let mut covered = BTreeSet::new();
+
+        for (start, end) in starts.iter().zip(starts.iter().skip(1).chain(Some(&buf.bytes.len()))) {
+            let encoded = &buf.bytes[*start..*end];
+            assert_eq!(decode_u16_be(encoded, 0), 2);
+
+            for glyph in coverage_glyphs(encoded) {
+                assert!(covered.insert(glyph), "glyph {} is covered by two subtables", glyph);
+            }
+        }
+
+        assert_eq!(covered, subtable.glyphs.0.iter().copied().collect());
+    }
+}
@@ -1,4 +1,5 @@
 use crate::compile_model::util::encode::*;
+use crate::compile_model::lookup::*;
 use crate::util::variant::*;
 
 
@@ -23,3 +24,15 @@ impl TTFEncode for Single {
         }
     }
 }
+
+impl TTFSubtableSplit for Single {
+    fn split(&self) -> Option<(Self, Self)> {
+        match self {
+            Single::Class(sc) => sc.split()
+                .map(|(a, b)| (Single::Class(a), Single::Class(b))),
+
+            Single::Array(sa) => sa.split()
+                .map(|(a, b)| (Single::Array(a), Single::Array(b)))
+        }
+    }
+}
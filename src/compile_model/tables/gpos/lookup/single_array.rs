@@ -1,6 +1,8 @@
 use endian_codec::{PackedSize, EncodeBE, DecodeBE};
 
 use crate::compile_model::util::encode::*;
+use crate::compile_model::lookup::*;
+use crate::compile_model::util::*;
 use crate::compile_model::value_record::*;
 use crate::compile_model::coverage::*;
 
@@ -74,9 +76,10 @@ impl TTFEncode for SingleArray {
         buf.defer_header_encode(
             |buf| Ok(SinglePosFormat2Header {
                 format: 2,
-                coverage_offset: (self.glyphs.ttf_encode(buf)? - start) as u16,
+                coverage_offset: (self.glyphs.ttf_encode(buf)? - start)
+                    .checked_into("SinglePos", "coverage offset")?,
                 value_format,
-                value_count: self.glyphs.len() as u16
+                value_count: self.glyphs.len().checked_into("SinglePos", "value count")?
             }),
 
             |buf| {
@@ -94,3 +97,19 @@ impl TTFEncode for SingleArray {
             })
    }
 }
+
+impl TTFSubtableSplit for SingleArray {
+    fn split(&self) -> Option<(Self, Self)> {
+        self.glyphs.split()
+            .map(|(a, b)| (
+                SingleArray {
+                    glyphs: a,
+                    common_value_format: self.common_value_format
+                },
+
+                SingleArray {
+                    glyphs: b,
+                    common_value_format: self.common_value_format
+                }))
+    }
+}
@@ -1,6 +1,8 @@
 use endian_codec::{PackedSize, EncodeBE, DecodeBE};
 
 use crate::compile_model::util::encode::*;
+use crate::compile_model::lookup::*;
+use crate::compile_model::util::*;
 use crate::compile_model::value_record::*;
 use crate::compile_model::coverage::*;
 
@@ -40,7 +42,8 @@ impl TTFEncode for SingleClass {
         buf.defer_header_encode(
             |buf| Ok(SinglePosFormat1Header {
                 format: 1,
-                coverage_offset: (self.glyphs.ttf_encode(buf)? - start) as u16,
+                coverage_offset: (self.glyphs.ttf_encode(buf)? - start)
+                    .checked_into("SinglePos", "coverage offset")?,
                 value_format
             }),
 
@@ -54,3 +57,19 @@ impl TTFEncode for SingleClass {
         Ok(start)
    }
 }
+
+impl TTFSubtableSplit for SingleClass {
+    fn split(&self) -> Option<(Self, Self)> {
+        self.glyphs.split()
+            .map(|(a, b)| (
+                SingleClass {
+                    glyphs: a,
+                    value_record: self.value_record.clone()
+                },
+
+                SingleClass {
+                    glyphs: b,
+                    value_record: self.value_record.clone()
+                }))
+    }
+}
@@ -1,15 +1,79 @@
+use std::collections::HashMap;
+
 use endian_codec::{EncodeBE, DecodeBE, PackedSize};
 
+use crate::glyph_order::*;
+
+use crate::compile_model::compiler_state::*;
 use crate::compile_model::util::encode::*;
+use crate::compile_model::coverage::*;
+use crate::compile_model::error::*;
+
+use crate::parse_model::MarkClassName;
+
 use super::Anchor;
 
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct MarkRecord {
     pub class_id: u16,
     pub anchor: Anchor
 }
 
+// mark classes referenced by a mark attachment subtable, which are assigned class ids in order of
+// first use. shared by mark-to-base, mark-to-ligature and mark-to-mark.
+#[derive(Debug, Clone, Default)]
+pub struct MarkClasses {
+    classes: HashMap<MarkClassName, u16>,
+    marks: CoverageLookup<MarkRecord>
+}
+
+impl MarkClasses {
+    fn add_marks(&mut self, glyph_order: &GlyphOrder, gc_table: &NamedGlyphClassTable,
+        mark_class: &[MarkClassGlyphClass], class_id: u16) -> CompileResult<()>
+    {
+        for (glyph_class, anchor) in mark_class {
+            for glyph in glyph_class.iter_glyphs(glyph_order, gc_table) {
+                let was_present = self.marks
+                    .insert(glyph?, MarkRecord {
+                        class_id,
+                        anchor: anchor.clone(),
+                    })
+                    .is_some();
+                if was_present {
+                    panic!("glyph class overlap in mark attachment");
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn class_id(&mut self, glyph_order: &GlyphOrder, gc_table: &NamedGlyphClassTable,
+        name: &MarkClassName, mark_class: &[MarkClassGlyphClass]) -> CompileResult<u16>
+    {
+        if let Some(id) = self.classes.get(name) {
+            return Ok(*id);
+        }
+
+        let id = self.classes.len() as u16;
+        self.add_marks(glyph_order, gc_table, mark_class, id)?;
+        self.classes.insert(name.clone(), id);
+
+        Ok(id)
+    }
+
+    #[inline]
+    pub fn class_count(&self) -> usize {
+        self.classes.len()
+    }
+
+    #[inline]
+    pub fn records(&self) -> &CoverageLookup<MarkRecord> {
+        &self.marks
+    }
+}
+
 pub trait MarkArrayTTFEncode {
     fn ttf_encode_mark_array(self, buf: &mut EncodeBuf) -> EncodeResult<usize>;
 }
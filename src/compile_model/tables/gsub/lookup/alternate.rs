@@ -3,6 +3,8 @@ use std::ops;
 use endian_codec::{PackedSize, EncodeBE, DecodeBE};
 
 use crate::compile_model::util::encode::*;
+use crate::compile_model::util::*;
+use crate::compile_model::lookup::*;
 use crate::compile_model::coverage::*;
 
 
@@ -39,8 +41,9 @@ impl TTFEncode for Alternate {
         buf.encode_pool_with_header(
             |buf| Ok(AlternateSubstFormat1Header {
                 format: 1,
-                coverage_offset: (self.0.ttf_encode(buf)? - start) as u16,
-                set_count: self.len() as u16
+                coverage_offset: (self.0.ttf_encode(buf)? - start)
+                    .checked_into("AlternateSubst", "coverage offset")?,
+                set_count: self.len().checked_into("AlternateSubst", "set count")?
             }),
 
             self.values(),
@@ -56,3 +59,10 @@ impl TTFEncode for Alternate {
             })
     }
 }
+
+impl TTFSubtableSplit for Alternate {
+    fn split(&self) -> Option<(Self, Self)> {
+        self.0.split()
+            .map(|(a, b)| (Alternate(a), Alternate(b)))
+    }
+}
@@ -0,0 +1,144 @@
+use std::ops;
+
+use endian_codec::{PackedSize, EncodeBE, DecodeBE};
+
+use crate::compile_model::util::encode::*;
+use crate::compile_model::util::*;
+use crate::compile_model::lookup::*;
+use crate::compile_model::coverage::*;
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LigatureRule {
+    // component glyphs following the first glyph, which is the key in the coverage table
+    pub components: Vec<u16>,
+    pub ligature: u16
+}
+
+// each LigatureSet is kept sorted longest-first so that a shaper, which takes the first matching
+// ligature in the set, will always prefer "f f i" over "f f".
+type inner = CoverageLookup<Vec<LigatureRule>>;
+
+#[derive(Debug, Default)]
+pub struct Ligature(pub inner);
+
+impl ops::Deref for Ligature {
+    type Target = inner;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl ops::DerefMut for Ligature {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl Ligature {
+    fn find_rule(&self, first: u16, components: &[u16]) -> Option<&LigatureRule> {
+        self.0.get(&first)
+            .and_then(|set| set.iter().find(|rule| rule.components == components))
+    }
+
+    pub fn can_add(&self, sequence: &[u16], ligature: u16) -> bool {
+        let (first, components) = match sequence.split_first() {
+            Some(split) => split,
+            None => return false
+        };
+
+        match self.find_rule(*first, components) {
+            Some(rule) => rule.ligature == ligature,
+            None => true
+        }
+    }
+
+    pub fn add_ligature(&mut self, sequence: &[u16], ligature: u16) {
+        let (first, components) = match sequence.split_first() {
+            Some(split) => split,
+            None => return
+        };
+
+        if self.find_rule(*first, components).is_some() {
+            return;
+        }
+
+        let set = self.0.entry(*first)
+            .or_default();
+
+        // insert after every rule which is at least as long, so rules of equal length keep the
+        // order in which they were defined.
+        let idx = set.iter()
+            .position(|rule| rule.components.len() < components.len())
+            .unwrap_or(set.len());
+
+        set.insert(idx, LigatureRule {
+            components: components.to_vec(),
+            ligature
+        });
+    }
+}
+
+#[derive(Debug, PackedSize, EncodeBE, DecodeBE)]
+struct LigatureSubstFormat1Header {
+    format: u16,
+    coverage_offset: u16,
+    ligature_set_count: u16
+}
+
+#[derive(Debug, PackedSize, EncodeBE, DecodeBE)]
+struct LigatureTableHeader {
+    ligature_glyph: u16,
+    component_count: u16
+}
+
+fn encode_ligature_set(buf: &mut EncodeBuf, set: &[LigatureRule]) -> EncodeResult<usize> {
+    let start = buf.bytes.len();
+
+    let ligature_count: u16 = set.len().checked_into("LigatureSet", "ligature count")?;
+    buf.append(&ligature_count)?;
+
+    buf.encode_pool(start, set.iter(),
+        |offset, _| offset,
+        |buf, rule| {
+            buf.append(&LigatureTableHeader {
+                ligature_glyph: rule.ligature,
+                component_count: (rule.components.len() + 1)
+                    .checked_into("Ligature", "component count")?
+            })?;
+
+            for glyph_id in &rule.components {
+                buf.append(glyph_id)?;
+            }
+
+            Ok(())
+        })?;
+
+    Ok(start)
+}
+
+impl TTFEncode for Ligature {
+    fn ttf_encode(&self, buf: &mut EncodeBuf) -> EncodeResult<usize> {
+        let start = buf.bytes.len();
+
+        buf.encode_pool_with_header(
+            |buf| Ok(LigatureSubstFormat1Header {
+                format: 1,
+                coverage_offset: (self.0.ttf_encode(buf)? - start)
+                    .checked_into("LigatureSubst", "coverage offset")?,
+                ligature_set_count: self.len().checked_into("LigatureSubst", "ligature set count")?
+            }),
+
+            self.values(),
+            |offset, _| offset,
+            |buf, set| encode_ligature_set(buf, set))
+    }
+}
+
+impl TTFSubtableSplit for Ligature {
+    fn split(&self) -> Option<(Self, Self)> {
+        self.0.split()
+            .map(|(a, b)| (Ligature(a), Ligature(b)))
+    }
+}
@@ -1,14 +1,27 @@
 use crate::compile_model::util::decode::*;
 use crate::compile_model::util::encode::*;
 use crate::compile_model::lookup::*;
+use crate::compile_model::chain_context::*;
 
 
+mod single;
+pub use single::*;
+
 mod multiple;
 pub use multiple::*;
 
 mod alternate;
 pub use alternate::*;
 
+mod ligature;
+pub use ligature::*;
+
+mod reverse_chain_single;
+pub use reverse_chain_single::*;
+
+// lookup type for extension lookups, which wrap subtables of any of the other types.
+const EXTENSION: u16 = 7;
+
 macro_rules! impl_subtable_for {
     ($ty:ident) => {
         $crate::impl_lookup_subtable_for!(GSUBLookup, $ty, $ty);
@@ -17,12 +30,30 @@ macro_rules! impl_subtable_for {
 
 #[derive(Debug)]
 pub enum GSUBLookup {
+    Single(Lookup<Single>),
     Multiple(Lookup<Multiple>),
-    Alternate(Lookup<Alternate>)
+    Alternate(Lookup<Alternate>),
+    Ligature(Lookup<Ligature>),
+    ChainContext(Lookup<ChainContext>),
+    ReverseChainSingle(Lookup<ReverseChainSingle>)
 }
 
+impl_subtable_for!(Single);
 impl_subtable_for!(Multiple);
 impl_subtable_for!(Alternate);
+impl_subtable_for!(Ligature);
+impl_subtable_for!(ChainContext);
+impl_subtable_for!(ReverseChainSingle);
+
+impl OffsetLookupIndices for GSUBLookup {
+    fn offset_lookup_indices(&mut self, offset: u16) {
+        if let GSUBLookup::ChainContext(lookup) = self {
+            for subtable in lookup.subtables.iter_mut() {
+                subtable.offset_lookup_indices(offset);
+            }
+        }
+    }
+}
 
 
 impl TTFDecode for GSUBLookup {
@@ -36,8 +67,13 @@ impl TTFDecode for GSUBLookup {
 impl TTFEncode for GSUBLookup {
     fn ttf_encode(&self, buf: &mut EncodeBuf) -> EncodeResult<usize> {
         match self {
-            GSUBLookup::Multiple(lookup) => lookup.ttf_encode_with_lookup_type(buf, 2),
-            GSUBLookup::Alternate(lookup) => lookup.ttf_encode_with_lookup_type(buf, 3)
+            GSUBLookup::Single(lookup) => lookup.ttf_encode_with_lookup_type(buf, 1, EXTENSION),
+            GSUBLookup::Multiple(lookup) => lookup.ttf_encode_with_lookup_type(buf, 2, EXTENSION),
+            GSUBLookup::Alternate(lookup) => lookup.ttf_encode_with_lookup_type(buf, 3, EXTENSION),
+            GSUBLookup::Ligature(lookup) => lookup.ttf_encode_with_lookup_type(buf, 4, EXTENSION),
+            GSUBLookup::ChainContext(lookup) =>
+                lookup.ttf_encode_with_context_lookup_types(buf, 5, 6, EXTENSION),
+            GSUBLookup::ReverseChainSingle(lookup) => lookup.ttf_encode_with_lookup_type(buf, 8, EXTENSION)
         }
     }
 }
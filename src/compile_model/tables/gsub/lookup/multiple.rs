@@ -3,6 +3,8 @@ use std::ops;
 use endian_codec::{PackedSize, EncodeBE, DecodeBE};
 
 use crate::compile_model::util::encode::*;
+use crate::compile_model::util::*;
+use crate::compile_model::lookup::*;
 use crate::compile_model::coverage::*;
 
 
@@ -25,6 +27,15 @@ impl ops::DerefMut for Multiple {
     }
 }
 
+impl Multiple {
+    pub fn can_add(&self, glyph: u16, sequence: &[u16]) -> bool {
+        match self.0.get(&glyph) {
+            Some(existing) => existing.as_slice() == sequence,
+            None => true
+        }
+    }
+}
+
 #[derive(Debug, PackedSize, EncodeBE, DecodeBE)]
 struct MultipleSubstFormat1Header {
     format: u16,
@@ -40,8 +51,9 @@ impl TTFEncode for Multiple {
         buf.encode_pool_with_header(
             |buf| Ok(MultipleSubstFormat1Header {
                 format: 1,
-                coverage_offset: (self.0.ttf_encode(buf)? - start) as u16,
-                sequence_count: self.len() as u16
+                coverage_offset: (self.0.ttf_encode(buf)? - start)
+                    .checked_into("MultipleSubst", "coverage offset")?,
+                sequence_count: self.len().checked_into("MultipleSubst", "sequence count")?
             }),
 
             self.values(),
@@ -58,3 +70,10 @@ impl TTFEncode for Multiple {
             })
     }
 }
+
+impl TTFSubtableSplit for Multiple {
+    fn split(&self) -> Option<(Self, Self)> {
+        self.0.split()
+            .map(|(a, b)| (Multiple(a), Multiple(b)))
+    }
+}
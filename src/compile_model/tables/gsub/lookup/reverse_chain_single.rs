@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+
+use endian_codec::{PackedSize, EncodeBE, DecodeBE};
+
+use crate::compile_model::util::encode::*;
+use crate::compile_model::util::*;
+use crate::compile_model::lookup::*;
+use crate::compile_model::class_def::*;
+use crate::compile_model::coverage::*;
+
+
+#[derive(Debug)]
+pub struct ReverseChainSingle {
+    // both stored in logical (left-to-right) order. backtrack is reversed when encoding.
+    pub backtrack: Vec<ClassDef>,
+    pub lookahead: Vec<ClassDef>,
+
+    pub substitutes: CoverageLookup<u16>
+}
+
+impl ReverseChainSingle {
+    pub fn new(backtrack: Vec<ClassDef>, lookahead: Vec<ClassDef>) -> Self {
+        Self {
+            backtrack,
+            lookahead,
+
+            substitutes: CoverageLookup::new()
+        }
+    }
+
+    pub fn has_context(&self, backtrack: &[ClassDef], lookahead: &[ClassDef]) -> bool {
+        self.backtrack == backtrack && self.lookahead == lookahead
+    }
+
+    pub fn can_add(&self, glyph: u16, replacement: u16) -> bool {
+        match self.substitutes.get(&glyph) {
+            Some(existing) => *existing == replacement,
+            None => true
+        }
+    }
+}
+
+#[derive(Debug, PackedSize, EncodeBE, DecodeBE)]
+struct ReverseChainSingleSubstFormat1Header {
+    format: u16,
+    coverage_offset: u16
+}
+
+impl TTFEncode for ReverseChainSingle {
+    fn ttf_encode(&self, buf: &mut EncodeBuf) -> EncodeResult<usize> {
+        let start = buf.bytes.len();
+
+        buf.reserve_bytes(ReverseChainSingleSubstFormat1Header::PACKED_LEN);
+
+        let mut coverage_offsets = Vec::new();
+
+        let backtrack_count: u16 =
+            self.backtrack.len().checked_into("ReverseChainSingle", "backtrack glyph count")?;
+        buf.append(&backtrack_count)?;
+
+        for cls in self.backtrack.iter().rev() {
+            coverage_offsets.push((buf.bytes.len(), cls));
+            buf.reserve_bytes(u16::PACKED_LEN);
+        }
+
+        let lookahead_count: u16 =
+            self.lookahead.len().checked_into("ReverseChainSingle", "lookahead glyph count")?;
+        buf.append(&lookahead_count)?;
+
+        for cls in &self.lookahead {
+            coverage_offsets.push((buf.bytes.len(), cls));
+            buf.reserve_bytes(u16::PACKED_LEN);
+        }
+
+        let glyph_count: u16 =
+            self.substitutes.len().checked_into("ReverseChainSingle", "glyph count")?;
+        buf.append(&glyph_count)?;
+
+        for replacement in self.substitutes.values() {
+            buf.append(replacement)?;
+        }
+
+        let coverage_offset: u16 = (self.substitutes.ttf_encode(buf)? - start)
+            .checked_into("ReverseChainSingle", "coverage offset")?;
+
+        // context classes are frequently repeated, so share their coverage tables.
+        let mut encoded: HashMap<&ClassDef, u16> = HashMap::new();
+
+        for (offset_at, cls) in coverage_offsets {
+            let offset = match encoded.get(cls) {
+                Some(offset) => *offset,
+                None => {
+                    let offset: u16 =
+                        (CoverageLookup::<()>::encode(cls.iter().copied(), buf)? - start)
+                        .checked_into("ReverseChainSingle", "context coverage offset")?;
+
+                    encoded.insert(cls, offset);
+                    offset
+                }
+            };
+
+            buf.encode_at(&offset, offset_at)?;
+        }
+
+        let header = ReverseChainSingleSubstFormat1Header {
+            format: 1,
+            coverage_offset
+        };
+
+        buf.encode_at(&header, start)
+    }
+}
+
+impl TTFSubtableSplit for ReverseChainSingle {
+    // the context is the same for every covered glyph, so both halves keep all of it.
+    fn split(&self) -> Option<(Self, Self)> {
+        self.substitutes.split()
+            .map(|(a, b)| (
+                ReverseChainSingle {
+                    backtrack: self.backtrack.clone(),
+                    lookahead: self.lookahead.clone(),
+                    substitutes: a
+                },
+
+                ReverseChainSingle {
+                    backtrack: self.backtrack.clone(),
+                    lookahead: self.lookahead.clone(),
+                    substitutes: b
+                }))
+    }
+}
@@ -0,0 +1,112 @@
+use std::ops;
+
+use endian_codec::{PackedSize, EncodeBE, DecodeBE};
+
+use crate::compile_model::util::encode::*;
+use crate::compile_model::util::*;
+use crate::compile_model::lookup::*;
+use crate::compile_model::coverage::*;
+
+
+type inner = CoverageLookup<u16>;
+
+#[derive(Debug, Default)]
+pub struct Single(pub inner);
+
+impl ops::Deref for Single {
+    type Target = inner;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl ops::DerefMut for Single {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl Single {
+    pub fn can_add(&self, glyph: u16, replacement: u16) -> bool {
+        // re-stating an identical rule is harmless, but a glyph can only map to one replacement
+        // within a subtable.
+        match self.0.get(&glyph) {
+            Some(existing) => *existing == replacement,
+            None => true
+        }
+    }
+
+    // format 1 stores a single delta which is added (modulo 65536) to every covered glyph id.
+    // it's only usable if every rule in the subtable shares the same delta, but it's always
+    // smaller than format 2 when it is.
+    fn common_delta(&self) -> Option<i16> {
+        let mut deltas = self.0.iter()
+            .map(|(glyph, replacement)| replacement.wrapping_sub(*glyph) as i16);
+
+        let first = deltas.next().unwrap_or(0);
+
+        if deltas.all(|d| d == first) {
+            Some(first)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, PackedSize, EncodeBE, DecodeBE)]
+struct SingleSubstFormat1Header {
+    format: u16,
+    coverage_offset: u16,
+    delta_glyph_id: i16
+}
+
+#[derive(Debug, PackedSize, EncodeBE, DecodeBE)]
+struct SingleSubstFormat2Header {
+    format: u16,
+    coverage_offset: u16,
+    glyph_count: u16
+}
+
+impl TTFEncode for Single {
+    fn ttf_encode(&self, buf: &mut EncodeBuf) -> EncodeResult<usize> {
+        let start = buf.bytes.len();
+
+        match self.common_delta() {
+            Some(delta_glyph_id) =>
+                buf.defer_header_encode(
+                    |buf| Ok(SingleSubstFormat1Header {
+                        format: 1,
+                        coverage_offset: (self.0.ttf_encode(buf)? - start)
+                            .checked_into("SingleSubst", "coverage offset")?,
+                        delta_glyph_id
+                    }),
+
+                    |_| Ok(())),
+
+            None =>
+                buf.defer_header_encode(
+                    |buf| Ok(SingleSubstFormat2Header {
+                        format: 2,
+                        coverage_offset: (self.0.ttf_encode(buf)? - start)
+                            .checked_into("SingleSubst", "coverage offset")?,
+                        glyph_count: self.len().checked_into("SingleSubst", "glyph count")?
+                    }),
+
+                    |buf| {
+                        for replacement in self.values() {
+                            buf.append(replacement)?;
+                        }
+
+                        Ok(())
+                    })
+        }
+    }
+}
+
+impl TTFSubtableSplit for Single {
+    fn split(&self) -> Option<(Self, Self)> {
+        self.0.split()
+            .map(|(a, b)| (Single(a), Single(b)))
+    }
+}
@@ -0,0 +1,139 @@
+use endian_codec::{PackedSize, EncodeBE, DecodeBE};
+
+use crate::parse_model::*;
+use crate::compile_model::util::decode::*;
+use crate::compile_model::util::*;
+use crate::compile_model::error::*;
+
+#[derive(Debug, Clone, PackedSize, EncodeBE, DecodeBE)]
+pub struct Hhea {
+    pub major_version: u16,
+    pub minor_version: u16,
+
+    pub ascender: i16,
+    pub descender: i16,
+    pub line_gap: i16,
+
+    pub advance_width_max: u16,
+
+    pub min_left_side_bearing: i16,
+    pub min_right_side_bearing: i16,
+    pub x_max_extent: i16,
+
+    pub caret_slope_rise: i16,
+    pub caret_slope_run: i16,
+    pub caret_offset: i16,
+
+    pub reserved_0: i16,
+    pub reserved_1: i16,
+    pub reserved_2: i16,
+    pub reserved_3: i16,
+
+    // set to 0 for "current format"
+    pub metric_data_format: i16,
+
+    pub number_of_h_metrics: u16
+}
+
+impl Hhea {
+    pub fn new() -> Self {
+        Self {
+            major_version: 1,
+            minor_version: 0,
+
+            ascender: 0,
+            descender: 0,
+            line_gap: 0,
+
+            advance_width_max: 0,
+
+            min_left_side_bearing: 0,
+            min_right_side_bearing: 0,
+            x_max_extent: 0,
+
+            // vertical caret
+            caret_slope_rise: 1,
+            caret_slope_run: 0,
+            caret_offset: 0,
+
+            reserved_0: 0,
+            reserved_1: 0,
+            reserved_2: 0,
+            reserved_3: 0,
+
+            metric_data_format: 0,
+
+            number_of_h_metrics: 0
+        }
+    }
+}
+
+impl Default for Hhea {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TTFDecode for Hhea {
+    fn ttf_decode(bytes: &[u8]) -> DecodeResult<Self> {
+        if bytes.len() < Self::PACKED_LEN {
+            return Err(DecodeError::BufferUnderflow("hhea"));
+        }
+
+        Ok(decode_from_slice(bytes))
+    }
+}
+
+fn metric_into(m: &Metric, item: &'static str) -> CompileResult<i16> {
+    (m.value.trunc() as isize).checked_into("hhea", item)
+}
+
+// the metrics set by a feature file's `table hhea { ... } hhea;` block. only these are written
+// over the font's own hhea, everything else (advance widths, bearings, etc) comes from the glyphs.
+#[derive(Debug, Clone, Default)]
+pub struct HheaOverrides {
+    pub caret_offset: Option<i16>,
+    pub ascender: Option<i16>,
+    pub descender: Option<i16>,
+    pub line_gap: Option<i16>
+}
+
+impl HheaOverrides {
+    pub fn update_from_parsed_table(&mut self, statements: &[TableStatement]) -> CompileResult<()> {
+        for s in statements {
+            use TableStatement::*;
+
+            match s {
+                CaretOffset(hhea::CaretOffset(m)) =>
+                    self.caret_offset = Some(metric_into(m, "CaretOffset")?),
+                Ascender(hhea::Ascender(m)) =>
+                    self.ascender = Some(metric_into(m, "Ascender")?),
+                Descender(hhea::Descender(m)) =>
+                    self.descender = Some(metric_into(m, "Descender")?),
+                LineGap(hhea::LineGap(m)) =>
+                    self.line_gap = Some(metric_into(m, "LineGap")?),
+                _ => unreachable!()
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn apply(&self, hhea: &mut Hhea) {
+        if let Some(caret_offset) = self.caret_offset {
+            hhea.caret_offset = caret_offset;
+        }
+
+        if let Some(ascender) = self.ascender {
+            hhea.ascender = ascender;
+        }
+
+        if let Some(descender) = self.descender {
+            hhea.descender = descender;
+        }
+
+        if let Some(line_gap) = self.line_gap {
+            hhea.line_gap = line_gap;
+        }
+    }
+}
@@ -4,10 +4,32 @@ pub use gpos::GPOS;
 pub mod gsub;
 pub use gsub::GSUB;
 
-mod gdef;
+pub mod gdef;
+pub use gdef::GDEF;
 
 mod head;
 pub use head::Head;
 
+mod hhea;
+pub use hhea::{
+    Hhea,
+    HheaOverrides
+};
+
 mod name;
-pub use name::Name;
+pub use name::{
+    Name,
+    NameRecord
+};
+
+mod os2;
+pub use os2::{
+    Os2,
+    Os2Overrides
+};
+
+mod vhea;
+pub use vhea::{
+    Vhea,
+    VheaOverrides
+};
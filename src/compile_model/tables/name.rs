@@ -1,13 +1,42 @@
 use endian_codec::{PackedSize, EncodeBE, DecodeBE};
-use encoding_rs::UTF_16BE;
+use encoding_rs::{
+    UTF_16BE,
+    MACINTOSH
+};
 
 use crate::compile_model::util::decode::*;
 use crate::parse_model as pm;
 
-#[derive(Debug)]
+#[derive(Debug, Default, Clone)]
 pub struct Name(pub Vec<NameRecord>);
 
 impl Name {
+    // name IDs from here up are for names referenced by other tables (like the subfamily name of
+    // the `size` feature).
+    pub const FIRST_USER_NAME_ID: usize = 256;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // new user name IDs are allocated past any which are already in use.
+    pub fn next_user_name_id(&self) -> usize {
+        self.0.iter()
+            .map(|nr| nr.name_id as usize + 1)
+            .max()
+            .unwrap_or(0)
+            .max(Self::FIRST_USER_NAME_ID)
+    }
+
+    // adds `records`, replacing any existing ones for the same name ID with the same platform,
+    // encoding and language.
+    pub fn merge<I: IntoIterator<Item = NameRecord>>(&mut self, records: I) {
+        for record in records {
+            self.0.retain(|nr| nr.key() != record.key());
+            self.0.push(record);
+        }
+    }
+
     pub fn from_parsed_table(statements: &[pm::TableStatement]) -> Self {
         let records = statements.iter().filter_map(|s| {
             if let pm::TableStatement::NameId(n) = s {
@@ -35,12 +64,21 @@ impl Name {
         // vec directly
         res.resize(res.len() + records_size, 0u8);
 
-        for (i, nr) in self.0.iter().enumerate() {
+        // records have to be sorted by platform, encoding, language and then name ID.
+        let mut records: Vec<_> = self.0.iter().collect();
+        records.sort_by_key(|nr| (nr.platform_id, nr.encoding_id, nr.language_id, nr.name_id));
+
+        for (i, nr) in records.into_iter().enumerate() {
             let start = res.len();
 
-            res.reserve(nr.name.as_bytes().len());
-            for c in nr.name.encode_utf16() {
-                res.extend(&c.to_be_bytes());
+            if nr.platform_id == 1 {
+                // FIXME: other mac encodings?
+                res.extend(&*MACINTOSH.encode(&nr.name).0);
+            } else {
+                res.reserve(nr.name.as_bytes().len());
+                for c in nr.name.encode_utf16() {
+                    res.extend(&c.to_be_bytes());
+                }
             }
 
             let record = EncodedNameRecord {
@@ -89,7 +127,7 @@ struct EncodedNameRecord {
     offset: u16
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct NameRecord {
     pub platform_id: u16,
     pub encoding_id: u16,
@@ -99,6 +137,10 @@ pub struct NameRecord {
 }
 
 impl NameRecord {
+    fn key(&self) -> (u16, u16, u16, u16) {
+        (self.platform_id, self.encoding_id, self.language_id, self.name_id)
+    }
+
     fn from_encoded(e: EncodedNameRecord, string_storage: &[u8]) -> Self {
         let EncodedNameRecord {
             platform_id,
@@ -111,17 +153,29 @@ impl NameRecord {
         let start = e.offset as usize;
         let end = start + (e.string_length as usize);
 
+        let encoding = if platform_id == 1 { MACINTOSH } else { UTF_16BE };
+
         Self {
             platform_id,
             encoding_id,
             language_id,
             name_id,
             name:
-                UTF_16BE.decode_without_bom_handling(&string_storage[start..end])
+                encoding.decode_without_bom_handling(&string_storage[start..end])
                     .0.into_owned()
         }
     }
 
+    pub fn from_parsed_name(name_id: u16, n: &pm::Name) -> Self {
+        Self {
+            name_id,
+            platform_id: n.platform_id as u16,
+            encoding_id: n.script_id as u16,
+            language_id: n.language_id as u16,
+            name: n.name.clone()
+        }
+    }
+
     fn from_parse_model(n: &pm::tables::name::NameId) -> Self {
         Self {
             name_id: n.name_id as u16,
@@ -0,0 +1,460 @@
+use endian_codec::{PackedSize, EncodeBE, DecodeBE};
+
+use crate::parse_model::*;
+use crate::compile_model::util::decode::*;
+use crate::compile_model::util::encode::*;
+use crate::compile_model::util::*;
+use crate::compile_model::error::*;
+
+// the fields shared by every version of the table, which make up all of version 0.
+#[derive(Debug, Clone, PackedSize, EncodeBE, DecodeBE)]
+pub struct Os2Common {
+    pub version: u16,
+
+    pub x_avg_char_width: i16,
+    pub us_weight_class: u16,
+    pub us_width_class: u16,
+    pub fs_type: u16,
+
+    pub y_subscript_x_size: i16,
+    pub y_subscript_y_size: i16,
+    pub y_subscript_x_offset: i16,
+    pub y_subscript_y_offset: i16,
+    pub y_superscript_x_size: i16,
+    pub y_superscript_y_size: i16,
+    pub y_superscript_x_offset: i16,
+    pub y_superscript_y_offset: i16,
+    pub y_strikeout_size: i16,
+    pub y_strikeout_position: i16,
+
+    pub s_family_class: i16,
+    pub panose: [u8; 10],
+
+    pub ul_unicode_range_1: u32,
+    pub ul_unicode_range_2: u32,
+    pub ul_unicode_range_3: u32,
+    pub ul_unicode_range_4: u32,
+
+    pub ach_vend_id: [u8; 4],
+
+    pub fs_selection: u16,
+    pub us_first_char_index: u16,
+    pub us_last_char_index: u16,
+
+    pub s_typo_ascender: i16,
+    pub s_typo_descender: i16,
+    pub s_typo_line_gap: i16,
+    pub us_win_ascent: u16,
+    pub us_win_descent: u16
+}
+
+// version 1
+#[derive(Debug, Clone, Default, PackedSize, EncodeBE, DecodeBE)]
+pub struct Os2CodePages {
+    pub ul_code_page_range_1: u32,
+    pub ul_code_page_range_2: u32
+}
+
+// versions 2 through 4 share a layout, and only differ in how some fields are interpreted.
+#[derive(Debug, Clone, Default, PackedSize, EncodeBE, DecodeBE)]
+pub struct Os2Metrics {
+    pub sx_height: i16,
+    pub s_cap_height: i16,
+    pub us_default_char: u16,
+    pub us_break_char: u16,
+    pub us_max_context: u16
+}
+
+// version 5
+#[derive(Debug, Clone, Default, PackedSize, EncodeBE, DecodeBE)]
+pub struct Os2OpticalSize {
+    pub us_lower_optical_point_size: u16,
+    pub us_upper_optical_point_size: u16
+}
+
+#[derive(Debug, Clone)]
+pub struct Os2 {
+    pub common: Os2Common,
+    pub code_pages: Os2CodePages,
+    pub metrics: Os2Metrics,
+    pub optical_size: Os2OpticalSize
+}
+
+impl Os2 {
+    pub fn new() -> Self {
+        Self {
+            common: Os2Common {
+                version: 4,
+
+                x_avg_char_width: 0,
+                us_weight_class: 400,
+                us_width_class: 5,
+                fs_type: 0,
+
+                y_subscript_x_size: 0,
+                y_subscript_y_size: 0,
+                y_subscript_x_offset: 0,
+                y_subscript_y_offset: 0,
+                y_superscript_x_size: 0,
+                y_superscript_y_size: 0,
+                y_superscript_x_offset: 0,
+                y_superscript_y_offset: 0,
+                y_strikeout_size: 0,
+                y_strikeout_position: 0,
+
+                s_family_class: 0,
+                panose: [0; 10],
+
+                ul_unicode_range_1: 0,
+                ul_unicode_range_2: 0,
+                ul_unicode_range_3: 0,
+                ul_unicode_range_4: 0,
+
+                ach_vend_id: *b"    ",
+
+                fs_selection: 0,
+                us_first_char_index: 0,
+                us_last_char_index: 0,
+
+                s_typo_ascender: 0,
+                s_typo_descender: 0,
+                s_typo_line_gap: 0,
+                us_win_ascent: 0,
+                us_win_descent: 0
+            },
+
+            code_pages: Default::default(),
+
+            metrics: Os2Metrics {
+                us_break_char: 0x20,
+                ..Default::default()
+            },
+
+            optical_size: Os2OpticalSize {
+                us_lower_optical_point_size: 0,
+                us_upper_optical_point_size: 0xFFFF
+            }
+        }
+    }
+
+    #[inline]
+    pub fn version(&self) -> u16 {
+        self.common.version
+    }
+
+    fn packed_len_for_version(version: u16) -> usize {
+        let mut len = Os2Common::PACKED_LEN;
+
+        if version >= 1 {
+            len += Os2CodePages::PACKED_LEN;
+        }
+
+        if version >= 2 {
+            len += Os2Metrics::PACKED_LEN;
+        }
+
+        if version >= 5 {
+            len += Os2OpticalSize::PACKED_LEN;
+        }
+
+        len
+    }
+}
+
+impl Default for Os2 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TTFEncode for Os2 {
+    fn ttf_encode(&self, buf: &mut EncodeBuf) -> EncodeResult<usize> {
+        let version = self.version();
+        let start = buf.append(&self.common)?;
+
+        if version >= 1 {
+            buf.append(&self.code_pages)?;
+        }
+
+        if version >= 2 {
+            buf.append(&self.metrics)?;
+        }
+
+        if version >= 5 {
+            buf.append(&self.optical_size)?;
+        }
+
+        Ok(start)
+    }
+}
+
+impl TTFDecode for Os2 {
+    fn ttf_decode(bytes: &[u8]) -> DecodeResult<Self> {
+        if bytes.len() < Os2Common::PACKED_LEN {
+            return Err(DecodeError::BufferUnderflow("OS/2"));
+        }
+
+        let common: Os2Common = decode_from_slice(bytes);
+        let version = common.version;
+
+        if bytes.len() < Self::packed_len_for_version(version) {
+            return Err(DecodeError::BufferUnderflow("OS/2"));
+        }
+
+        // fields from versions newer than the font's table keep their defaults, so that bumping
+        // the version when applying overrides doesn't write out garbage.
+        let mut os2 = Self {
+            common,
+            ..Self::new()
+        };
+
+        let mut offset = Os2Common::PACKED_LEN;
+
+        if version >= 1 {
+            os2.code_pages = decode_from_slice(&bytes[offset..]);
+            offset += Os2CodePages::PACKED_LEN;
+        }
+
+        if version >= 2 {
+            os2.metrics = decode_from_slice(&bytes[offset..]);
+            offset += Os2Metrics::PACKED_LEN;
+        }
+
+        if version >= 5 {
+            os2.optical_size = decode_from_slice(&bytes[offset..]);
+        }
+
+        Ok(os2)
+    }
+}
+
+// bit number in ulCodePageRange1/2 for each code page the spec assigns one to.
+fn code_page_bit(code_page: usize) -> Option<u32> {
+    Some(match code_page {
+        1252 => 0,
+        1250 => 1,
+        1251 => 2,
+        1253 => 3,
+        1254 => 4,
+        1255 => 5,
+        1256 => 6,
+        1257 => 7,
+        1258 => 8,
+        874 => 16,
+        932 => 17,
+        936 => 18,
+        949 => 19,
+        950 => 20,
+        1361 => 21,
+        869 => 48,
+        866 => 49,
+        865 => 50,
+        864 => 51,
+        863 => 52,
+        862 => 53,
+        861 => 54,
+        860 => 55,
+        857 => 56,
+        855 => 57,
+        852 => 58,
+        775 => 59,
+        737 => 60,
+        708 => 61,
+        850 => 62,
+        437 => 63,
+
+        _ => return None
+    })
+}
+
+fn number_into<T>(n: usize, item: &'static str) -> CompileResult<T>
+    where T: CheckedFrom<usize, CompileError>
+{
+    n.checked_into("OS/2", item)
+}
+
+fn metric_into<T>(m: &Metric, item: &'static str) -> CompileResult<T>
+    where T: CheckedFrom<isize, CompileError>
+{
+    (m.value.trunc() as isize).checked_into("OS/2", item)
+}
+
+// as with hhea and vhea, only the fields which the feature file sets are written over the font's
+// OS/2 table. the table's version is raised if one of them doesn't exist in older versions.
+#[derive(Debug, Clone, Default)]
+pub struct Os2Overrides {
+    pub fs_type: Option<u16>,
+    pub panose: Option<[u8; 10]>,
+    pub unicode_range: Option<[u32; 4]>,
+    pub code_page_range: Option<[u32; 2]>,
+
+    pub typo_ascender: Option<i16>,
+    pub typo_descender: Option<i16>,
+    pub typo_line_gap: Option<i16>,
+    pub win_ascent: Option<u16>,
+    pub win_descent: Option<u16>,
+    pub x_height: Option<i16>,
+    pub cap_height: Option<i16>,
+
+    pub weight_class: Option<u16>,
+    pub width_class: Option<u16>,
+    pub vendor: Option<[u8; 4]>,
+    pub family_class: Option<i16>,
+
+    pub lower_op_size: Option<u16>,
+    pub upper_op_size: Option<u16>
+}
+
+impl Os2Overrides {
+    pub fn update_from_parsed_table(&mut self, statements: &[TableStatement]) -> CompileResult<()> {
+        for s in statements {
+            use TableStatement::*;
+
+            match s {
+                FSType(os2::FSType(n)) =>
+                    self.fs_type = Some(number_into(*n, "FSType")?),
+
+                Panose(os2::Panose(numbers)) => {
+                    let mut panose = [0u8; 10];
+
+                    for (p, n) in panose.iter_mut().zip(numbers.iter()) {
+                        *p = (*n).checked_into("OS/2", "Panose")?;
+                    }
+
+                    self.panose = Some(panose);
+                },
+
+                UnicodeRange(os2::UnicodeRange(bits)) => {
+                    let mut range = [0u32; 4];
+
+                    for &bit in bits {
+                        // bits 123 through 127 are reserved.
+                        if bit > 122 {
+                            return Err(CompileError::InvalidValue("UnicodeRange bit", bit));
+                        }
+
+                        range[bit / 32] |= 1 << (bit % 32);
+                    }
+
+                    self.unicode_range = Some(range);
+                },
+
+                CodePageRange(os2::CodePageRange(code_pages)) => {
+                    let mut range = [0u32; 2];
+
+                    for &code_page in code_pages {
+                        let bit = code_page_bit(code_page)
+                            .ok_or(CompileError::InvalidValue("code page", code_page))?;
+
+                        range[(bit / 32) as usize] |= 1 << (bit % 32);
+                    }
+
+                    self.code_page_range = Some(range);
+                },
+
+                TypoAscender(os2::TypoAscender(m)) =>
+                    self.typo_ascender = Some(metric_into(m, "TypoAscender")?),
+                TypoDescender(os2::TypoDescender(m)) =>
+                    self.typo_descender = Some(metric_into(m, "TypoDescender")?),
+                TypoLineGap(os2::TypoLineGap(m)) =>
+                    self.typo_line_gap = Some(metric_into(m, "TypoLineGap")?),
+                WinAscent(os2::WinAscent(m)) =>
+                    self.win_ascent = Some(metric_into(m, "winAscent")?),
+                WinDescent(os2::WinDescent(m)) =>
+                    self.win_descent = Some(metric_into(m, "winDescent")?),
+                XHeight(os2::XHeight(m)) =>
+                    self.x_height = Some(metric_into(m, "XHeight")?),
+                CapHeight(os2::CapHeight(m)) =>
+                    self.cap_height = Some(metric_into(m, "CapHeight")?),
+
+                WeightClass(os2::WeightClass(n)) =>
+                    self.weight_class = Some(number_into(*n, "WeightClass")?),
+
+                WidthClass(os2::WidthClass(n)) => {
+                    if !(1..=9).contains(n) {
+                        return Err(CompileError::InvalidValue("WidthClass", *n));
+                    }
+
+                    self.width_class = Some(*n as u16);
+                },
+
+                Vendor(os2::Vendor(tag)) => {
+                    let mut id = [0u8; 4];
+
+                    for (byte, ch) in id.iter_mut().zip(tag.0.iter()) {
+                        *byte = ch.as_byte();
+                    }
+
+                    self.vendor = Some(id);
+                },
+
+                FamilyClass(os2::FamilyClass(n)) =>
+                    self.family_class = Some((*n).checked_into("OS/2", "FamilyClass")?),
+
+                LowerOpSize(os2::LowerOpSize(n)) =>
+                    self.lower_op_size = Some(number_into(*n, "LowerOpSize")?),
+                UpperOpSize(os2::UpperOpSize(n)) =>
+                    self.upper_op_size = Some(number_into(*n, "UpperOpSize")?),
+
+                _ => unreachable!()
+            }
+        }
+
+        Ok(())
+    }
+
+    fn required_version(&self) -> u16 {
+        if self.lower_op_size.is_some() || self.upper_op_size.is_some() {
+            5
+        } else if self.x_height.is_some() || self.cap_height.is_some() {
+            2
+        } else if self.code_page_range.is_some() {
+            1
+        } else {
+            0
+        }
+    }
+
+    pub fn apply(&self, os2: &mut Os2) {
+        os2.common.version = os2.common.version.max(self.required_version());
+
+        macro_rules! set {
+            ($field:ident => $($dest:tt)+) => {
+                if let Some(value) = self.$field {
+                    os2.$($dest)+ = value;
+                }
+            }
+        }
+
+        set!(fs_type => common.fs_type);
+        set!(panose => common.panose);
+
+        if let Some(range) = self.unicode_range {
+            os2.common.ul_unicode_range_1 = range[0];
+            os2.common.ul_unicode_range_2 = range[1];
+            os2.common.ul_unicode_range_3 = range[2];
+            os2.common.ul_unicode_range_4 = range[3];
+        }
+
+        if let Some(range) = self.code_page_range {
+            os2.code_pages.ul_code_page_range_1 = range[0];
+            os2.code_pages.ul_code_page_range_2 = range[1];
+        }
+
+        set!(typo_ascender => common.s_typo_ascender);
+        set!(typo_descender => common.s_typo_descender);
+        set!(typo_line_gap => common.s_typo_line_gap);
+        set!(win_ascent => common.us_win_ascent);
+        set!(win_descent => common.us_win_descent);
+        set!(x_height => metrics.sx_height);
+        set!(cap_height => metrics.s_cap_height);
+
+        set!(weight_class => common.us_weight_class);
+        set!(width_class => common.us_width_class);
+        set!(vendor => common.ach_vend_id);
+        set!(family_class => common.s_family_class);
+
+        set!(lower_op_size => optical_size.us_lower_optical_point_size);
+        set!(upper_op_size => optical_size.us_upper_optical_point_size);
+    }
+}
@@ -0,0 +1,128 @@
+use endian_codec::{PackedSize, EncodeBE, DecodeBE};
+
+use crate::parse_model::*;
+use crate::compile_model::util::decode::*;
+use crate::compile_model::util::*;
+use crate::compile_model::error::*;
+
+#[derive(Debug, Clone, PackedSize, EncodeBE, DecodeBE)]
+pub struct Vhea {
+    // version 1.1 is 0x00011000, which isn't a "real" fixed-point 1.1.
+    pub major_version: u16,
+    pub minor_version: u16,
+
+    pub vert_typo_ascender: i16,
+    pub vert_typo_descender: i16,
+    pub vert_typo_line_gap: i16,
+
+    pub advance_height_max: i16,
+
+    pub min_top_side_bearing: i16,
+    pub min_bottom_side_bearing: i16,
+    pub y_max_extent: i16,
+
+    pub caret_slope_rise: i16,
+    pub caret_slope_run: i16,
+    pub caret_offset: i16,
+
+    pub reserved_0: i16,
+    pub reserved_1: i16,
+    pub reserved_2: i16,
+    pub reserved_3: i16,
+
+    // set to 0 for "current format"
+    pub metric_data_format: i16,
+
+    pub num_of_long_ver_metrics: u16
+}
+
+impl Vhea {
+    pub fn new() -> Self {
+        Self {
+            major_version: 1,
+            minor_version: 0x1000,
+
+            vert_typo_ascender: 0,
+            vert_typo_descender: 0,
+            vert_typo_line_gap: 0,
+
+            advance_height_max: 0,
+
+            min_top_side_bearing: 0,
+            min_bottom_side_bearing: 0,
+            y_max_extent: 0,
+
+            // horizontal caret
+            caret_slope_rise: 0,
+            caret_slope_run: 1,
+            caret_offset: 0,
+
+            reserved_0: 0,
+            reserved_1: 0,
+            reserved_2: 0,
+            reserved_3: 0,
+
+            metric_data_format: 0,
+
+            num_of_long_ver_metrics: 0
+        }
+    }
+}
+
+impl Default for Vhea {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TTFDecode for Vhea {
+    fn ttf_decode(bytes: &[u8]) -> DecodeResult<Self> {
+        if bytes.len() < Self::PACKED_LEN {
+            return Err(DecodeError::BufferUnderflow("vhea"));
+        }
+
+        Ok(decode_from_slice(bytes))
+    }
+}
+
+// as with hhea, only the metrics which the feature file sets are written over the font's vhea.
+#[derive(Debug, Clone, Default)]
+pub struct VheaOverrides {
+    pub vert_typo_ascender: Option<i16>,
+    pub vert_typo_descender: Option<i16>,
+    pub vert_typo_line_gap: Option<i16>
+}
+
+impl VheaOverrides {
+    pub fn update_from_parsed_table(&mut self, statements: &[TableStatement]) -> CompileResult<()> {
+        for s in statements {
+            use TableStatement::*;
+
+            match s {
+                VertTypoAscender(vhea::VertTypoAscender(n)) =>
+                    self.vert_typo_ascender = Some((*n).checked_into("vhea", "VertTypoAscender")?),
+                VertTypoDescender(vhea::VertTypoDescender(n)) =>
+                    self.vert_typo_descender = Some((*n).checked_into("vhea", "VertTypoDescender")?),
+                VertTypoLineGap(vhea::VertTypoLineGap(n)) =>
+                    self.vert_typo_line_gap = Some((*n).checked_into("vhea", "VertTypoLineGap")?),
+                _ => unreachable!()
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn apply(&self, vhea: &mut Vhea) {
+        if let Some(ascender) = self.vert_typo_ascender {
+            vhea.vert_typo_ascender = ascender;
+        }
+
+        if let Some(descender) = self.vert_typo_descender {
+            vhea.vert_typo_descender = descender;
+        }
+
+        if let Some(line_gap) = self.vert_typo_line_gap {
+            vhea.vert_typo_line_gap = line_gap;
+        }
+    }
+}
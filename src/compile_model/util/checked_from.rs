@@ -47,7 +47,7 @@ impl<T, F> CheckedFrom<F, CompileError> for T
                 ty: type_name::<T>(),
                 scope: scope.into(),
                 item,
-                value: value.try_into().unwrap_or(0).into()
+                value: Box::new(value.try_into().unwrap_or(0).into())
             })
     }
 }
@@ -61,10 +61,10 @@ impl<T, F> CheckedFrom<&MaybePositioned<F>, CompileError> for T
                 ty: type_name::<T>(),
                 scope: scope.into(),
                 item,
-                value: MaybePositioned {
+                value: Box::new(MaybePositioned {
                     value: value.value.try_into().unwrap_or(0),
                     span: value.span.clone()
-                }
+                })
             })
     }
 }
@@ -18,6 +18,9 @@ use crate::compile_model::{
     CompiledEntry
 };
 
+use crate::compile_model::lookup::ExtensionSubtables;
+use crate::compile_model::util::CheckedInto;
+
 use crate::SourceSpan;
 
 use crate::glyph_order::*;
@@ -31,7 +34,15 @@ pub struct EncodeBuf {
     pub(crate) bytes: Vec<u8>,
     pub(crate) source_map: SourceMap,
 
-    pub(crate) should_optimize_filesize: bool
+    pub(crate) should_optimize_filesize: bool,
+
+    // when set, every lookup is encoded as an extension lookup regardless of `useExtension`.
+    pub(crate) use_extension_lookups: bool,
+    pub(crate) extension_subtables: Vec<ExtensionSubtables>,
+
+    // added to the name IDs in feature params, when the names they refer to have been moved past
+    // the ones already in the font.
+    pub(crate) name_id_offset: u16
 }
 
 impl EncodeBuf {
@@ -40,7 +51,12 @@ impl EncodeBuf {
             bytes: Vec::new(),
             source_map: SourceMap::new(),
 
-            should_optimize_filesize: false
+            should_optimize_filesize: false,
+
+            use_extension_lookups: false,
+            extension_subtables: Vec::new(),
+
+            name_id_offset: 0
         }
     }
 
@@ -49,7 +65,12 @@ impl EncodeBuf {
             bytes: Vec::new(),
             source_map: SourceMap::new(),
 
-            should_optimize_filesize: false
+            should_optimize_filesize: false,
+
+            use_extension_lookups: false,
+            extension_subtables: Vec::new(),
+
+            name_id_offset: 0
         }
     }
 
@@ -68,13 +89,24 @@ impl EncodeBuf {
         self.bytes.resize(self.bytes.len() + to_reserve, 0u8);
     }
 
+    // discards everything encoded from `len` onwards, so that a failed encode can be retried.
+    pub(crate) fn truncate(&mut self, len: usize) {
+        self.bytes.truncate(len);
+
+        for entries in self.source_map.values_mut() {
+            entries.retain(|_, entry| match entry {
+                CompiledEntry::I16(loc) => *loc < len
+            });
+        }
+    }
+
     #[inline]
     pub(crate) fn add_source_map_entry(&mut self, span: &SourceSpan, entry: CompiledEntry) {
         if self.should_optimize_filesize() {
             return
         }
 
-        self.source_map.entry(span.start.line)
+        self.source_map.entry((span.file, span.start.line))
             .or_default()
             .insert(span.start.column, entry);
     }
@@ -170,7 +202,8 @@ impl EncodeBuf {
                 }
             };
 
-            let item_offset = (item_offset - table_start) as u16;
+            let item_offset: u16 = (item_offset - table_start)
+                .checked_into("pool", "item offset")?;
 
             self.encode_at(
                 &record_for_offset(item_offset, &item),
@@ -37,3 +37,4 @@ macro_rules! impl_for {
 impl_for!(Tag);
 impl_for!(FeatureTag);
 impl_for!(ScriptTag);
+impl_for!(LanguageTag);
@@ -5,8 +5,10 @@ use endian_codec::PackedSize;
 
 use crate::compile_model::util::decode::*;
 use crate::compile_model::util::encode::*;
+use crate::compile_model::util::CheckedInto;
 use crate::compile_model::device::*;
 use crate::compile_model::error::*;
+use crate::compile_model::compiler_state::NamedValueRecordTable;
 
 use crate::parse_model as pm;
 
@@ -194,7 +196,8 @@ impl ValueRecord {
                 if (format & (1u16 << $shift)) != 0 {
                     let offset = match self.$var.as_ref() {
                         Some(dev) if !dev.is_empty() =>
-                            (buf.append(dev)? - parent_table_start) as u16,
+                            (buf.append(dev)? - parent_table_start)
+                                .checked_into("ValueRecord", "device offset")?,
 
                         _ => 0u16
                     };
@@ -216,7 +219,7 @@ impl ValueRecord {
 
 
 pub trait ValueRecordFromParsed<T>: Sized {
-    fn from_parsed(parsed: T, vertical: bool) -> CompileResult<Self>;
+    fn from_parsed(parsed: T, vertical: bool, named: &NamedValueRecordTable) -> CompileResult<Self>;
 }
 
 #[inline]
@@ -231,7 +234,8 @@ fn metric_to_i16_checked(m: &pm::Metric) -> CompileResult<MaybePositioned<i16>>
 
 impl ValueRecordFromParsed<&pm::ValueRecord> for ValueRecord {
     // FIXME: return a Result<> if the f64 -> i16 fails
-    fn from_parsed(parsed: &pm::ValueRecord, vertical: bool) -> CompileResult<Self> {
+    fn from_parsed(parsed: &pm::ValueRecord, vertical: bool, named: &NamedValueRecordTable)
+            -> CompileResult<Self> {
         use pm::ValueRecord::*;
 
         Ok(match parsed {
@@ -275,14 +279,21 @@ impl ValueRecordFromParsed<&pm::ValueRecord> for ValueRecord {
             },
 
             Null => Self::zero(),
+
+            // named value records are stored as they were parsed, since a format A record means
+            // something different depending on whether it's used in a vertical feature.
+            Named(name) => named.get(name)
+                .ok_or_else(|| CompileError::UndefinedReference("value record", name.into()))
+                .and_then(|vr| Self::from_parsed(vr, vertical, named))?
         })
     }
 }
 
 impl ValueRecordFromParsed<&Option<pm::ValueRecord>> for ValueRecord {
-    fn from_parsed(parsed: &Option<pm::ValueRecord>, vertical: bool) -> CompileResult<Self> {
+    fn from_parsed(parsed: &Option<pm::ValueRecord>, vertical: bool, named: &NamedValueRecordTable)
+            -> CompileResult<Self> {
         parsed.as_ref()
-            .map(|vr| Self::from_parsed(vr, vertical))
+            .map(|vr| Self::from_parsed(vr, vertical, named))
             .unwrap_or_else(|| Ok(Self::zero()))
     }
 }
@@ -1,12 +1,17 @@
+use std::mem;
+use std::collections::{
+    BTreeMap,
+    BTreeSet
+};
 use std::convert::TryInto;
-use std::iter;
 
 use crate::*;
 use crate::glyph_class::*;
-use crate::util::*;
 
 use crate::compile_model::*;
+use crate::compile_model::util::decode::TTFDecode;
 use crate::compile_model::util::encode::*;
+use crate::compile_model::util::*;
 
 use crate::parse_model as pm;
 
@@ -34,27 +39,67 @@ fn feature_is_vertical(tag: &FeatureTag) -> bool {
 use crate::compile_model::lookup::*;
 use tables::{
     gsub,
-    gpos
+    gpos,
+    gdef,
+    NameRecord
 };
+use gdef::GlyphType;
 
 struct Block<'a> {
     ident: BlockIdent<'a>,
-    subtable_breaks: usize
+    subtable_breaks: usize,
+
+    lookup_flags: LookupFlags,
+    mark_filtering_set: Option<u16>,
+    use_extension: bool,
+
+    // only meaningful for feature blocks. `script` is the script that a `language` statement
+    // applies to, and `language_systems` are the ones that new lookups are registered under.
+    script: ScriptTag,
+    language_systems: Vec<LanguageSystem>,
+    required_in: Vec<LanguageSystem>
 }
 
 impl<'a> Block<'a> {
-    pub fn new_feature(tag: &'a FeatureTag) -> Self {
+    fn new(ident: BlockIdent<'a>, language_systems: Vec<LanguageSystem>) -> Self {
         Self {
-            ident: BlockIdent::Feature(tag),
-            subtable_breaks: 0
+            ident,
+            subtable_breaks: 0,
+
+            lookup_flags: LookupFlags::empty(),
+            mark_filtering_set: None,
+            use_extension: false,
+
+            script: script_tag!(D,F,L,T),
+            language_systems,
+            required_in: Vec::new()
         }
     }
 
+    pub fn new_feature(tag: &'a FeatureTag, language_systems: &[LanguageSystem]) -> Self {
+        let language_systems =
+            if language_systems.is_empty() {
+                vec![LanguageSystem::default()]
+            } else {
+                language_systems.to_vec()
+            };
+
+        Self::new(BlockIdent::Feature(tag), language_systems)
+    }
+
     pub fn new_lookup(name: &'a pm::LookupName) -> Self {
-        Self {
-            ident: BlockIdent::Lookup(name),
-            subtable_breaks: 0
+        Self::new(BlockIdent::Lookup(name), Vec::new())
+    }
+
+    pub fn set_lookup_flags(&mut self, flags: LookupFlags, mark_filtering_set: Option<u16>) {
+        // subtable breaks are counted per lookup, and changing the flags moves us on to a
+        // different one. restating the current flags carries on with the current lookup.
+        if flags != self.lookup_flags || mark_filtering_set != self.mark_filtering_set {
+            self.subtable_breaks = 0;
         }
+
+        self.lookup_flags = flags;
+        self.mark_filtering_set = mark_filtering_set;
     }
 
     pub fn add_subtable_break(&mut self) {
@@ -75,26 +120,43 @@ impl<'a> Block<'a> {
         }
     }
 
-    fn find_or_insert_lookup<'b, T, L, S>(&self, table: &'b mut T) -> &'b mut Lookup<S>
-        where T: KeyedLookups<FeatureTag, L> + KeyedLookups<pm::LookupName, L>,
-              S: LookupSubtable<L>
-    {
+    fn is_aalt(&self) -> bool {
+        match self.ident {
+            BlockIdent::Feature(tag) => *tag == feature_tag!(a,a,l,t),
+            BlockIdent::Lookup(_) => false,
+        }
+    }
+
+    fn feature_scope(&self) -> Option<FeatureScope<'_>> {
         match self.ident {
-            BlockIdent::Feature(f) => table.find_or_insert_lookup(f),
-            BlockIdent::Lookup(l) => table.find_or_insert_lookup(l)
+            BlockIdent::Feature(tag) => Some(FeatureScope {
+                tag,
+                language_systems: &self.language_systems
+            }),
+
+            BlockIdent::Lookup(_) => None
         }
     }
 
-    fn insert_into_script<T>(&self, table: &mut LookupTable<T>, script_tag: &ScriptTag) {
-        let feature_tag = match self.ident {
-            BlockIdent::Feature(tag) => tag,
-            BlockIdent::Lookup(_) => return
+    fn find_or_insert_lookup<'b, L, S>(&self, table: &'b mut LookupTable<L>) -> &'b mut Lookup<S>
+        where S: LookupSubtable<L>
+    {
+        let lookup: &mut Lookup<S> = match self.ident {
+            BlockIdent::Feature(tag) => {
+                let scope = FeatureScope {
+                    tag,
+                    language_systems: &self.language_systems
+                };
+
+                table.find_or_insert_lookup(&scope, self.lookup_flags, self.mark_filtering_set)
+            },
+
+            BlockIdent::Lookup(l) =>
+                table.find_or_insert_lookup(l, self.lookup_flags, self.mark_filtering_set)
         };
 
-        table.script_list.script_for_tag_mut(script_tag)
-            .default_lang_sys
-            .features
-            .insert(*feature_tag);
+        lookup.use_extension |= self.use_extension;
+        lookup
     }
 }
 
@@ -108,7 +170,7 @@ fn handle_single_adjustment_position(ctx: &mut CompilerState, block: &Block,
     let gpos = ctx.gpos.get_or_insert_with(|| tables::GPOS::new());
     let lookup: &mut Lookup<gpos::Single> = block.find_or_insert_lookup(gpos);
 
-    let vr = ValueRecord::from_parsed(&pos.value_record, block.is_vertical())?;
+    let vr = ValueRecord::from_parsed(&pos.value_record, block.is_vertical(), &ctx.value_record_table)?;
 
     if let Some(glyph) = pos.glyph_class.as_single() {
         let glyph_id = ctx.glyph_order.id_for_glyph(glyph)?;
@@ -143,8 +205,8 @@ fn handle_pair_position_glyphs(ctx: &mut CompilerState, block: &Block, pair: &pm
 
     let vertical = block.is_vertical();
 
-    let vr1 = ValueRecord::from_parsed(&value_records.0, vertical)?;
-    let vr2 = ValueRecord::from_parsed(&value_records.1, vertical)?;
+    let vr1 = ValueRecord::from_parsed(&value_records.0, vertical, &ctx.value_record_table)?;
+    let vr2 = ValueRecord::from_parsed(&value_records.1, vertical, &ctx.value_record_table)?;
 
     let value_formats = (
         vr1.smallest_possible_format(),
@@ -188,8 +250,8 @@ fn handle_pair_position_class(ctx: &mut CompilerState, block: &Block, pair: &pm:
     );
 
     let value_records = (
-        ValueRecord::from_parsed(&value_records.0, vertical)?,
-        ValueRecord::from_parsed(&value_records.1, vertical)?
+        ValueRecord::from_parsed(&value_records.0, vertical, &ctx.value_record_table)?,
+        ValueRecord::from_parsed(&value_records.1, vertical, &ctx.value_record_table)?
     );
 
     let mut skip = block.subtable_breaks;
@@ -244,50 +306,194 @@ fn handle_cursive_position(ctx: &mut CompilerState, block: &Block, cursive: &pm:
 fn handle_mark_to_base_position(ctx: &mut CompilerState, block: &Block, m2b: &pm::position::MarkToBase) -> CompileResult<()> {
     ctx.mark_class_statements_allowed = false;
 
+    let anchors = m2b.marks.iter()
+        .map(|(anchor, _)| ctx.lookup_anchor(anchor))
+        .collect::<CompileResult<Vec<_>>>()?;
+
     let gpos = ctx.gpos.get_or_insert_with(|| tables::GPOS::new());
     let lookup: &mut Lookup<gpos::MarkToBase> = block.find_or_insert_lookup(gpos);
     let subtable = lookup.get_subtable(block.subtable_breaks);
 
-    for (anchor, mark_class_name) in &m2b.marks {
+    for (anchor, (_, mark_class_name)) in anchors.iter().zip(&m2b.marks) {
         let mark_class = ctx.mark_class_table.get(mark_class_name)
             .ok_or_else(|| CompileError::UnknownMarkClass(mark_class_name.into()))?;
 
         subtable.add_mark_class(&ctx.glyph_order, &ctx.glyph_class_table, &m2b.base,
-            &anchor.try_into()?, mark_class_name, mark_class)?;
+            anchor, mark_class_name, mark_class)?;
     }
 
-    Ok(())
+    ctx.infer_glyph_class(&m2b.base, GlyphType::Base)
+}
+
+fn handle_mark_to_ligature_position(ctx: &mut CompilerState, block: &Block, m2l: &pm::position::Ligature) -> CompileResult<()> {
+    ctx.mark_class_statements_allowed = false;
+
+    let mut components = Vec::with_capacity(m2l.components.len());
+
+    for component in &m2l.components {
+        let mut marks = Vec::new();
+
+        for (anchor, mark_class_name) in &component.anchors {
+            let mark_class_name = match mark_class_name {
+                Some(name) => name,
+                None => continue
+            };
+
+            let mark_class = ctx.mark_class_table.get(mark_class_name)
+                .ok_or_else(|| CompileError::UnknownMarkClass(mark_class_name.into()))?;
+
+            marks.push(gpos::LigatureComponentMark {
+                anchor: ctx.lookup_anchor(anchor)?,
+                class_name: mark_class_name,
+                mark_class
+            });
+        }
+
+        components.push(marks);
+    }
+
+    let gpos = ctx.gpos.get_or_insert_with(tables::GPOS::new);
+    let lookup: &mut Lookup<gpos::MarkToLigature> = block.find_or_insert_lookup(gpos);
+    let subtable = lookup.get_subtable(block.subtable_breaks);
+
+    subtable.add_ligature(&ctx.glyph_order, &ctx.glyph_class_table, &m2l.glyph_class,
+        &components)?;
+
+    ctx.infer_glyph_class(&m2l.glyph_class, GlyphType::Ligature)
 }
 
 fn handle_mark_to_mark_position(ctx: &mut CompilerState, block: &Block, m2m: &pm::position::MarkToMark) -> CompileResult<()> {
     ctx.mark_class_statements_allowed = false;
 
-    let pm::position::MarkToMark {
-        marks,
-        ..
-    } = m2m;
+    let anchors = m2m.marks.iter()
+        .map(|(anchor, _)| ctx.lookup_anchor(anchor))
+        .collect::<CompileResult<Vec<_>>>()?;
 
-    let gpos = ctx.gpos.get_or_insert_with(|| tables::GPOS::new());
+    let gpos = ctx.gpos.get_or_insert_with(tables::GPOS::new);
     let lookup: &mut Lookup<gpos::MarkToMark> = block.find_or_insert_lookup(gpos);
-    let _subtable = lookup.get_subtable(block.subtable_breaks);
+    let subtable = lookup.get_subtable(block.subtable_breaks);
 
-    for (anchor, mark_class_name) in marks {
+    for (anchor, (_, mark_class_name)) in anchors.iter().zip(&m2m.marks) {
         let mark_class = ctx.mark_class_table.get(mark_class_name)
             .ok_or_else(|| CompileError::UnknownMarkClass(mark_class_name.into()))?;
 
-        println!("{:?} {:#?}", anchor, mark_class);
+        subtable.add_mark_class(&ctx.glyph_order, &ctx.glyph_class_table, &m2m.base_mark,
+            anchor, mark_class_name, mark_class)?;
+    }
+
+    ctx.infer_glyph_class(&m2m.base_mark, GlyphType::Mark)
+}
+
+// inline value records in contextual rules ("pos [A V]' -40 T;") are gathered into anonymous
+// single adjustment lookups, re-using one already referenced by the same contextual lookup when
+// it doesn't cover any of the glyphs yet. anonymous lookups take on the flags of the rule.
+fn inline_single_position_lookup(gpos: &mut tables::GPOS, block: &Block,
+    referenced: &[u16], glyphs: &[u16], vr: &ValueRecord) -> u16 {
+
+    let reusable = referenced.iter()
+        .copied()
+        .filter(|idx| !gpos.is_named_lookup(*idx))
+        .find(|idx| {
+            gpos.lookup::<gpos::Single>(*idx)
+                .map(|lookup| lookup.subtables.iter()
+                    .all(|st| match st {
+                        gpos::Single::Array(array) =>
+                            glyphs.iter().all(|glyph| array.can_add(*glyph, vr)),
+                        _ => false
+                    }))
+                .unwrap_or(false)
+        });
+
+    let (idx, lookup) = match reusable {
+        Some(idx) => (idx, gpos.lookup_mut::<gpos::Single>(idx).unwrap()),
+        None => gpos.insert_anonymous_lookup(block.lookup_flags, block.mark_filtering_set)
+    };
+
+    let subtable: &mut gpos::SingleArray = lookup.get_subtable_variant(0);
+
+    for glyph in glyphs {
+        subtable.add_glyph(*glyph, vr.clone());
+    }
+
+    idx
+}
+
+fn handle_chain_context_position(ctx: &mut CompilerState, block: &Block, pos: &pm::position::ChainContext) -> CompileResult<()> {
+    let backtrack = class_defs_for(ctx, pos.prefix.iter())?;
+    let input = class_defs_for(ctx, pos.input.iter().map(|i| &i.glyph_class))?;
+    let lookahead = class_defs_for(ctx, pos.suffix.iter())?;
+
+    let mut inline_value_records = Vec::new();
+
+    for (sequence_index, item) in pos.input.iter().enumerate() {
+        if let Some(vr) = &item.value_record {
+            let glyphs: Vec<_> = item.glyph_class
+                .iter_glyphs(&ctx.glyph_order, &ctx.glyph_class_table)
+                .collect::<Result<_, _>>()?;
+
+            inline_value_records.push((sequence_index,
+                glyphs, ValueRecord::from_parsed(vr, block.is_vertical(), &ctx.value_record_table)?));
+        }
+    }
+
+    let gpos = ctx.gpos.get_or_insert_with(tables::GPOS::new);
+
+    let mut lookups = named_lookup_records(gpos,
+        pos.input.iter().map(|item| item.lookup.as_ref()))?;
+
+    let referenced = referenced_lookups(block.find_or_insert_lookup(gpos));
+
+    for (sequence_index, glyphs, vr) in inline_value_records {
+        lookups.push(SequenceLookup {
+            sequence_index: sequence_index as u16,
+            lookup_index: inline_single_position_lookup(gpos, block, &referenced, &glyphs, &vr)
+        });
+    }
+
+    lookups.sort_by_key(|l| l.sequence_index);
+
+    let lookup: &mut Lookup<ChainContext> = block.find_or_insert_lookup(gpos);
+
+    lookup.get_subtable(block.subtable_breaks)
+        .add_rule(ContextRule {
+            backtrack,
+            input,
+            lookahead,
+
+            lookups
+        });
+
+    Ok(())
+}
+
+fn handle_ignore_position(ctx: &mut CompilerState, block: &Block, ignore: &pm::position::Ignore) -> CompileResult<()> {
+    let mut rules = Vec::with_capacity(ignore.contexts.len());
+
+    for context in &ignore.contexts {
+        rules.push(ContextRule {
+            backtrack: class_defs_for(ctx, context.prefix.iter())?,
+            input: class_defs_for(ctx, context.input.iter().map(|i| &i.glyph_class))?,
+            lookahead: class_defs_for(ctx, context.suffix.iter())?,
+
+            // a rule which matches without applying anything stops later rules from matching
+            lookups: Vec::new()
+        });
+    }
+
+    let gpos = ctx.gpos.get_or_insert_with(tables::GPOS::new);
+    let lookup: &mut Lookup<ChainContext> = block.find_or_insert_lookup(gpos);
+    let subtable = lookup.get_subtable(block.subtable_breaks);
+
+    for rule in rules {
+        subtable.add_rule(rule);
     }
 
-    panic!("unimplemented");
-    // Ok(())
+    Ok(())
 }
 
 fn handle_position_statement(ctx: &mut CompilerState, block: &Block, p: &pm::Position) -> CompileResult<()> {
     use pm::Position::*;
 
-    let gpos = ctx.gpos.get_or_insert_with(|| tables::GPOS::new());
-    block.insert_into_script(gpos, &script_tag!(D,F,L,T));
-
     match p {
         SingleAdjustment(adj) => handle_single_adjustment_position(ctx, block, adj),
 
@@ -295,11 +501,57 @@ fn handle_position_statement(ctx: &mut CompilerState, block: &Block, p: &pm::Pos
         Cursive(cursive) => handle_cursive_position(ctx, block, cursive),
 
         MarkToBase(m2b) => handle_mark_to_base_position(ctx, block, m2b),
+        Ligature(m2l) => handle_mark_to_ligature_position(ctx, block, m2l),
         MarkToMark(m2m) => handle_mark_to_mark_position(ctx, block, m2m),
 
+        ChainContext(chain) => handle_chain_context_position(ctx, block, chain),
+        Ignore(ignore) => handle_ignore_position(ctx, block, ignore)
+    }
+}
+
+fn single_substitution_mapping(ctx: &CompilerState, glyph_class: &GlyphClass, replacement: &GlyphClass)
+        -> CompileResult<Vec<(u16, u16)>> {
+    let glyphs: Vec<_> =
+        glyph_class.iter_glyphs(&ctx.glyph_order, &ctx.glyph_class_table)
+        .collect::<Result<_, _>>()?;
+
+    let replacements: Vec<_> =
+        replacement.iter_glyphs(&ctx.glyph_order, &ctx.glyph_class_table)
+        .collect::<Result<_, _>>()?;
+
+    // either every glyph is replaced by the same single glyph ("sub [a b c] by d;"), or the
+    // glyph class maps one-to-one, in order, onto a replacement class of the same length
+    // ("sub [a b c] by [A B C];").
+    if replacements.len() == 1 {
+        return Ok(glyphs.into_iter()
+            .map(|glyph| (glyph, replacements[0]))
+            .collect());
+    }
 
-        p => panic!("unhandled position statement: {:#?}", p)
+    if replacements.len() != glyphs.len() {
+        return Err(CompileError::SubstitutionLengthMismatch(glyphs.len(), replacements.len()));
     }
+
+    Ok(glyphs.into_iter()
+        .zip(replacements)
+        .collect())
+}
+
+fn handle_single_substitution(ctx: &mut CompilerState, block: &Block, sub: &pm::substitute::Single) -> CompileResult<()> {
+    let mapping = single_substitution_mapping(ctx, &sub.glyph_class, &sub.replacement)?;
+
+    let gsub = ctx.gsub.get_or_insert_with(tables::GSUB::new);
+    let lookup: &mut Lookup<gsub::Single> = block.find_or_insert_lookup(gsub);
+
+    for (glyph, replacement) in mapping {
+        let subtable = lookup.get_subtable_filter(block.subtable_breaks,
+            |st| st.can_add(glyph, replacement),
+            gsub::Single::default);
+
+        subtable.insert(glyph, replacement);
+    }
+
+    Ok(())
 }
 
 fn handle_multiple_substitution(ctx: &mut CompilerState, block: &Block, sub: &pm::substitute::Multiple) -> CompileResult<()> {
@@ -340,215 +592,2419 @@ fn handle_alternate_substitution(ctx: &mut CompilerState, block: &Block, sub: &p
     Ok(())
 }
 
-fn handle_substitute_statement(ctx: &mut CompilerState, block: &Block, s: &pm::Substitute) -> CompileResult<()> {
-    use pm::Substitute::*;
+// glyph classes in a ligature's input sequence expand to every combination of their members, all of
+// which map onto the same ligature glyph.
+fn ligature_sequences<'a, I>(ctx: &CompilerState, glyphs: I) -> CompileResult<Vec<Vec<u16>>>
+    where I: Iterator<Item = &'a GlyphClass>
+{
+    let classes: Vec<Vec<_>> = glyphs
+        .map(|gc| gc.iter_glyphs(&ctx.glyph_order, &ctx.glyph_class_table)
+            .collect::<Result<_, _>>())
+        .collect::<Result<_, _>>()?;
 
-    let gsub = ctx.gsub.get_or_insert_with(|| tables::GSUB::new());
-    block.insert_into_script(gsub, &script_tag!(D,F,L,T));
+    Ok(classes.iter()
+        .fold(vec![Vec::new()], |sequences, class| {
+            sequences.iter()
+                .flat_map(|seq: &Vec<u16>| class.iter().map(move |glyph| {
+                    let mut seq = seq.clone();
+                    seq.push(*glyph);
+                    seq
+                }))
+                .collect()
+        }))
+}
 
-    match s {
-        Multiple(m) => handle_multiple_substitution(ctx, block, m),
-        Alternate(a) => handle_alternate_substitution(ctx, block, a),
+fn handle_ligature_substitution(ctx: &mut CompilerState, block: &Block, sub: &pm::substitute::Ligature) -> CompileResult<()> {
+    let ligature = ctx.glyph_order.id_for_glyph(&sub.replacement)?;
+    let sequences = ligature_sequences(ctx, sub.glyphs.iter())?;
+
+    let gsub = ctx.gsub.get_or_insert_with(tables::GSUB::new);
+    let lookup: &mut Lookup<gsub::Ligature> = block.find_or_insert_lookup(gsub);
+
+    for sequence in sequences {
+        let subtable = lookup.get_subtable_filter(block.subtable_breaks,
+            |st| st.can_add(&sequence, ligature),
+            gsub::Ligature::default);
 
-        Single(_) => Ok(())
+        subtable.add_ligature(&sequence, ligature);
     }
+
+    Ok(())
 }
 
-fn handle_lookup_reference(ctx: &mut CompilerState, block: &Block, name: &pm::LookupName) -> CompileResult<()> {
-    // FIXME: also needs to happen for gsub
+fn class_defs_for<'a, I>(ctx: &CompilerState, classes: I) -> CompileResult<Vec<ClassDef>>
+    where I: Iterator<Item = &'a GlyphClass>
+{
+    classes
+        .map(|gc| ClassDef::from_glyph_class(gc, &ctx.glyph_order, &ctx.glyph_class_table))
+        .collect()
+}
 
-    let gpos = match ctx.gpos.as_mut() {
-        None => return Ok(()),
-        Some(gpos) => gpos
-    };
+fn named_lookup_records<'a, L, I>(table: &LookupTable<L>, names: I) -> CompileResult<Vec<SequenceLookup>>
+    where I: Iterator<Item = Option<&'a pm::LookupName>>
+{
+    let mut lookups = Vec::new();
 
-    let feature_indices = match block.ident {
-        BlockIdent::Feature(tag) => gpos.feature_list.indices_for_tag_mut(tag),
-        BlockIdent::Lookup(_) =>
-            panic!("lookup references from inside a lookup block are unsupported")
-    };
+    for (sequence_index, name) in names.enumerate() {
+        let name = match name {
+            Some(name) => name,
+            None => continue
+        };
 
-    let lookup_indices = gpos.named_lookups.get(name)
-        .map(|indices| Either2::A(indices.iter()))
-        .unwrap_or_else(|| Either2::B(iter::empty::<&u16>()));
+        let indices = table.named_lookups.get(name)
+            .ok_or_else(|| CompileError::UndefinedReference("lookup", name.to_string()))?;
 
-    for idx in lookup_indices {
-        feature_indices.push(*idx);
+        for lookup_index in indices {
+            lookups.push(SequenceLookup {
+                sequence_index: sequence_index as u16,
+                lookup_index: *lookup_index
+            });
+        }
     }
 
-    block.insert_into_script(gpos, &script_tag!(D,F,L,T));
-    Ok(())
+    Ok(lookups)
 }
 
-fn handle_block_statements(ctx: &mut CompilerState, block: &mut Block, statements: &[pm::BlockStatement]) -> CompileResult<()> {
-    use pm::BlockStatement::*;
+fn referenced_lookups(lookup: &Lookup<ChainContext>) -> Vec<u16> {
+    lookup.subtables.iter()
+        .flat_map(|st| st.rules.iter())
+        .flat_map(ContextRule::referenced_lookups)
+        .collect()
+}
 
-    for s in statements {
-        match s {
-            Position(pos) => handle_position_statement(ctx, block, pos)?,
-            Substitute(sub) => handle_substitute_statement(ctx, block, sub)?,
+// inline substitutions in contextual rules ("sub a b' c by d;") are gathered into anonymous lookups
+// of the matching type. one which is already referenced by the same contextual lookup is re-used,
+// as long as none of its existing rules conflict. as with positioning, anonymous lookups take on
+// the flags of the rule.
+fn inline_substitution_lookup<T, C, A>(gsub: &mut tables::GSUB, block: &Block,
+    referenced: &[u16], can_add: C, add: A) -> u16
+    where T: LookupSubtable<gsub::GSUBLookup> + Default,
+          C: Fn(&T) -> bool,
+          A: FnOnce(&mut T)
+{
+    let reusable = referenced.iter()
+        .copied()
+        .filter(|idx| !gsub.is_named_lookup(*idx))
+        .find(|idx| {
+            gsub.lookup::<T>(*idx)
+                .map(|lookup| lookup.subtables.iter().all(&can_add))
+                .unwrap_or(false)
+        });
+
+    let (idx, lookup) = match reusable {
+        Some(idx) => (idx, gsub.lookup_mut::<T>(idx).unwrap()),
+        None => gsub.insert_anonymous_lookup(block.lookup_flags, block.mark_filtering_set)
+    };
 
-            Lookup(pm::Lookup(name)) => handle_lookup_reference(ctx, block, name)?,
+    add(lookup.get_subtable(0));
 
-            Subtable => block.add_subtable_break(),
+    idx
+}
 
-            LookupDefinition(ref ld) => handle_lookup_definition(ctx, ld)?,
-            NamedGlyphClass(ref gc) => handle_glyph_class_definition(ctx, gc)?,
-            MarkClass(ref mc) => handle_mark_class_statement(ctx, mc)?,
+// an inline replacement, resolved to glyph ids before the GSUB table is borrowed
+enum InlineSubstitution {
+    Single(Vec<(u16, u16)>),
+    Multiple(u16, Vec<u16>),
+    Ligature(Vec<Vec<u16>>, u16)
+}
 
-            Script(_) | Language(_) | FeatureNames(_) => {},
+impl InlineSubstitution {
+    fn resolve(ctx: &CompilerState, input: &[pm::substitute::ChainInput],
+        replacement: &pm::substitute::ChainReplacement) -> CompileResult<Self> {
+
+        use pm::substitute::ChainReplacement;
+
+        // the parser only allows single and multiple replacements for exactly one marked glyph,
+        // which has to be a single glyph for a multiple substitution.
+        Ok(match replacement {
+            ChainReplacement::Single(replacement) =>
+                InlineSubstitution::Single(
+                    single_substitution_mapping(ctx, &input[0].glyph_class, replacement)?),
+
+            ChainReplacement::Multiple(sequence) => {
+                let glyph = input[0].glyph_class.as_single().unwrap();
+
+                InlineSubstitution::Multiple(
+                    ctx.glyph_order.id_for_glyph(glyph)?,
+                    sequence.iter()
+                        .map(|gr| ctx.glyph_order.id_for_glyph(gr))
+                        .collect::<Result<_, _>>()?)
+            },
+
+            ChainReplacement::Ligature(ligature) =>
+                InlineSubstitution::Ligature(
+                    ligature_sequences(ctx, input.iter().map(|i| &i.glyph_class))?,
+                    ctx.glyph_order.id_for_glyph(ligature)?)
+        })
+    }
 
-            stmt => panic!("unimplemented block statement {:?}", stmt)
+    fn lookup_index(self, gsub: &mut tables::GSUB, block: &Block, referenced: &[u16]) -> u16 {
+        match self {
+            InlineSubstitution::Single(mapping) =>
+                inline_substitution_lookup(gsub, block, referenced,
+                    |st: &gsub::Single| mapping.iter()
+                        .all(|(glyph, replacement)| st.can_add(*glyph, *replacement)),
+                    |st| {
+                        for (glyph, replacement) in &mapping {
+                            st.insert(*glyph, *replacement);
+                        }
+                    }),
+
+            InlineSubstitution::Multiple(glyph, sequence) =>
+                inline_substitution_lookup(gsub, block, referenced,
+                    |st: &gsub::Multiple| st.can_add(glyph, &sequence),
+                    |st| {
+                        st.insert(glyph, sequence.clone());
+                    }),
+
+            InlineSubstitution::Ligature(sequences, ligature) =>
+                inline_substitution_lookup(gsub, block, referenced,
+                    |st: &gsub::Ligature| sequences.iter()
+                        .all(|sequence| st.can_add(sequence, ligature)),
+                    |st| {
+                        for sequence in &sequences {
+                            st.add_ligature(sequence, ligature);
+                        }
+                    })
         }
     }
-
-    Ok(())
 }
 
-fn handle_feature_definition(ctx: &mut CompilerState, def: &pm::FeatureDefinition) -> CompileResult<()> {
-    let tag = &def.tag;
-    let mut block = Block::new_feature(tag);
+fn handle_chain_context_substitution(ctx: &mut CompilerState, block: &Block, sub: &pm::substitute::ChainContext) -> CompileResult<()> {
+    let backtrack = class_defs_for(ctx, sub.prefix.iter())?;
+    let input = class_defs_for(ctx, sub.input.iter().map(|i| &i.glyph_class))?;
+    let lookahead = class_defs_for(ctx, sub.suffix.iter())?;
 
-    println!("feature {}:", tag);
+    let inline = sub.replacement.as_ref()
+        .map(|replacement| InlineSubstitution::resolve(ctx, &sub.input, replacement))
+        .transpose()?;
 
-    handle_block_statements(ctx, &mut block, &def.statements)
-}
+    let gsub = ctx.gsub.get_or_insert_with(tables::GSUB::new);
 
-fn handle_lookup_definition(ctx: &mut CompilerState, def: &pm::LookupDefinition) -> CompileResult<()> {
-    let name = &def.label;
-    let mut block = Block::new_lookup(name);
+    let mut lookups = named_lookup_records(gsub,
+        sub.input.iter().map(|item| item.lookup.as_ref()))?;
 
-    println!("lookup {}:", name);
+    // looking up the contextual lookup first also ensures that it precedes any anonymous lookups
+    // it references in the lookup list.
+    let referenced = referenced_lookups(block.find_or_insert_lookup(gsub));
 
-    handle_block_statements(ctx, &mut block, &def.statements)
-}
+    // a ligature replaces every marked glyph, so all inline lookups apply from the first one
+    if let Some(inline) = inline {
+        lookups.push(SequenceLookup {
+            sequence_index: 0,
+            lookup_index: inline.lookup_index(gsub, block, &referenced)
+        });
+    }
 
-/**
- * simple top level
- */
+    let lookup: &mut Lookup<ChainContext> = block.find_or_insert_lookup(gsub);
 
-fn handle_table(ctx: &mut CompilerState, table: &pm::Table) {
-    let pm::Table { tag, statements } = table;
+    lookup.get_subtable(block.subtable_breaks)
+        .add_rule(ContextRule {
+            backtrack,
+            input,
+            lookahead,
 
-    match tag {
-        pm::TableTag::head =>
-            ctx.head = Some(tables::Head::from_parsed_table(statements)),
-        pm::TableTag::name => {
-            // let table = tables::Name::from_parsed_table(statements);
-            // ctx.tables_encoded.push((tag!(n,a,m,e), table.to_be()));
-        }
+            lookups
+        });
 
-        _ => panic!()
-    }
+    Ok(())
 }
 
-fn handle_mark_class_statement(ctx: &mut CompilerState, mark_class: &pm::MarkClass) -> CompileResult<()> {
-    if !ctx.mark_class_statements_allowed {
-        return Err(CompileError::MarkClassNotAllowed);
+fn handle_ignore_substitution(ctx: &mut CompilerState, block: &Block, ignore: &pm::substitute::Ignore) -> CompileResult<()> {
+    let mut rules = Vec::with_capacity(ignore.contexts.len());
+
+    for context in &ignore.contexts {
+        rules.push(ContextRule {
+            backtrack: class_defs_for(ctx, context.prefix.iter())?,
+            input: class_defs_for(ctx, context.input.iter().map(|i| &i.glyph_class))?,
+            lookahead: class_defs_for(ctx, context.suffix.iter())?,
+
+            // as with "ignore pos", a rule which matches without applying anything stops later
+            // rules from matching
+            lookups: Vec::new()
+        });
     }
 
-    let pm::MarkClass {
-        glyph_class,
-        anchor,
-        class_name
-    } = mark_class;
+    let gsub = ctx.gsub.get_or_insert_with(tables::GSUB::new);
+    let lookup: &mut Lookup<ChainContext> = block.find_or_insert_lookup(gsub);
+    let subtable = lookup.get_subtable(block.subtable_breaks);
 
-    ctx.mark_class_table.entry(class_name.clone())
-        .or_default()
-        .push((glyph_class.clone(), anchor.try_into()?));
+    for rule in rules {
+        subtable.add_rule(rule);
+    }
 
     Ok(())
 }
 
-fn handle_anchor_definition(ctx: &mut CompilerState, anchor_def: &pm::AnchorDefinition) -> CompileResult<()> {
-    let pm::AnchorDefinition {
-        name,
-        anchor
-    } = anchor_def;
+fn handle_reverse_chain_substitution(ctx: &mut CompilerState, block: &Block, sub: &pm::substitute::ReverseChain) -> CompileResult<()> {
+    let backtrack = class_defs_for(ctx, sub.prefix.iter())?;
+    let lookahead = class_defs_for(ctx, sub.suffix.iter())?;
 
-    ctx.anchor_table.entry(name.clone())
-        .or_insert(anchor.try_into()?);
+    let mapping = single_substitution_mapping(ctx, &sub.glyph_class, &sub.replacement)?;
 
-    Ok(())
-}
+    let gsub = ctx.gsub.get_or_insert_with(tables::GSUB::new);
+    let lookup: &mut Lookup<gsub::ReverseChainSingle> = block.find_or_insert_lookup(gsub);
 
-fn handle_glyph_class_definition(ctx: &mut CompilerState, cls: &NamedGlyphClass) -> CompileResult<()> {
-    ctx.glyph_class_table.insert(cls.name.clone(), cls.glyph_class.clone());
+    // a subtable can only have a single context, so consecutive rules sharing the same context
+    // are merged. we only ever merge into the last subtable, since merging into an earlier one
+    // would change which rule matches first, and as with other lookup types a `subtable`
+    // statement skips past the subtables before it.
+    let can_merge = lookup.subtables.len() > block.subtable_breaks
+        && lookup.subtables.last()
+            .map(|st| st.has_context(&backtrack, &lookahead)
+                && mapping.iter().all(|(glyph, replacement)| st.can_add(*glyph, *replacement)))
+            .unwrap_or(false);
+
+    if !can_merge {
+        lookup.subtables.push(gsub::ReverseChainSingle::new(backtrack, lookahead));
+    }
+
+    let subtable = lookup.subtables.last_mut().unwrap();
+
+    for (glyph, replacement) in mapping {
+        subtable.substitutes.insert(glyph, replacement);
+    }
 
     Ok(())
 }
 
-fn handle_top_level(ctx: &mut CompilerState, statement: &pm::TopLevelStatement) -> CompileResult<()> {
-    use pm::TopLevelStatement::*;
+fn handle_substitute_statement(ctx: &mut CompilerState, block: &Block, s: &pm::Substitute) -> CompileResult<()> {
+    use pm::Substitute::*;
 
-    match statement {
-        LanguageSystem(ref _ls) => (),
+    // single and alternate substitutions in `aalt` don't get lookups of their own. they're merged
+    // into the lookups generated for it at the end.
+    if block.is_aalt() {
+        match s {
+            Single(s) => return handle_aalt_single_substitution(ctx, s),
+            Alternate(a) => return handle_aalt_alternate_substitution(ctx, a),
+            _ => {}
+        }
+    }
 
-        Table(ref t) => handle_table(ctx, t),
+    match s {
+        Multiple(m) => handle_multiple_substitution(ctx, block, m),
+        Alternate(a) => handle_alternate_substitution(ctx, block, a),
+        Ligature(l) => handle_ligature_substitution(ctx, block, l),
+        ChainContext(c) => handle_chain_context_substitution(ctx, block, c),
+        ReverseChain(r) => handle_reverse_chain_substitution(ctx, block, r),
+        Ignore(i) => handle_ignore_substitution(ctx, block, i),
 
-        FeatureDefinition(ref fd) => handle_feature_definition(ctx, fd)?,
-        LookupDefinition(ref ld) => handle_lookup_definition(ctx, ld)?,
-        AnchorDefinition(ref ad) => handle_anchor_definition(ctx, ad)?,
-        NamedGlyphClass(ref gc) => handle_glyph_class_definition(ctx, gc)?,
+        Single(s) => handle_single_substitution(ctx, block, s)
+    }
+}
 
-        MarkClass(ref mc) => handle_mark_class_statement(ctx, mc)?,
+fn handle_lookup_reference(ctx: &mut CompilerState, block: &Block, name: &pm::LookupName) -> CompileResult<()> {
+    let scope = match block.feature_scope() {
+        Some(scope) => scope,
+        None => panic!("lookup references from inside a lookup block are unsupported")
+    };
+
+    let mut found = false;
+
+    if let Some(gsub) = ctx.gsub.as_mut() {
+        for idx in gsub.named_lookups.get(name).cloned().unwrap_or_default() {
+            gsub.add_feature_lookup(&scope, idx);
+            found = true;
+        }
+    }
 
-        s => {
-            println!("unhandled {:#?}\n", s);
-            panic!();
+    if let Some(gpos) = ctx.gpos.as_mut() {
+        for idx in gpos.named_lookups.get(name).cloned().unwrap_or_default() {
+            gpos.add_feature_lookup(&scope, idx);
+            found = true;
         }
     }
 
+    if !found {
+        return Err(CompileError::UndefinedReference("lookup", name.to_string()));
+    }
+
     Ok(())
 }
 
-/**
- * todo: move this out into a separate file
- */
-
-impl CompilerOutput {
-    pub fn merge_encoded_tables(&self, tables: &mut EncodedTables) -> EncodeResult<()> {
-        macro_rules! encode_table {
-            ($table:ident, $tag:expr) => {
-                if let Some(table) = self.$table.as_ref() {
-                    let mut buf = EncodeBuf::new_with_glyph_order(&self.glyph_order);
-                    table.ttf_encode(&mut buf)?;
+fn start_language_section<L>(table: &mut LookupTable<L>, feature: &FeatureTag,
+    language_system: &LanguageSystem, include_default: bool) {
 
-                    tables.add_table($tag, buf.bytes, buf.source_map);
-                }
-            }
-        }
+    let default_lookups =
+        if include_default || language_system.is_default_language() {
+            table.script_list
+                .lang_sys(&LanguageSystem::default_for_script(language_system.script))
+                .and_then(|lang_sys| lang_sys.features.get(feature))
+                .cloned()
+        } else {
+            None
+        };
 
-        encode_table!(gpos, tag!(G,P,O,S));
-        encode_table!(gsub, tag!(G,S,U,B));
+    match default_lookups {
+        Some(lookups) =>
+            drop(table.script_list.lang_sys_mut(language_system)
+                .features.insert(*feature, lookups)),
 
-        Ok(())
-    }
+        // lookups registered through `languagesystem` are excluded as well.
+        None if table.script_list.lang_sys(language_system).is_some() =>
+            drop(table.script_list.lang_sys_mut(language_system)
+                .features.remove(feature)),
 
-    pub fn encode_tables(&self) -> EncodeResult<EncodedTables> {
-        let mut encoded = EncodedTables::new(self.head.clone());
-        self.merge_encoded_tables(&mut encoded)?;
-        Ok(encoded)
+        None => ()
     }
 }
 
-pub fn compile_iter<'a, I>(glyph_order: GlyphOrder, statements: I)
-    -> CompileResult<CompilerOutput>
-    where I: Iterator<Item = &'a pm::TopLevelStatement>
-{
-    let mut ctx = CompilerState::new();
+fn handle_language(ctx: &mut CompilerState, block: &mut Block, language: &LanguageTag,
+    include_default: bool, required: bool) -> CompileResult<()> {
 
-    ctx.glyph_order = glyph_order;
+    let feature = match block.ident {
+        BlockIdent::Feature(tag) => tag,
+        BlockIdent::Lookup(_) =>
+            return Err(CompileError::NotAllowedInLookupBlock("language"))
+    };
 
-    for s in statements {
-        handle_top_level(&mut ctx, &s)?;
+    let language_system = LanguageSystem::new(block.script, *language);
+
+    if let Some(gsub) = ctx.gsub.as_mut() {
+        start_language_section(gsub, feature, &language_system, include_default);
     }
 
-    Ok(ctx.into())
-}
+    if let Some(gpos) = ctx.gpos.as_mut() {
+        start_language_section(gpos, feature, &language_system, include_default);
+    }
+
+    if required {
+        block.required_in.push(language_system);
+    }
+
+    block.language_systems = vec![language_system];
+    block.subtable_breaks = 0;
+
+    Ok(())
+}
+
+fn handle_script(ctx: &mut CompilerState, block: &mut Block, script: &ScriptTag) -> CompileResult<()> {
+    if let BlockIdent::Lookup(_) = block.ident {
+        return Err(CompileError::NotAllowedInLookupBlock("script"));
+    }
+
+    block.script = *script;
+    block.set_lookup_flags(LookupFlags::empty(), None);
+
+    handle_language(ctx, block, &language_tag!(d,f,l,t), true, false)
+}
+
+fn set_required_feature<L>(table: &mut LookupTable<L>, feature: &FeatureTag,
+    language_system: &LanguageSystem) {
+
+    let has_feature = table.script_list.lang_sys(language_system)
+        .map(|lang_sys| lang_sys.features.contains_key(feature))
+        .unwrap_or(false);
+
+    if has_feature {
+        table.script_list.lang_sys_mut(language_system).required_feature = Some(*feature);
+    }
+}
+
+fn class_def_for_class_name(ctx: &mut CompilerState, name: &pm::ClassName) -> CompileResult<ClassDef> {
+    let glyph_class_name = GlyphClassName(name.0.clone());
+
+    if let Some(glyph_class) = ctx.glyph_class_table.get(&glyph_class_name) {
+        return ClassDef::from_glyph_class(glyph_class, &ctx.glyph_order, &ctx.glyph_class_table);
+    }
+
+    let mark_class_name = pm::MarkClassName(name.0.clone());
+
+    let mark_class = ctx.mark_class_table.get(&mark_class_name)
+        .ok_or_else(|| CompileError::UnknownGlyphClass((&glyph_class_name).into()))?;
+
+    let mut class_def = ClassDef::default();
+
+    for (glyph_class, _) in mark_class {
+        class_def.extend(ClassDef::from_glyph_class(glyph_class,
+            &ctx.glyph_order, &ctx.glyph_class_table)?.iter());
+    }
+
+    // the class we've just resolved has to stay the same as the one that ends up in GDEF.
+    ctx.mark_class_statements_allowed = false;
+
+    Ok(class_def)
+}
+
+fn handle_lookup_flag(ctx: &mut CompilerState, block: &mut Block, flag: &pm::LookupFlag) -> CompileResult<()> {
+    let mut flags = LookupFlags::empty();
+
+    flags.set(LookupFlags::RIGHT_TO_LEFT, flag.right_to_left);
+    flags.set(LookupFlags::IGNORE_BASE_GLYPHS, flag.ignore_base_glyphs);
+    flags.set(LookupFlags::IGNORE_LIGATURES, flag.ignore_ligatures);
+    flags.set(LookupFlags::IGNORE_MARKS, flag.ignore_marks);
+
+    if let Some(name) = &flag.mark_attachment_type {
+        let class_def = class_def_for_class_name(ctx, name)?;
+        let class_id = ctx.mark_attach_class_id(class_def)?;
+
+        flags |= LookupFlags::from_bits_truncate(class_id << 8);
+    }
+
+    let mark_filtering_set = flag.use_mark_filtering_set.as_ref()
+        .map(|name| {
+            let class_def = class_def_for_class_name(ctx, name)?;
+            ctx.mark_glyph_set_index(class_def)
+        })
+        .transpose()?;
+
+    block.set_lookup_flags(flags, mark_filtering_set);
+    Ok(())
+}
+
+/**
+ * feature params
+ */
+
+fn user_name_id(name_table: &Option<tables::Name>) -> CompileResult<u16> {
+    let name_id = name_table.as_ref()
+        .map(tables::Name::next_user_name_id)
+        .unwrap_or(tables::Name::FIRST_USER_NAME_ID);
+
+    CheckedInto::<u16, CompileError>::checked_into(name_id, "name", "name ID")
+}
+
+// the size feature doesn't have any lookups, it's only there for its params. it's registered under
+// the language systems in scope like any other feature though.
+fn size_params<'a>(gpos: &'a mut Option<tables::GPOS>, block: &Block, statement: &'static str)
+        -> CompileResult<&'a mut SizeParams> {
+    let scope = match block.feature_scope() {
+        Some(scope) if *scope.tag == feature_tag!(s,i,z,e) => scope,
+        _ => return Err(CompileError::OnlyAllowedIn(statement, "the size feature"))
+    };
+
+    let gpos = gpos.get_or_insert_with(tables::GPOS::new);
+    gpos.add_feature(&scope);
+
+    let params = gpos.feature_params.entry(*scope.tag)
+        .or_insert_with(|| FeatureParams::Size(SizeParams::default()));
+
+    match params {
+        FeatureParams::Size(size) => Ok(size),
+        _ => unreachable!("params for the size feature are always size params")
+    }
+}
+
+fn handle_parameters(ctx: &mut CompilerState, block: &Block, parameters: &pm::Parameters) -> CompileResult<()> {
+    let decipoints = |size: &pm::PointSize, item| -> CompileResult<u16> {
+        size.decipoints().checked_into("size", item)
+    };
+
+    let range = |size: &Option<pm::PointSize>, item| -> CompileResult<u16> {
+        size.as_ref()
+            .map(|size| decipoints(size, item))
+            .unwrap_or(Ok(0))
+    };
+
+    let size = size_params(&mut ctx.gpos, block, "parameters")?;
+
+    size.design_size = decipoints(&parameters.design_size, "design size")?;
+    size.subfamily_id = parameters.subfamily_id.checked_into("size", "subfamily ID")?;
+    size.range_start = range(&parameters.range_start, "range start")?;
+    size.range_end = range(&parameters.range_end, "range end")?;
+
+    Ok(())
+}
+
+// every `sizemenuname` in the feature is a different language or platform for the same name, so
+// they all share a single name ID.
+fn handle_size_menu_name(ctx: &mut CompilerState, block: &Block, name: &pm::Name) -> CompileResult<()> {
+    let size = size_params(&mut ctx.gpos, block, "sizemenuname")?;
+
+    if size.subfamily_name_id == 0 {
+        size.subfamily_name_id = user_name_id(&ctx.name)?;
+    }
+
+    ctx.name.get_or_insert_with(tables::Name::new)
+        .0.push(NameRecord::from_parsed_name(size.subfamily_name_id, name));
+
+    Ok(())
+}
+
+// all of the names in a block are the same name in different languages or platforms, so they share
+// a single name ID. an empty block doesn't get one, and doesn't create the name table either.
+fn add_names(name_table: &mut Option<tables::Name>, names: &[pm::Name]) -> CompileResult<u16> {
+    if names.is_empty() {
+        return Ok(0);
+    }
+
+    let name_id = user_name_id(name_table)?;
+
+    name_table.get_or_insert_with(tables::Name::new)
+        .0.extend(names.iter().map(|n| NameRecord::from_parsed_name(name_id, n)));
+
+    Ok(name_id)
+}
+
+fn handle_feature_names(ctx: &mut CompilerState, block: &Block, names: &[pm::Name]) -> CompileResult<()> {
+    let scope = match block.feature_scope() {
+        Some(scope) if is_stylistic_set(scope.tag) => scope,
+        _ => return Err(CompileError::OnlyAllowedIn("featureNames", "stylistic set features"))
+    };
+
+    let params = StylisticSetParams {
+        version: 0,
+        ui_name_id: add_names(&mut ctx.name, names)?
+    };
+
+    let gsub = ctx.gsub.get_or_insert_with(tables::GSUB::new);
+    gsub.add_feature(&scope);
+    gsub.feature_params.insert(*scope.tag, FeatureParams::StylisticSet(params));
+
+    Ok(())
+}
+
+// the named parameters need consecutive name IDs, so they're allocated after the names for the
+// feature itself regardless of where they appear in the block.
+fn handle_cv_parameters(ctx: &mut CompilerState, block: &Block, cv: &pm::CvParameters) -> CompileResult<()> {
+    use pm::CvParameter::*;
+
+    let scope = match block.feature_scope() {
+        Some(scope) if is_character_variant(scope.tag) => scope,
+        _ => return Err(CompileError::OnlyAllowedIn("cvParameters", "character variant features"))
+    };
+
+    let name_table = &mut ctx.name;
+    let mut params = CharacterVariantParams::default();
+    let mut param_labels = Vec::new();
+
+    for p in &cv.parameters {
+        match p {
+            FeatUILabelNameID(names) => params.ui_label_name_id = add_names(name_table, names)?,
+            FeatUITooltipTextNameID(names) => params.tooltip_text_name_id = add_names(name_table, names)?,
+            SampleTextNameID(names) => params.sample_text_name_id = add_names(name_table, names)?,
+            ParamUILabelNameID(names) => param_labels.push(names),
+
+            Character(c) => match (*c).try_into() {
+                Ok(c) if c <= MAX_CHARACTER => params.characters.push(c),
+                _ => return Err(CompileError::InvalidValue("Unicode character", *c))
+            }
+        }
+    }
+
+    if !param_labels.is_empty() {
+        let first = user_name_id(name_table)?;
+
+        for (i, names) in param_labels.iter().enumerate() {
+            let name_id = CheckedInto::<u16, CompileError>::checked_into(first as usize + i,
+                "name", "name ID")?;
+
+            if !names.is_empty() {
+                name_table.get_or_insert_with(tables::Name::new)
+                    .0.extend(names.iter().map(|n| NameRecord::from_parsed_name(name_id, n)));
+            }
+        }
+
+        params.first_param_ui_label_name_id = first;
+        params.num_named_parameters = CheckedInto::<u16, CompileError>::checked_into(param_labels.len(),
+            "cvParameters", "named parameter count")?;
+    }
+
+    let gsub = ctx.gsub.get_or_insert_with(tables::GSUB::new);
+    gsub.add_feature(&scope);
+    gsub.feature_params.insert(*scope.tag, FeatureParams::CharacterVariant(params));
+
+    Ok(())
+}
+
+/**
+ * aalt
+ */
+
+fn add_alternates<I>(alternates: &mut BTreeMap<u16, Vec<u16>>, glyph: u16, alts: I)
+    where I: IntoIterator<Item = u16>
+{
+    let existing = alternates.entry(glyph).or_default();
+
+    for alt in alts {
+        if !existing.contains(&alt) {
+            existing.push(alt);
+        }
+    }
+}
+
+fn handle_aalt_single_substitution(ctx: &mut CompilerState, sub: &pm::substitute::Single) -> CompileResult<()> {
+    let mapping = single_substitution_mapping(ctx, &sub.glyph_class, &sub.replacement)?;
+    let aalt = ctx.aalt.get_or_insert_with(AaltFeature::default);
+
+    for (glyph, replacement) in mapping {
+        add_alternates(&mut aalt.alternates, glyph, Some(replacement));
+    }
+
+    Ok(())
+}
+
+fn handle_aalt_alternate_substitution(ctx: &mut CompilerState, sub: &pm::substitute::Alternate) -> CompileResult<()> {
+    let glyph = ctx.glyph_order.id_for_glyph(&sub.glyph)?;
+
+    let replacement: Vec<_> =
+        sub.replacement.iter_glyphs(&ctx.glyph_order, &ctx.glyph_class_table)
+        .collect::<Result<_, _>>()?;
+
+    let aalt = ctx.aalt.get_or_insert_with(AaltFeature::default);
+    add_alternates(&mut aalt.alternates, glyph, replacement);
+
+    Ok(())
+}
+
+fn handle_feature_reference(ctx: &mut CompilerState, block: &Block, tag: &FeatureTag) -> CompileResult<()> {
+    if !block.is_aalt() {
+        return Err(CompileError::OnlyAllowedIn("feature", "the aalt feature"));
+    }
+
+    let aalt = ctx.aalt.get_or_insert_with(AaltFeature::default);
+
+    if !aalt.features.contains(tag) {
+        aalt.features.push(*tag);
+    }
+
+    Ok(())
+}
+
+fn has_feature<L>(table: &Option<LookupTable<L>>, tag: &FeatureTag) -> bool {
+    table.as_ref()
+        .map(|table| table.script_list.iter_lang_sys()
+            .any(|(_, lang_sys)| lang_sys.features.contains_key(tag)))
+        .unwrap_or(false)
+}
+
+// every single and alternate substitution used by the feature in any language system.
+fn feature_alternates(gsub: &tables::GSUB, tag: &FeatureTag, alternates: &mut BTreeMap<u16, Vec<u16>>) {
+    let mut lookup_indices: Vec<u16> = Vec::new();
+
+    for (_, lang_sys) in gsub.script_list.iter_lang_sys() {
+        for idx in lang_sys.features.get(tag).into_iter().flatten() {
+            if !lookup_indices.contains(idx) {
+                lookup_indices.push(*idx);
+            }
+        }
+    }
+
+    for idx in lookup_indices {
+        if let Some(lookup) = gsub.lookup::<gsub::Single>(idx) {
+            for (glyph, replacement) in lookup.subtables.iter().flat_map(|st| st.iter()) {
+                add_alternates(alternates, *glyph, Some(*replacement));
+            }
+        } else if let Some(lookup) = gsub.lookup::<gsub::Alternate>(idx) {
+            for (glyph, alts) in lookup.subtables.iter().flat_map(|st| st.iter()) {
+                add_alternates(alternates, *glyph, alts.iter().copied());
+            }
+        }
+    }
+}
+
+// glyphs with a single alternate go into a single substitution lookup, and the rest into an
+// alternate substitution lookup. both go ahead of every other lookup in GSUB, and ahead of any
+// lookups `aalt` already has.
+fn build_aalt(ctx: &mut CompilerState) -> CompileResult<()> {
+    let AaltFeature { language_systems, features, mut alternates } = match ctx.aalt.take() {
+        Some(aalt) => aalt,
+        None => return Ok(())
+    };
+
+    for tag in &features {
+        if !has_feature(&ctx.gsub, tag) && !has_feature(&ctx.gpos, tag) {
+            return Err(CompileError::UndefinedReference("feature", tag.to_string()));
+        }
+
+        if let Some(gsub) = ctx.gsub.as_ref() {
+            feature_alternates(gsub, tag, &mut alternates);
+        }
+    }
+
+    let mut single = gsub::Single::default();
+    let mut alternate = gsub::Alternate::default();
+
+    for (glyph, alts) in alternates {
+        match *alts {
+            [] => {},
+            [replacement] => { single.insert(glyph, replacement); },
+            _ => { alternate.insert(glyph, alts); }
+        }
+    }
+
+    let mut lookups = Vec::new();
+
+    if !single.is_empty() {
+        let mut lookup = Lookup::new();
+        lookup.subtables.push(single);
+        lookups.push(gsub::GSUBLookup::Single(lookup));
+    }
+
+    if !alternate.is_empty() {
+        let mut lookup = Lookup::new();
+        lookup.subtables.push(alternate);
+        lookups.push(gsub::GSUBLookup::Alternate(lookup));
+    }
+
+    if lookups.is_empty() {
+        return Ok(());
+    }
+
+    let count = lookups.len() as u16;
+    let gsub = ctx.gsub.get_or_insert_with(tables::GSUB::new);
+
+    gsub.prepend_lookups(lookups);
+
+    for language_system in &language_systems {
+        gsub.script_list.lang_sys_mut(language_system)
+            .features
+            .entry(feature_tag!(a,a,l,t))
+            .or_default()
+            .splice(0..0, 0..count);
+    }
+
+    Ok(())
+}
+
+fn handle_block_statements(ctx: &mut CompilerState, block: &mut Block, statements: &[pm::BlockStatement]) -> CompileResult<()> {
+    use pm::BlockStatement::*;
+
+    for s in statements {
+        match s {
+            Position(pos) => handle_position_statement(ctx, block, pos)?,
+            Substitute(sub) => handle_substitute_statement(ctx, block, sub)?,
+
+            Lookup(pm::Lookup(name)) => handle_lookup_reference(ctx, block, name)?,
+            FeatureReference(pm::FeatureReference(tag)) => handle_feature_reference(ctx, block, tag)?,
+
+            Subtable => block.add_subtable_break(),
+            LookupFlag(ref flag) => handle_lookup_flag(ctx, block, flag)?,
+
+            LookupDefinition(ref ld) => handle_lookup_definition(ctx, ld)?,
+            NamedGlyphClass(ref gc) => handle_glyph_class_definition(ctx, gc)?,
+            ValueRecordDefinition(ref vd) => handle_value_record_definition(ctx, vd)?,
+            MarkClass(ref mc) => handle_mark_class_statement(ctx, mc)?,
+
+            Script(pm::Script(script)) => handle_script(ctx, block, script)?,
+            Language(pm::Language { tag, include_default, required }) =>
+                handle_language(ctx, block, tag, *include_default, *required)?,
+
+            FeatureNames(pm::FeatureNames { ref names }) => handle_feature_names(ctx, block, names)?,
+            CvParameters(ref cv) => handle_cv_parameters(ctx, block, cv)?,
+
+            Parameters(ref p) => handle_parameters(ctx, block, p)?,
+            SizeMenuName(pm::SizeMenuName(ref n)) => handle_size_menu_name(ctx, block, n)?,
+
+            stmt => panic!("unimplemented block statement {:?}", stmt)
+        }
+    }
+
+    Ok(())
+}
+
+fn handle_feature_definition(ctx: &mut CompilerState, def: &pm::FeatureDefinition) -> CompileResult<()> {
+    let tag = &def.tag;
+    let mut block = Block::new_feature(tag, &ctx.language_systems);
+
+    if block.is_aalt() {
+        let aalt = ctx.aalt.get_or_insert_with(AaltFeature::default);
+
+        for language_system in &block.language_systems {
+            if !aalt.language_systems.contains(language_system) {
+                aalt.language_systems.push(*language_system);
+            }
+        }
+    }
+
+    handle_block_statements(ctx, &mut block, &def.statements)?;
+
+    // a feature is only required in a language system once all of its lookups are known, since
+    // it's only required in the table(s) which actually have lookups for it.
+    for language_system in &block.required_in {
+        if let Some(gsub) = ctx.gsub.as_mut() {
+            set_required_feature(gsub, tag, language_system);
+        }
+
+        if let Some(gpos) = ctx.gpos.as_mut() {
+            set_required_feature(gpos, tag, language_system);
+        }
+    }
+
+    Ok(())
+}
+
+fn handle_lookup_definition(ctx: &mut CompilerState, def: &pm::LookupDefinition) -> CompileResult<()> {
+    let name = &def.label;
+    let mut block = Block::new_lookup(name);
+    block.use_extension = def.use_extension;
+
+    handle_block_statements(ctx, &mut block, &def.statements)
+}
+
+/**
+ * simple top level
+ */
+
+fn handle_glyph_class_def(ctx: &mut CompilerState, gcd: &pm::gdef::GlyphClassDef) -> CompileResult<()> {
+    let classes = [
+        (&gcd.base, GlyphType::Base),
+        (&gcd.ligature, GlyphType::Ligature),
+        (&gcd.mark, GlyphType::Mark),
+        (&gcd.component, GlyphType::Component)
+    ];
+
+    let gdef = ctx.gdef.get_or_insert_with(tables::GDEF::new);
+
+    for (glyph_classes, glyph_type) in classes.iter() {
+        let glyph_order = &ctx.glyph_order;
+        let gc_table = &ctx.glyph_class_table;
+
+        for glyph in glyph_classes.iter()
+                .flat_map(|gc| gc.iter_glyphs(glyph_order, gc_table)) {
+            let glyph = glyph?;
+
+            match gdef.glyph_classes.insert(glyph, *glyph_type) {
+                Some(previous) if previous != *glyph_type =>
+                    return Err(CompileError::GlyphClassDefConflict(glyph)),
+
+                _ => ()
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn handle_attach(ctx: &mut CompilerState, attach: &pm::gdef::Attach) -> CompileResult<()> {
+    let gdef = ctx.gdef.get_or_insert_with(tables::GDEF::new);
+
+    for glyph in attach.glyphs.iter_glyphs(&ctx.glyph_order, &ctx.glyph_class_table) {
+        let points = gdef.attach_points.entry(glyph?)
+            .or_default();
+
+        for point in &attach.contour_points {
+            points.insert(CheckedInto::<u16, CompileError>::checked_into(*point,
+                "Attach", "contour point")?);
+        }
+    }
+
+    Ok(())
+}
+
+fn handle_ligature_carets(ctx: &mut CompilerState, glyphs: &GlyphClass, carets: Vec<gdef::CaretValue>)
+    -> CompileResult<()>
+{
+    let gdef = ctx.gdef.get_or_insert_with(tables::GDEF::new);
+
+    // as with feaLib, only the first caret definition for a ligature is used.
+    for glyph in glyphs.iter_glyphs(&ctx.glyph_order, &ctx.glyph_class_table) {
+        gdef.ligature_carets.entry(glyph?)
+            .or_insert_with(|| carets.clone());
+    }
+
+    Ok(())
+}
+
+fn handle_gdef_table(ctx: &mut CompilerState, statements: &[pm::TableStatement]) -> CompileResult<()> {
+    use pm::TableStatement::*;
+
+    ctx.gdef.get_or_insert_with(tables::GDEF::new);
+
+    for statement in statements {
+        match statement {
+            GlyphClassDef(ref gcd) => handle_glyph_class_def(ctx, gcd)?,
+            Attach(ref attach) => handle_attach(ctx, attach)?,
+
+            LigatureCaretByPos(ref carets) => {
+                let positions = carets.carets.iter()
+                    .map(|c| (*c).checked_into("LigatureCaretByPos", "caret")
+                        .map(gdef::CaretValue::Coordinate))
+                    .collect::<CompileResult<_>>()?;
+
+                handle_ligature_carets(ctx, &carets.glyphs, positions)?
+            },
+
+            LigatureCaretByIndex(ref carets) => {
+                let points = carets.carets.iter()
+                    .map(|c| (*c).checked_into("LigatureCaretByIndex", "caret")
+                        .map(gdef::CaretValue::ContourPoint))
+                    .collect::<CompileResult<_>>()?;
+
+                handle_ligature_carets(ctx, &carets.glyphs, points)?
+            },
+
+            _ => ()
+        }
+    }
+
+    Ok(())
+}
+
+fn handle_table(ctx: &mut CompilerState, table: &pm::Table) -> CompileResult<()> {
+    let pm::Table { tag, statements } = table;
+
+    match tag {
+        pm::TableTag::GDEF => handle_gdef_table(ctx, statements)?,
+
+        pm::TableTag::head =>
+            ctx.head = Some(tables::Head::from_parsed_table(statements)),
+        pm::TableTag::name => {
+            let table = tables::Name::from_parsed_table(statements);
+
+            if !table.0.is_empty() {
+                ctx.name.get_or_insert_with(tables::Name::new)
+                    .0.extend(table.0);
+            }
+        }
+
+        pm::TableTag::hhea =>
+            ctx.hhea.get_or_insert_with(Default::default)
+                .update_from_parsed_table(statements)?,
+        pm::TableTag::vhea =>
+            ctx.vhea.get_or_insert_with(Default::default)
+                .update_from_parsed_table(statements)?,
+        pm::TableTag::OS_2 =>
+            ctx.os2.get_or_insert_with(Default::default)
+                .update_from_parsed_table(statements)?,
+    }
+
+    Ok(())
+}
+
+fn handle_mark_class_statement(ctx: &mut CompilerState, mark_class: &pm::MarkClass) -> CompileResult<()> {
+    if !ctx.mark_class_statements_allowed {
+        return Err(CompileError::MarkClassNotAllowed);
+    }
+
+    let pm::MarkClass {
+        glyph_class,
+        anchor,
+        class_name
+    } = mark_class;
+
+    ctx.mark_class_table.entry(class_name.clone())
+        .or_default()
+        .push((glyph_class.clone(), anchor.try_into()?));
+
+    Ok(())
+}
+
+fn handle_anchor_definition(ctx: &mut CompilerState, anchor_def: &pm::AnchorDefinition) -> CompileResult<()> {
+    let pm::AnchorDefinition {
+        name,
+        anchor
+    } = anchor_def;
+
+    ctx.anchor_table.entry(name.clone())
+        .or_insert(anchor.try_into()?);
+
+    Ok(())
+}
+
+fn handle_value_record_definition(ctx: &mut CompilerState, vr_def: &pm::ValueRecordDefinition) -> CompileResult<()> {
+    let pm::ValueRecordDefinition {
+        name,
+        value_record
+    } = vr_def;
+
+    // references to other named value records are resolved here, so that the table never holds
+    // a reference and can't end up with one pointing back at itself.
+    let value_record = match value_record {
+        pm::ValueRecord::Named(other) => ctx.value_record_table.get(other)
+            .cloned()
+            .ok_or_else(|| CompileError::UndefinedReference("value record", other.into()))?,
+
+        vr => vr.clone()
+    };
+
+    ctx.value_record_table.insert(name.clone(), value_record);
+
+    Ok(())
+}
+
+fn handle_glyph_class_definition(ctx: &mut CompilerState, cls: &NamedGlyphClass) -> CompileResult<()> {
+    ctx.glyph_class_table.define(cls.name.clone(), cls.glyph_class.clone())
+}
+
+fn handle_language_system(ctx: &mut CompilerState, ls: &pm::LanguageSystem) {
+    let language_system = LanguageSystem::new(ls.script, ls.language);
+
+    if !ctx.language_systems.contains(&language_system) {
+        ctx.language_systems.push(language_system);
+    }
+}
+
+fn handle_top_level(ctx: &mut CompilerState, statement: &pm::TopLevelStatement) -> CompileResult<()> {
+    use pm::TopLevelStatement::*;
+
+    match statement {
+        LanguageSystem(ref ls) => handle_language_system(ctx, ls),
+
+        Table(ref t) => handle_table(ctx, t)?,
+
+        FeatureDefinition(ref fd) => handle_feature_definition(ctx, fd)?,
+        LookupDefinition(ref ld) => handle_lookup_definition(ctx, ld)?,
+        AnchorDefinition(ref ad) => handle_anchor_definition(ctx, ad)?,
+        ValueRecordDefinition(ref vd) => handle_value_record_definition(ctx, vd)?,
+        NamedGlyphClass(ref gc) => handle_glyph_class_definition(ctx, gc)?,
+
+        MarkClass(ref mc) => handle_mark_class_statement(ctx, mc)?,
+
+        s => panic!("unimplemented top level statement {:?}", s)
+    }
+
+    Ok(())
+}
+
+fn build_gdef(ctx: &mut CompilerState) -> CompileResult<()> {
+    let mut gdef = ctx.gdef.take()
+        .unwrap_or_default();
+
+    // like feaLib, glyph classes are only inferred when there isn't an explicit GlyphClassDef.
+    // every glyph in a mark class is a mark, regardless of how the lookups used it.
+    if gdef.glyph_classes.is_empty() {
+        gdef.glyph_classes = mem::take(&mut ctx.inferred_glyph_classes);
+
+        for (glyph_class, _) in ctx.mark_class_table.values().flatten() {
+            for glyph in glyph_class.iter_glyphs(&ctx.glyph_order, &ctx.glyph_class_table) {
+                gdef.glyph_classes.insert(glyph?, GlyphType::Mark);
+            }
+        }
+    }
+
+    gdef.mark_attach_classes = mem::take(&mut ctx.mark_attach_classes);
+    gdef.mark_glyph_sets = mem::take(&mut ctx.mark_glyph_sets);
+
+    if !gdef.is_empty() {
+        ctx.gdef = Some(gdef);
+    }
+
+    Ok(())
+}
+
+/**
+ * todo: move this out into a separate file
+ */
+
+impl CompilerOutput {
+    // the name IDs which feature params refer to. these are the only ones the compiler allocates.
+    fn feature_name_ids(&self) -> BTreeSet<u16> {
+        let gsub_params = self.gsub.iter().flat_map(|gsub| gsub.feature_params.values());
+        let gpos_params = self.gpos.iter().flat_map(|gpos| gpos.feature_params.values());
+
+        gsub_params.chain(gpos_params)
+            .flat_map(FeatureParams::name_ids)
+            .collect()
+    }
+
+    // names are allocated IDs from 256 up while compiling, without knowing what's already in the
+    // font. if that clashes with the font's own names, the allocated IDs are all moved up by the
+    // same amount to just past them, which keeps runs of consecutive IDs (for `cvParameters`)
+    // intact.
+    fn name_id_offset(allocated: &BTreeSet<u16>, existing: Option<&tables::Name>) -> EncodeResult<u16> {
+        let (first, existing) = match (allocated.iter().next(), existing) {
+            (Some(first), Some(existing)) => (*first as usize, existing),
+            _ => return Ok(0)
+        };
+
+        existing.next_user_name_id()
+            .saturating_sub(first)
+            .checked_into("name", "name ID offset")
+    }
+
+    pub fn merge_encoded_tables(&self, tables: &mut EncodedTables) -> EncodeResult<()> {
+        let existing_names = tables.get_table(tag!(n,a,m,e))
+            .map(|existing| tables::Name::decode_from_be_bytes(&existing.bytes));
+
+        let allocated_name_ids = self.feature_name_ids();
+        let name_id_offset = Self::name_id_offset(&allocated_name_ids, existing_names.as_ref())?;
+
+        macro_rules! encode_table {
+            ($table:ident, $tag:expr) => {
+                if let Some(table) = self.$table.as_ref() {
+                    let mut buf = EncodeBuf::new_with_glyph_order(&self.glyph_order);
+                    buf.name_id_offset = name_id_offset;
+
+                    // if the table is too big for its 16-bit offsets, we have another go with
+                    // every lookup promoted to an extension lookup.
+                    match table.ttf_encode(&mut buf) {
+                        Err(EncodeError::U16Overflow { .. }) => {
+                            buf = EncodeBuf::new_with_glyph_order(&self.glyph_order);
+                            buf.use_extension_lookups = true;
+                            buf.name_id_offset = name_id_offset;
+
+                            table.ttf_encode(&mut buf)?;
+                        },
+
+                        res => { res?; }
+                    }
+
+                    tables.add_table($tag, buf.bytes, buf.source_map);
+                }
+            }
+        }
+
+        encode_table!(gpos, tag!(G,P,O,S));
+        encode_table!(gsub, tag!(G,S,U,B));
+        encode_table!(gdef, tag!(G,D,E,F));
+
+        // names are added to the font's own name table, rather than replacing it.
+        if let Some(name) = self.name.as_ref() {
+            let records = name.0.iter()
+                .map(|record| {
+                    let name_id =
+                        if allocated_name_ids.contains(&record.name_id) {
+                            (record.name_id as usize + name_id_offset as usize)
+                                .checked_into("name", "name ID")?
+                        } else {
+                            record.name_id
+                        };
+
+                    Ok(NameRecord { name_id, ..record.clone() })
+                })
+                .collect::<EncodeResult<Vec<_>>>()?;
+
+            let mut merged = existing_names.unwrap_or_default();
+            merged.merge(records);
+
+            tables.add_table(tag!(n,a,m,e), merged.to_be(), SourceMap::new());
+        }
+
+        // hhea, vhea and OS/2 overrides are patched into the font's existing table if it has one, so
+        // that the metrics which the feature file doesn't mention are left alone.
+        macro_rules! patch_table {
+            ($overrides:ident, $table:ty, $tag:expr) => {
+                if let Some(overrides) = self.$overrides.as_ref() {
+                    let mut table = match tables.get_table($tag) {
+                        Some(existing) => <$table>::ttf_decode(&existing.bytes)
+                            .map_err(|e| EncodeError::ExistingTable(stringify!($overrides), e))?,
+                        None => <$table>::new()
+                    };
+
+                    overrides.apply(&mut table);
+
+                    let mut buf = EncodeBuf::new_with_glyph_order(&self.glyph_order);
+                    table.ttf_encode(&mut buf)?;
+
+                    tables.add_table($tag, buf.bytes, SourceMap::new());
+                }
+            }
+        }
+
+        patch_table!(hhea, tables::Hhea, tag!(h,h,e,a));
+        patch_table!(vhea, tables::Vhea, tag!(v,h,e,a));
+        patch_table!(os2, tables::Os2, tag!(O,S,Slash,_2));
+
+        Ok(())
+    }
+
+    pub fn encode_tables(&self) -> EncodeResult<EncodedTables> {
+        let mut encoded = EncodedTables::new(self.head.clone());
+        self.merge_encoded_tables(&mut encoded)?;
+        Ok(encoded)
+    }
+}
+
+pub fn compile_iter<'a, I>(glyph_order: GlyphOrder, statements: I)
+    -> CompileResult<CompilerOutput>
+    where I: Iterator<Item = &'a pm::TopLevelStatement>
+{
+    let mut ctx = CompilerState::new();
+
+    ctx.glyph_order = glyph_order;
+
+    for s in statements {
+        handle_top_level(&mut ctx, &s)?;
+    }
+
+    build_aalt(&mut ctx)?;
+    build_gdef(&mut ctx)?;
+
+    Ok(ctx.into())
+}
 
 #[inline]
 pub fn compile(glyph_order: GlyphOrder, statements: &[pm::TopLevelStatement])
     -> CompileResult<CompilerOutput> {
     compile_iter(glyph_order, statements.iter())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::glyph::GlyphRef;
+    use crate::parser::ParseError;
+    use crate::compile_model::util::decode::decode_u16_be;
+
+    const GLYPH_COUNT: usize = 4000;
+
+    // the parser needs more stack than test threads get by default in debug builds.
+    fn on_large_stack<T, F>(f: F) -> T
+        where T: Send + 'static,
+              F: FnOnce() -> T + Send + 'static
+    {
+        std::thread::Builder::new()
+            .stack_size(64 * 1024 * 1024)
+            .spawn(f)
+            .unwrap()
+            .join()
+            .unwrap()
+    }
+
+    fn glyph_order() -> GlyphOrder {
+        (0..GLYPH_COUNT)
+            .map(|i| GlyphRef::from_name(&format!("g{}", i)))
+            .enumerate()
+            .collect_into_glyph_order()
+            .unwrap()
+    }
+
+    fn parse(fea: &str) -> Result<Vec<pm::TopLevelStatement>, ParseError> {
+        let fea = fea.to_string();
+        on_large_stack(move || crate::parser::parse_str(&fea))
+    }
+
+    fn try_compile_str(fea: &str) -> CompileResult<CompilerOutput> {
+        let fea = fea.to_string();
+        on_large_stack(move || compile(glyph_order(), &crate::parser::parse_str(&fea).unwrap()))
+    }
+
+    fn compile_str(fea: &str) -> CompilerOutput {
+        try_compile_str(fea).unwrap()
+    }
+
+    fn encode<T: TTFEncode>(table: &T) -> Vec<u8> {
+        let mut buf = EncodeBuf::new();
+        table.ttf_encode(&mut buf).unwrap();
+        buf.bytes
+    }
+
+    fn gsub(fea: &str) -> Vec<u8> {
+        encode(compile_str(fea).gsub.as_ref().unwrap())
+    }
+
+    fn gpos(fea: &str) -> Vec<u8> {
+        encode(compile_str(fea).gpos.as_ref().unwrap())
+    }
+
+    fn u16s(bytes: &[u8], offset: usize, count: usize) -> Vec<u16> {
+        (0..count)
+            .map(|i| decode_u16_be(bytes, offset + i * 2))
+            .collect()
+    }
+
+    fn decode_u32_be(bytes: &[u8], offset: usize) -> u32 {
+        ((decode_u16_be(bytes, offset) as u32) << 16) | decode_u16_be(bytes, offset + 2) as u32
+    }
+
+    // the start of every lookup in a GSUB or GPOS table.
+    fn lookup_starts(table: &[u8]) -> Vec<usize> {
+        let lookup_list = decode_u16_be(table, 8) as usize;
+
+        u16s(table, lookup_list + 2, decode_u16_be(table, lookup_list) as usize).into_iter()
+            .map(|offset| lookup_list + offset as usize)
+            .collect()
+    }
+
+    // (lookup type, start of each subtable) for every lookup in a GSUB or GPOS table.
+    fn lookups(table: &[u8]) -> Vec<(u16, Vec<usize>)> {
+        lookup_starts(table).into_iter()
+            .map(|lookup| {
+                let subtable_count = decode_u16_be(table, lookup + 4) as usize;
+
+                let subtables = (0..subtable_count)
+                    .map(|j| lookup + decode_u16_be(table, lookup + 6 + j * 2) as usize)
+                    .collect();
+
+                (decode_u16_be(table, lookup), subtables)
+            })
+            .collect()
+    }
+
+    // follows every extension subtable to the subtable it points at, returning
+    // (extension_lookup_type, start of the subtable) for each.
+    fn extension_targets(table: &[u8], subtables: &[usize]) -> Vec<(u16, usize)> {
+        subtables.iter()
+            .map(|ext| {
+                assert_eq!(decode_u16_be(table, *ext), 1);
+
+                let target = *ext + decode_u32_be(table, *ext + 4) as usize;
+                assert!(target < table.len());
+
+                (decode_u16_be(table, *ext + 2), target)
+            })
+            .collect()
+    }
+
+    fn coverage_glyphs(table: &[u8], coverage: usize) -> Vec<u16> {
+        let count = decode_u16_be(table, coverage + 2) as usize;
+
+        match decode_u16_be(table, coverage) {
+            1 => u16s(table, coverage + 4, count),
+
+            2 => (0..count)
+                .flat_map(|i| decode_u16_be(table, coverage + 4 + i * 6)
+                    ..=decode_u16_be(table, coverage + 6 + i * 6))
+                .collect(),
+
+            format => panic!("invalid coverage format {}", format)
+        }
+    }
+
+    // (backtrack, input, lookahead, (sequence index, lookup index) records) for each rule of a
+    // format 1 chained context subtable, with the first input glyph filled in from the coverage.
+    type ChainRule = (Vec<u16>, Vec<u16>, Vec<u16>, Vec<(u16, u16)>);
+
+    fn chain_format_1_rules(table: &[u8], subtable: usize) -> Vec<ChainRule> {
+        assert_eq!(decode_u16_be(table, subtable), 1);
+
+        let coverage = coverage_glyphs(table, subtable + decode_u16_be(table, subtable + 2) as usize);
+        let set_offsets = u16s(table, subtable + 6, decode_u16_be(table, subtable + 4) as usize);
+
+        coverage.iter().zip(set_offsets)
+            .flat_map(|(first, set_offset)| {
+                let set = subtable + set_offset as usize;
+
+                u16s(table, set + 2, decode_u16_be(table, set) as usize).into_iter()
+                    .map(move |rule_offset| {
+                        let mut at = set + rule_offset as usize;
+
+                        let sequence = |at: &mut usize, implied: Option<u16>| {
+                            let count = decode_u16_be(table, *at) as usize;
+                            let explicit = count - implied.is_some() as usize;
+                            let glyphs = implied.into_iter()
+                                .chain(u16s(table, *at + 2, explicit))
+                                .collect::<Vec<_>>();
+
+                            *at += 2 + explicit * 2;
+                            glyphs
+                        };
+
+                        let backtrack = sequence(&mut at, None);
+                        let input = sequence(&mut at, Some(*first));
+                        let lookahead = sequence(&mut at, None);
+
+                        let records = u16s(table, at + 2, decode_u16_be(table, at) as usize * 2)
+                            .chunks(2)
+                            .map(|record| (record[0], record[1]))
+                            .collect();
+
+                        (backtrack, input, lookahead, records)
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    fn anchor(table: &[u8], at: usize) -> Option<(i16, i16)> {
+        Some((decode_u16_be(table, at + 2) as i16, decode_u16_be(table, at + 4) as i16))
+    }
+
+    // anchors from an array of offsets relative to `base`, where a null offset is a missing anchor.
+    fn anchors(table: &[u8], base: usize, offsets_at: usize, count: usize) -> Vec<Option<(i16, i16)>> {
+        u16s(table, offsets_at, count).into_iter()
+            .map(|offset| match offset {
+                0 => None,
+                offset => anchor(table, base + offset as usize)
+            })
+            .collect()
+    }
+
+    // (glyph, class, anchor) for every mark in the MarkArray of a mark attachment subtable.
+    fn mark_array(table: &[u8], subtable: usize) -> Vec<(u16, u16, (i16, i16))> {
+        let marks = coverage_glyphs(table, subtable + decode_u16_be(table, subtable + 2) as usize);
+        let array = subtable + decode_u16_be(table, subtable + 8) as usize;
+
+        marks.into_iter().enumerate()
+            .map(|(i, glyph)| {
+                let record = array + 2 + i * 4;
+                let anchor = anchor(table, array + decode_u16_be(table, record + 2) as usize);
+
+                (glyph, decode_u16_be(table, record), anchor.unwrap())
+            })
+            .collect()
+    }
+
+    // (glyph, anchor per mark class) for every base of a mark-to-base or mark-to-mark subtable.
+    fn base_array(table: &[u8], subtable: usize) -> Vec<(u16, Vec<Option<(i16, i16)>>)> {
+        let bases = coverage_glyphs(table, subtable + decode_u16_be(table, subtable + 4) as usize);
+        let class_count = decode_u16_be(table, subtable + 6) as usize;
+        let array = subtable + decode_u16_be(table, subtable + 10) as usize;
+
+        bases.into_iter().enumerate()
+            .map(|(i, glyph)| {
+                let record = array + 2 + i * class_count * 2;
+                (glyph, anchors(table, array, record, class_count))
+            })
+            .collect()
+    }
+
+    // the start of the first feature table with `tag` in a GSUB or GPOS table.
+    fn feature_table(table: &[u8], tag: &str) -> usize {
+        let feature_list = decode_u16_be(table, 6) as usize;
+
+        (0..decode_u16_be(table, feature_list) as usize)
+            .map(|i| feature_list + 2 + i * 6)
+            .find(|record| tag_at(table, *record) == tag)
+            .map(|record| feature_list + decode_u16_be(table, record + 4) as usize)
+            .unwrap_or_else(|| panic!("no {} feature", tag))
+    }
+
+    // the start of the params for the first feature with `tag`, which are offset from its feature
+    // table.
+    fn feature_params(table: &[u8], tag: &str) -> usize {
+        let feature = feature_table(table, tag);
+        feature + decode_u16_be(table, feature) as usize
+    }
+
+    fn name_records(output: &CompilerOutput) -> Vec<(u16, u16, u16, u16, &str)> {
+        output.name.iter()
+            .flat_map(|name| name.0.iter())
+            .map(|nr| (nr.platform_id, nr.encoding_id, nr.language_id, nr.name_id, &*nr.name))
+            .collect()
+    }
+
+    // (glyph, class) for every glyph with a non-zero class in a ClassDef of either format.
+    fn class_def(table: &[u8], at: usize) -> Vec<(u16, u16)> {
+        match decode_u16_be(table, at) {
+            1 => {
+                let first = decode_u16_be(table, at + 2);
+
+                u16s(table, at + 6, decode_u16_be(table, at + 4) as usize).into_iter()
+                    .enumerate()
+                    .filter(|(_, class)| *class != 0)
+                    .map(|(i, class)| (first + i as u16, class))
+                    .collect()
+            },
+
+            2 => u16s(table, at + 4, decode_u16_be(table, at + 2) as usize * 3)
+                .chunks(3)
+                .flat_map(|range| (range[0]..=range[1]).map(move |glyph| (glyph, range[2])))
+                .collect(),
+
+            format => panic!("unknown ClassDef format {}", format)
+        }
+    }
+
+    fn tag_at(bytes: &[u8], offset: usize) -> String {
+        String::from_utf8_lossy(&bytes[offset..offset + 4]).into_owned()
+    }
+
+    // (script, language, required feature, [(feature, lookups)]) for every LangSys in a GSUB or
+    // GPOS table, with the default LangSys listed as `dflt`.
+    type LangSysEntry = (String, String, Option<(String, Vec<u16>)>, Vec<(String, Vec<u16>)>);
+
+    fn script_list(table: &[u8]) -> Vec<LangSysEntry> {
+        let script_list = decode_u16_be(table, 4) as usize;
+        let feature_list = decode_u16_be(table, 6) as usize;
+
+        let feature = |index: u16| {
+            let record = feature_list + 2 + index as usize * 6;
+            let feature = feature_list + decode_u16_be(table, record + 4) as usize;
+            let lookups = u16s(table, feature + 4, decode_u16_be(table, feature + 2) as usize);
+            (tag_at(table, record), lookups)
+        };
+
+        let lang_sys = |script: String, language: String, at: usize| {
+            let required = match decode_u16_be(table, at + 2) {
+                0xFFFF => None,
+                index => Some(feature(index))
+            };
+
+            let features = u16s(table, at + 6, decode_u16_be(table, at + 4) as usize)
+                .into_iter()
+                .map(feature)
+                .collect();
+
+            (script, language, required, features)
+        };
+
+        let mut entries = vec![];
+
+        for i in 0..decode_u16_be(table, script_list) as usize {
+            let record = script_list + 2 + i * 6;
+            let script = script_list + decode_u16_be(table, record + 4) as usize;
+
+            match decode_u16_be(table, script) {
+                0 => (),
+                default => entries.push(lang_sys(tag_at(table, record), "dflt".into(),
+                    script + default as usize))
+            }
+
+            for j in 0..decode_u16_be(table, script + 2) as usize {
+                let lang_record = script + 4 + j * 6;
+                entries.push(lang_sys(tag_at(table, record), tag_at(table, lang_record),
+                    script + decode_u16_be(table, lang_record + 4) as usize));
+            }
+        }
+
+        entries
+    }
+
+    #[test]
+    fn test_single_substitution_formats() {
+        // the same delta for every glyph is format 1.
+        let table = gsub("feature test { sub g1 by g2; sub [g3 g4] by [g4 g5]; } test;");
+        let (lookup_type, subtables) = &lookups(&table)[0];
+
+        assert_eq!(*lookup_type, 1);
+        assert_eq!(u16s(&table, subtables[0], 1), [1]);
+        assert_eq!(decode_u16_be(&table, subtables[0] + 4), 1);
+
+        // otherwise the substitutes are listed in coverage order.
+        let table = gsub("feature test { sub g3 by g4; sub g1 by g7; } test;");
+        let (_, subtables) = &lookups(&table)[0];
+
+        assert_eq!(u16s(&table, subtables[0], 1), [2]);
+        assert_eq!(u16s(&table, subtables[0] + 4, 3), [2, 7, 4]);
+    }
+
+    #[test]
+    fn test_unsupported_substitution_is_rejected() {
+        let err = parse("feature test { sub g1 g2 by g3 g4; } test;").unwrap_err();
+        assert_eq!(err.message, "unsupported substitution form");
+    }
+
+    #[test]
+    fn test_ligature_sets_are_longest_first() {
+        let table = gsub("
+            feature test {
+                sub g1 g2 by g10;
+                sub g1 g2 g3 by g11;
+                sub g1 g4 by g12;
+            } test;
+        ");
+
+        let (lookup_type, subtables) = &lookups(&table)[0];
+        assert_eq!(*lookup_type, 4);
+
+        let subtable = subtables[0];
+        assert_eq!(u16s(&table, subtable, 1), [1]);
+        assert_eq!(u16s(&table, subtable + 4, 1), [1]);
+
+        let set = subtable + decode_u16_be(&table, subtable + 6) as usize;
+        let ligatures: Vec<_> = u16s(&table, set + 2, decode_u16_be(&table, set) as usize)
+            .into_iter()
+            .map(|offset| {
+                let ligature = set + offset as usize;
+                let component_count = decode_u16_be(&table, ligature + 2) as usize;
+
+                (decode_u16_be(&table, ligature), u16s(&table, ligature + 4, component_count - 1))
+            })
+            .collect();
+
+        assert_eq!(ligatures, [(11, vec![2, 3]), (10, vec![2]), (12, vec![4])]);
+    }
+
+    #[test]
+    fn test_ligature_component_count_overflows() {
+        let mut subtable = gsub::Ligature::default();
+        subtable.add_ligature(&[1; 70000], 2);
+
+        match subtable.ttf_encode(&mut EncodeBuf::new()) {
+            Err(EncodeError::U16Overflow { item, .. }) => assert_eq!(item, "component count"),
+            res => panic!("expected the component count to overflow, got {:?}", res)
+        }
+    }
+
+    #[test]
+    fn test_chain_context_formats() {
+        let formats = |fea: &str| {
+            let table = gsub(fea);
+            let (lookup_type, subtables) = lookups(&table).swap_remove(0);
+
+            (lookup_type, subtables.iter()
+                .map(|subtable| decode_u16_be(&table, *subtable))
+                .collect::<Vec<_>>())
+        };
+
+        // rules of single glyphs share a format 1 subtable, and without any backtrack or
+        // lookahead, the lookup is the smaller non-chained type.
+        assert_eq!(formats("
+            feature test {
+                sub g1' g2' by g3;
+                sub g1' g4' by g5;
+                sub g1' g6' by g7;
+            } test;
+        "), (5, vec![1]));
+
+        assert_eq!(formats("
+            feature test {
+                sub g1 g2' by g3;
+                sub g1 g4' by g3;
+                sub g1 g6' by g3;
+            } test;
+        "), (6, vec![1]));
+
+        // rules made of the same few disjoint classes share a format 2 subtable.
+        let classes = ["[g1 g5 g9]", "[g2 g6 g10]", "[g3 g7 g11]", "[g4 g8 g12]"];
+        let mut fea = String::from("feature test {\n");
+
+        for input in &classes {
+            for lookahead in &classes {
+                fea += &format!("    sub {}' {} by g30;\n", input, lookahead);
+            }
+        }
+
+        fea += "} test;\n";
+        assert_eq!(formats(&fea), (6, vec![2]));
+
+        // and anything else gets a format 3 subtable per rule.
+        assert_eq!(formats("
+            feature test {
+                sub [g1 g3]' [g5 g7] by g30;
+                sub [g1 g5]' [g3 g7] by g31;
+            } test;
+        "), (6, vec![3, 3]));
+    }
+
+    #[test]
+    fn test_ignore_substitution() {
+        let table = gsub("
+            feature test {
+                ignore sub g1 g2', g2' g3;
+                sub g2' by g4;
+            } test;
+        ");
+
+        let lookups = lookups(&table);
+        assert_eq!(lookups.len(), 2);
+        assert_eq!(lookups[0].0, 6);
+        assert_eq!(lookups[1].0, 1);
+
+        // the ignore rules come first and don't apply any lookups.
+        assert_eq!(chain_format_1_rules(&table, lookups[0].1[0]), [
+            (vec![1], vec![2], vec![], vec![]),
+            (vec![], vec![2], vec![3], vec![]),
+            (vec![], vec![2], vec![], vec![(0, 1)])
+        ]);
+    }
+
+    #[test]
+    fn test_reverse_chain_substitution() {
+        let table = gsub("
+            feature test {
+                rsub g1 [g2 g3]' g4 by [g5 g6];
+                rsub g1 g7' g4 by g8;
+                subtable;
+                rsub g1 g9' g4 by g10;
+            } test;
+        ");
+
+        let (lookup_type, subtables) = &lookups(&table)[0];
+        assert_eq!(*lookup_type, 8);
+
+        // rules with the same context are merged until the subtable break.
+        let decoded: Vec<_> = subtables.iter()
+            .map(|subtable| {
+                let subtable = *subtable;
+                let coverage = |at: usize| coverage_glyphs(&table,
+                    subtable + decode_u16_be(&table, at) as usize);
+
+                assert_eq!(decode_u16_be(&table, subtable), 1);
+                assert_eq!(u16s(&table, subtable + 4, 1), [1]);
+                assert_eq!(u16s(&table, subtable + 8, 1), [1]);
+
+                (coverage(subtable + 2), coverage(subtable + 6), coverage(subtable + 10),
+                    u16s(&table, subtable + 14, decode_u16_be(&table, subtable + 12) as usize))
+            })
+            .collect();
+
+        assert_eq!(decoded, [
+            (vec![2, 3, 7], vec![1], vec![4], vec![5, 6, 8]),
+            (vec![9], vec![1], vec![4], vec![10])
+        ]);
+    }
+
+    #[test]
+    fn test_mark_to_mark_position() {
+        let output = compile_str("
+            markClass g10 <anchor 0 100> @TOP;
+            markClass [g11 g13] <anchor 0 -100> @BOTTOM;
+
+            feature mkmk {
+                pos mark g12 <anchor 5 200> mark @TOP <anchor 6 -200> mark @BOTTOM;
+                pos mark g14 <anchor 7 300> mark @TOP;
+            } mkmk;
+        ");
+
+        let table = encode(output.gpos.as_ref().unwrap());
+        let (lookup_type, subtables) = &lookups(&table)[0];
+
+        assert_eq!(*lookup_type, 6);
+        assert_eq!(decode_u16_be(&table, subtables[0]), 1);
+
+        assert_eq!(mark_array(&table, subtables[0]), [
+            (10, 0, (0, 100)),
+            (11, 1, (0, -100)),
+            (13, 1, (0, -100))
+        ]);
+
+        assert_eq!(base_array(&table, subtables[0]), [
+            (12, vec![Some((5, 200)), Some((6, -200))]),
+            (14, vec![Some((7, 300)), None])
+        ]);
+
+        // the base marks are marks as far as GDEF is concerned too.
+        let gdef = output.gdef.as_ref().unwrap();
+        assert_eq!(gdef.glyph_classes.get(&12), Some(&gdef::GlyphType::Mark));
+        assert_eq!(gdef.glyph_classes.get(&14), Some(&gdef::GlyphType::Mark));
+    }
+
+    #[test]
+    fn test_mark_to_ligature_position() {
+        let table = gpos("
+            anchorDef 300 600 TOP_RIGHT;
+
+            markClass g10 <anchor 0 100> @TOP;
+            markClass g11 <anchor 0 -100> @BOTTOM;
+
+            feature mark {
+                pos ligature g20 <anchor 100 500> mark @TOP <anchor 100 -50> mark @BOTTOM
+                    ligComponent <anchor NULL>
+                    ligComponent <anchor TOP_RIGHT> mark @TOP;
+            } mark;
+        ");
+
+        let (lookup_type, subtables) = &lookups(&table)[0];
+        let subtable = subtables[0];
+
+        assert_eq!(*lookup_type, 5);
+        assert_eq!(decode_u16_be(&table, subtable), 1);
+        assert_eq!(mark_array(&table, subtable), [(10, 0, (0, 100)), (11, 1, (0, -100))]);
+
+        let ligatures = coverage_glyphs(&table, subtable + decode_u16_be(&table, subtable + 4) as usize);
+        assert_eq!(ligatures, [20]);
+
+        let array = subtable + decode_u16_be(&table, subtable + 10) as usize;
+        let attach = array + decode_u16_be(&table, array + 2) as usize;
+
+        // a component with a NULL anchor still gets a record, with no anchors in it.
+        let components: Vec<_> = (0..decode_u16_be(&table, attach) as usize)
+            .map(|i| anchors(&table, attach, attach + 2 + i * 4, 2))
+            .collect();
+
+        assert_eq!(components, [
+            vec![Some((100, 500)), Some((100, -50))],
+            vec![None, None],
+            vec![Some((300, 600)), None]
+        ]);
+    }
+
+    #[test]
+    fn test_named_anchors_in_mark_attachment() {
+        let fea = |statement: &str| format!("
+            anchorDef 10 20 TOP;
+            markClass g10 <anchor 0 100> @TOP;
+            feature mark {{ {} }} mark;
+        ", statement);
+
+        for statement in &["pos base g1 <anchor TOP> mark @TOP;", "pos mark g2 <anchor TOP> mark @TOP;"] {
+            let table = gpos(&fea(statement));
+            let subtable = lookups(&table)[0].1[0];
+
+            assert_eq!(base_array(&table, subtable)[0].1, [Some((10, 20))]);
+        }
+    }
+
+    #[test]
+    fn test_chain_context_position() {
+        let table = gpos("
+            lookup SHIFT {
+                pos g2 -30;
+            } SHIFT;
+
+            feature test {
+                ignore pos g5 g2';
+                pos g1 g2' 20 g3;
+                pos g4' lookup SHIFT;
+            } test;
+        ");
+
+        let lookups = lookups(&table);
+        let types: Vec<_> = lookups.iter().map(|(lookup_type, _)| *lookup_type).collect();
+        assert_eq!(types, [1, 8, 1]);
+
+        // the inline value record becomes an anonymous lookup of its own.
+        assert_eq!(chain_format_1_rules(&table, lookups[1].1[0]), [
+            (vec![5], vec![2], vec![], vec![]),
+            (vec![1], vec![2], vec![3], vec![(0, 2)]),
+            (vec![], vec![4], vec![], vec![(0, 0)])
+        ]);
+
+        // inline value records are gathered into format 2 single adjustment subtables, here with
+        // a single value record of just an x advance.
+        let single = lookups[2].1[0];
+        assert_eq!(u16s(&table, single, 1), [2]);
+        assert_eq!(coverage_glyphs(&table, single + decode_u16_be(&table, single + 2) as usize), [2]);
+        assert_eq!(u16s(&table, single + 4, 3), [4, 1, 20]);
+    }
+
+    #[test]
+    fn test_lookup_flags_start_new_lookups() {
+        let output = compile_str("
+            @MARKS = [g10 g11];
+
+            feature test {
+                sub g1 by g2;
+                lookupflag RightToLeft IgnoreMarks;
+                sub g3 by g4;
+                lookupflag 0;
+                sub g5 by g6;
+                lookupflag UseMarkFilteringSet @MARKS;
+                sub g7 by g8;
+                lookupflag UseMarkFilteringSet @MARKS;
+                sub g9 by g10;
+            } test;
+        ");
+
+        let table = encode(output.gsub.as_ref().unwrap());
+        let lookups = lookup_starts(&table);
+
+        // going back to earlier flags doesn't go back to the earlier lookup, but restating the
+        // current flags carries on with the current one.
+        let flags: Vec<_> = lookups.iter()
+            .map(|lookup| decode_u16_be(&table, lookup + 2))
+            .collect();
+
+        assert_eq!(flags, [0, 9, 0, 0x10]);
+
+        // the mark filtering set follows the subtable offsets.
+        assert_eq!(u16s(&table, lookups[3] + 4, 1), [1]);
+        assert_eq!(u16s(&table, lookups[3] + 8, 1), [0]);
+
+        let gdef = output.gdef.as_ref().unwrap();
+        assert_eq!(gdef.mark_glyph_sets.len(), 1);
+        assert_eq!(gdef.mark_glyph_sets[0].iter().copied().collect::<Vec<_>>(), [10, 11]);
+    }
+
+    #[test]
+    fn test_script_and_language_sections() {
+        let table = gsub("
+            languagesystem DFLT dflt;
+            languagesystem latn dflt;
+            languagesystem latn TRK ;
+
+            feature liga {
+                sub g1 by g2;
+                script latn;
+                language DEU;
+                sub g3 by g4;
+                language TRK exclude_dflt;
+                sub g5 by g6;
+            } liga;
+
+            feature locl {
+                script latn;
+                language ROM required;
+                sub g7 by g8;
+            } locl;
+        ");
+
+        let feature = |(tag, lookups): &(&str, &[u16])| (tag.to_string(), lookups.to_vec());
+
+        let entry = |script: &str, language: &str, required: Option<(&str, &[u16])>,
+            features: &[(&str, &[u16])]| -> LangSysEntry {
+            (script.into(), language.into(), required.as_ref().map(feature),
+                features.iter().map(feature).collect())
+        };
+
+        assert_eq!(script_list(&table), [
+            entry("DFLT", "dflt", None, &[("liga", &[0])]),
+            entry("latn", "dflt", None, &[("liga", &[0])]),
+            entry("latn", "DEU ", None, &[("liga", &[0, 1])]),
+            // the required feature is only referenced through `requiredFeatureIndex`.
+            entry("latn", "ROM ", Some(("locl", &[3])), &[]),
+            entry("latn", "TRK ", None, &[("liga", &[2])]),
+        ]);
+    }
+
+    #[test]
+    fn test_gdef_glyph_classes_are_inferred() {
+        let fea = "
+            markClass [g10 g11] <anchor 0 0> @TOP;
+
+            feature mark {
+                pos base [g1 g2] <anchor 0 0> mark @TOP;
+                pos ligature g3 <anchor 0 0> mark @TOP ligComponent <anchor 10 0> mark @TOP;
+            } mark;
+
+            feature mkmk {
+                pos mark g12 <anchor 0 0> mark @TOP;
+            } mkmk;
+        ";
+
+        let gdef = encode(compile_str(fea).gdef.as_ref().unwrap());
+        assert_eq!(class_def(&gdef, decode_u16_be(&gdef, 4) as usize),
+            [(1, 1), (2, 1), (3, 2), (10, 3), (11, 3), (12, 3)]);
+
+        // an explicit GlyphClassDef replaces the inferred classes entirely.
+        let fea = format!("{}
+            table GDEF {{
+                GlyphClassDef [g1], [g3], [g10], [g20];
+            }} GDEF;
+        ", fea);
+
+        let gdef = encode(compile_str(&fea).gdef.as_ref().unwrap());
+        assert_eq!(class_def(&gdef, decode_u16_be(&gdef, 4) as usize),
+            [(1, 1), (3, 2), (10, 3), (20, 4)]);
+    }
+
+    #[test]
+    fn test_class_definitions_at_the_end_of_the_glyph_range() {
+        let glyph_class_def = |classes: &[(u16, gdef::GlyphType)]| {
+            let mut gdef = tables::GDEF::new();
+            gdef.glyph_classes.extend(classes.iter().copied());
+
+            let table = encode(&gdef);
+            let at = decode_u16_be(&table, 4) as usize;
+            (decode_u16_be(&table, at), class_def(&table, at))
+        };
+
+        assert_eq!(glyph_class_def(&[(0xFFFE, gdef::GlyphType::Base), (0xFFFF, gdef::GlyphType::Mark)]),
+            (1, vec![(0xFFFE, 1), (0xFFFF, 3)]));
+
+        // spanning every glyph id; only the ranges are small enough to encode.
+        assert_eq!(glyph_class_def(&[(0, gdef::GlyphType::Base), (0xFFFF, gdef::GlyphType::Mark)]),
+            (2, vec![(0, 1), (0xFFFF, 3)]));
+    }
+
+    #[test]
+    fn test_named_value_records() {
+        let named = gpos("
+            valueRecordDef <-10 0 -20 0> TIGHT;
+            valueRecordDef <TIGHT> ALIAS;
+            valueRecordDef 30 WIDE;
+
+            feature kern {
+                valueRecordDef <5 0 0 0> LOCAL;
+
+                pos g1 g2 <TIGHT>;
+                pos g3 g4 <ALIAS>;
+                pos g5 <LOCAL>;
+                pos g6 <WIDE>;
+            } kern;
+
+            feature vkrn {
+                pos g7 <WIDE>;
+            } vkrn;
+        ");
+
+        // a single number is an advance in whichever direction the feature it's used in runs,
+        // so the same named record is an x advance in `kern` and a y advance in `vkrn`.
+        let inline = gpos("
+            feature kern {
+                pos g1 g2 <-10 0 -20 0>;
+                pos g3 g4 <-10 0 -20 0>;
+                pos g5 <5 0 0 0>;
+                pos g6 30;
+            } kern;
+
+            feature vkrn {
+                pos g7 30;
+            } vkrn;
+        ");
+
+        assert_eq!(named, inline);
+
+        let (_, subtables) = lookups(&named).pop().unwrap();
+        assert_eq!(u16s(&named, subtables[0], 1), [2]);
+        assert_eq!(u16s(&named, subtables[0] + 4, 3), [0x0008, 1, 30]);
+    }
+
+    #[test]
+    fn test_undefined_value_records_are_rejected() {
+        let undefined = |fea| match try_compile_str(fea) {
+            Err(CompileError::UndefinedReference("value record", name)) => name,
+            res => panic!("unexpected result {:?}", res.map(|_| ()))
+        };
+
+        assert_eq!(undefined("feature kern { pos g1 <NOPE>; } kern;"), "NOPE");
+        assert_eq!(undefined("valueRecordDef <NOPE> ALIAS;"), "NOPE");
+    }
+
+    #[test]
+    fn test_size_feature_params() {
+        let size_params = |output: &CompilerOutput| {
+            let table = encode(output.gpos.as_ref().unwrap());
+            let feature = feature_table(&table, "size");
+
+            // the params offset is from the start of the feature table, and there are no lookups.
+            assert_eq!(u16s(&table, feature + 2, 1), [0]);
+            u16s(&table, feature + decode_u16_be(&table, feature) as usize, 5)
+        };
+
+        let output = compile_str(r#"
+            feature size {
+                parameters 10.5 3 80 139;
+                sizemenuname "Regular";
+                sizemenuname 1 "Mac Regular";
+            } size;
+        "#);
+
+        assert_eq!(size_params(&output), [105, 3, 256, 80, 139]);
+        assert_eq!(name_records(&output), [
+            (3, 1, 0x409, 256, "Regular"),
+            (1, 0, 0, 256, "Mac Regular")
+        ]);
+
+        // without a subfamily there's no range, and no name for it either. integers are already
+        // in decipoints.
+        let output = compile_str("feature size { parameters 120 0; } size;");
+
+        assert_eq!(size_params(&output), [120, 0, 0, 0, 0]);
+        assert!(output.name.is_none());
+
+        assert!(matches!(try_compile_str("feature liga { parameters 12 0; } liga;"),
+            Err(CompileError::OnlyAllowedIn("parameters", _))));
+    }
+
+    const STYLISTIC_SET_AND_CHARACTER_VARIANT: &str = r#"
+        feature ss01 {
+            featureNames {
+                name "Alternate a";
+                name 1 "Alt a";
+            };
+
+            sub g1 by g2;
+        } ss01;
+
+        feature cv01 {
+            cvParameters {
+                FeatUILabelNameID {
+                    name "Label";
+                };
+
+                ParamUILabelNameID {
+                    name "First";
+                };
+
+                ParamUILabelNameID {
+                    name "Second";
+                };
+
+                Character 0x61;
+                Character 0x1F600;
+            };
+
+            sub g3 by g4;
+        } cv01;
+    "#;
+
+    #[test]
+    fn test_stylistic_set_and_character_variant_params() {
+        let output = compile_str(STYLISTIC_SET_AND_CHARACTER_VARIANT);
+        let table = encode(output.gsub.as_ref().unwrap());
+
+        assert_eq!(u16s(&table, feature_params(&table, "ss01"), 2), [0, 256]);
+
+        // the named parameters come after the feature's own names, with consecutive IDs.
+        let cv01 = feature_params(&table, "cv01");
+        assert_eq!(u16s(&table, cv01, 7), [0, 257, 0, 0, 2, 258, 2]);
+        assert_eq!(table[cv01 + 14..cv01 + 20], [0x00, 0x00, 0x61, 0x01, 0xF6, 0x00]);
+
+        assert_eq!(name_records(&output), [
+            (3, 1, 0x409, 256, "Alternate a"),
+            (1, 0, 0, 256, "Alt a"),
+            (3, 1, 0x409, 257, "Label"),
+            (3, 1, 0x409, 258, "First"),
+            (3, 1, 0x409, 259, "Second")
+        ]);
+
+        // no names, no name table.
+        let output = compile_str("feature cv01 { cvParameters { Character 0x1F600; }; sub g1 by g2; } cv01;");
+        assert!(output.name.is_none());
+
+        assert!(matches!(
+            try_compile_str("feature cv01 { cvParameters { Character 0x110000; }; sub g1 by g2; } cv01;"),
+            Err(CompileError::InvalidValue("Unicode character", 0x110000))));
+    }
+
+    #[test]
+    fn test_names_are_merged_into_the_fonts_name_table() {
+        let record = |name_id, name: &str| NameRecord {
+            platform_id: 3,
+            encoding_id: 1,
+            language_id: 0x409,
+            name_id,
+            name: name.into()
+        };
+
+        let font_names = tables::Name(vec![
+            record(1, "Family"),
+            record(2, "Regular"),
+            record(256, "Font's own"),
+            record(300, "Font's own too")
+        ]);
+
+        let fea = format!(r#"{}
+            table name {{
+                nameid 1 "Overridden";
+                nameid 9 "Designer";
+            }} name;
+        "#, STYLISTIC_SET_AND_CHARACTER_VARIANT);
+
+        let output = compile_str(&fea);
+
+        let mut tables = EncodedTables::new(None);
+        tables.add_table(tag!(n,a,m,e), font_names.to_be(), SourceMap::new());
+        output.merge_encoded_tables(&mut tables).unwrap();
+
+        let names = tables::Name::decode_from_be_bytes(&tables.get_table(tag!(n,a,m,e)).unwrap().bytes);
+        let names: Vec<_> = names.0.iter()
+            .map(|nr| (nr.platform_id, nr.name_id, &*nr.name))
+            .collect();
+
+        // the allocated IDs all move up past the font's highest, and names the feature file gives
+        // an ID of its own replace the font's.
+        assert_eq!(names, [
+            (1, 301, "Alt a"),
+            (3, 1, "Overridden"),
+            (3, 2, "Regular"),
+            (3, 9, "Designer"),
+            (3, 256, "Font's own"),
+            (3, 300, "Font's own too"),
+            (3, 301, "Alternate a"),
+            (3, 302, "Label"),
+            (3, 303, "First"),
+            (3, 304, "Second")
+        ]);
+
+        let gsub = &tables.get_table(tag!(G,S,U,B)).unwrap().bytes;
+        assert_eq!(u16s(gsub, feature_params(gsub, "ss01"), 2), [0, 301]);
+        assert_eq!(u16s(gsub, feature_params(gsub, "cv01"), 6), [0, 302, 0, 0, 2, 303]);
+
+        // without a name table in the font, the IDs are left as they were allocated.
+        let mut tables = EncodedTables::new(None);
+        output.merge_encoded_tables(&mut tables).unwrap();
+
+        let gsub = &tables.get_table(tag!(G,S,U,B)).unwrap().bytes;
+        assert_eq!(u16s(gsub, feature_params(gsub, "ss01"), 2), [0, 256]);
+    }
+
+    #[test]
+    fn test_hhea_and_vhea_overrides_patch_the_fonts_tables() {
+        let output = compile_str("
+            table hhea { CaretOffset -20; Ascender 800; } hhea;
+            table vhea { VertTypoLineGap 50; } vhea;
+        ");
+
+        let font_hhea = tables::Hhea {
+            ascender: 750,
+            descender: -250,
+            line_gap: 100,
+            advance_width_max: 1200,
+            number_of_h_metrics: 42,
+            ..tables::Hhea::new()
+        };
+
+        let font_vhea = tables::Vhea {
+            vert_typo_ascender: 500,
+            vert_typo_descender: -500,
+            vert_typo_line_gap: 0,
+            advance_height_max: 1000,
+            num_of_long_ver_metrics: 7,
+            ..tables::Vhea::new()
+        };
+
+        let mut tables = EncodedTables::new(None);
+        tables.add_table(tag!(h,h,e,a), encode(&font_hhea), SourceMap::new());
+        tables.add_table(tag!(v,h,e,a), encode(&font_vhea), SourceMap::new());
+        output.merge_encoded_tables(&mut tables).unwrap();
+
+        // only the metrics the feature file mentions change.
+        let hhea = tables::Hhea::ttf_decode(&tables.get_table(tag!(h,h,e,a)).unwrap().bytes).unwrap();
+        assert_eq!((hhea.caret_offset, hhea.ascender), (-20, 800));
+        assert_eq!((hhea.descender, hhea.line_gap), (-250, 100));
+        assert_eq!((hhea.advance_width_max, hhea.number_of_h_metrics), (1200, 42));
+
+        let vhea = tables::Vhea::ttf_decode(&tables.get_table(tag!(v,h,e,a)).unwrap().bytes).unwrap();
+        assert_eq!(vhea.vert_typo_line_gap, 50);
+        assert_eq!((vhea.vert_typo_ascender, vhea.vert_typo_descender), (500, -500));
+        assert_eq!((vhea.advance_height_max, vhea.num_of_long_ver_metrics), (1000, 7));
+
+        // without the tables in the font, they're made from the defaults.
+        let mut tables = EncodedTables::new(None);
+        output.merge_encoded_tables(&mut tables).unwrap();
+
+        let hhea = tables::Hhea::ttf_decode(&tables.get_table(tag!(h,h,e,a)).unwrap().bytes).unwrap();
+        assert_eq!((hhea.major_version, hhea.caret_offset, hhea.ascender, hhea.descender), (1, -20, 800, 0));
+    }
+
+    #[test]
+    fn test_os2_overrides_patch_the_fonts_table() {
+        let mut font_os2 = tables::Os2::new();
+        font_os2.common.version = 3;
+        font_os2.common.x_avg_char_width = 520;
+        font_os2.common.us_weight_class = 700;
+        font_os2.common.fs_selection = 0x20;
+        font_os2.common.s_typo_ascender = 750;
+        font_os2.code_pages.ul_code_page_range_1 = 1;
+        font_os2.metrics.sx_height = 480;
+        font_os2.metrics.s_cap_height = 700;
+
+        let patch = |fea: &str| {
+            let mut tables = EncodedTables::new(None);
+            tables.add_table(tag!(O,S,Slash,_2), encode(&font_os2), SourceMap::new());
+            compile_str(fea).merge_encoded_tables(&mut tables).unwrap();
+
+            let bytes = &tables.get_table(tag!(O,S,Slash,_2)).unwrap().bytes;
+            (bytes.len(), tables::Os2::ttf_decode(bytes).unwrap())
+        };
+
+        // only the fields the feature file mentions change, and the font's version is kept.
+        let (len, os2) = patch("
+            table OS/2 { FSType 4; XHeight 500; Vendor \"ABCD\"; UnicodeRange 0 33; } OS/2;
+        ");
+
+        assert_eq!((len, os2.version()), (96, 3));
+        assert_eq!((os2.common.fs_type, os2.metrics.sx_height), (4, 500));
+        assert_eq!(&os2.common.ach_vend_id, b"ABCD");
+        assert_eq!((os2.common.ul_unicode_range_1, os2.common.ul_unicode_range_2), (1, 2));
+
+        assert_eq!((os2.common.x_avg_char_width, os2.common.us_weight_class), (520, 700));
+        assert_eq!((os2.common.fs_selection, os2.common.s_typo_ascender), (0x20, 750));
+        assert_eq!((os2.code_pages.ul_code_page_range_1, os2.metrics.s_cap_height), (1, 700));
+
+        // the optical size range needs version 5, which adds it on the end.
+        let (len, os2) = patch("table OS/2 { LowerOpSize 80; } OS/2;\n");
+
+        assert_eq!((len, os2.version()), (100, 5));
+        assert_eq!(os2.optical_size.us_lower_optical_point_size, 80);
+        assert_eq!(os2.optical_size.us_upper_optical_point_size, 0xFFFF);
+        assert_eq!((os2.common.us_weight_class, os2.metrics.sx_height), (700, 480));
+    }
+
+    #[test]
+    fn test_aalt_merges_alternates_in_order() {
+        let table = gsub("
+            feature salt { sub g1 by g2; sub g5 by g6; } salt;
+            feature swsh { sub g1 from [g3 g2]; } swsh;
+
+            feature aalt {
+                feature salt;
+                feature swsh;
+                feature salt;
+                sub g1 by g4;
+            } aalt;
+        ");
+
+        // glyphs with one alternate become a single substitution, ahead of the alternate one.
+        let lookups = lookups(&table);
+        assert_eq!(lookups.iter().map(|(t, _)| *t).collect::<Vec<_>>(), [1, 3, 1, 3]);
+
+        let single = lookups[0].1[0];
+        assert_eq!(coverage_glyphs(&table, single + decode_u16_be(&table, single + 2) as usize), [5]);
+        assert_eq!(decode_u16_be(&table, single + 4), 1);
+
+        // the aalt block's own substitutions come first, then each referenced feature's in the
+        // order they're referenced, without repeating an alternate.
+        let alternate = lookups[1].1[0];
+        assert_eq!(coverage_glyphs(&table, alternate + decode_u16_be(&table, alternate + 2) as usize), [1]);
+        assert_eq!(u16s(&table, alternate + 4, 1), [1]);
+
+        let set = alternate + decode_u16_be(&table, alternate + 6) as usize;
+        assert_eq!(u16s(&table, set, 4), [3, 4, 2, 3]);
+
+        let aalt = feature_table(&table, "aalt");
+        assert_eq!(u16s(&table, aalt + 2, 3), [2, 0, 1]);
+    }
+
+    #[test]
+    fn test_feature_references_outside_aalt_are_rejected() {
+        assert!(matches!(try_compile_str("feature salt { feature swsh; } salt;"),
+            Err(CompileError::OnlyAllowedIn("feature", _))));
+
+        match try_compile_str("feature aalt { feature swsh; } aalt;") {
+            Err(CompileError::UndefinedReference("feature", tag)) => assert_eq!(tag, "swsh"),
+            res => panic!("unexpected result {:?}", res.map(|_| ()))
+        }
+    }
+
+    #[test]
+    fn test_extension_offsets_point_at_subtables() {
+        let output = compile_str("
+            feature test {
+                sub g1 by g2;
+                sub g3 by g4 g5;
+                sub g6 g7 by g8;
+                sub g9' g10 by g11;
+                sub g12 from [g13 g14];
+            } test;
+        ");
+
+        let gsub = output.gsub.as_ref().unwrap();
+
+        let mut buf = EncodeBuf::new();
+        gsub.ttf_encode(&mut buf).unwrap();
+        let plain = buf.bytes;
+
+        let mut buf = EncodeBuf::new();
+        buf.use_extension_lookups = true;
+        gsub.ttf_encode(&mut buf).unwrap();
+        let extended = buf.bytes;
+
+        let plain_lookups = lookups(&plain);
+        let extended_lookups = lookups(&extended);
+
+        assert_eq!(plain_lookups.len(), extended_lookups.len());
+
+        for ((lookup_type, subtables), (ext_type, ext_subtables)) in
+                plain_lookups.iter().zip(&extended_lookups) {
+            assert_eq!(*ext_type, 7);
+            assert_eq!(subtables.len(), ext_subtables.len());
+
+            let targets = extension_targets(&extended, ext_subtables);
+
+            // the subtables are encoded the same either way, so the extension subtable has to lead
+            // to the same bytes as the plain lookup's subtable.
+            for (subtable, (extension_lookup_type, target)) in subtables.iter().zip(targets) {
+                assert_eq!(extension_lookup_type, *lookup_type);
+                assert_eq!(extended[target..target + 6], plain[*subtable..*subtable + 6]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_overflowing_table_is_retried_with_extension_lookups() {
+        // every lookup is a format 2 single substitution of 2000 glyphs which don't form any
+        // ranges, so about 8K each. ten of them push the later lookups out of reach of the lookup
+        // list's offsets.
+        let mut fea = String::new();
+
+        for lookup in 0..10 {
+            fea += &format!("lookup l{} {{\n", lookup);
+
+            for glyph in 0..2000 {
+                fea += &format!("    sub g{} by g{};\n", glyph * 2,
+                    (glyph * 7 + lookup + 1) % GLYPH_COUNT);
+            }
+
+            fea += &format!("}} l{};\n", lookup);
+        }
+
+        fea += "feature test {\n";
+
+        for lookup in 0..10 {
+            fea += &format!("    lookup l{};\n", lookup);
+        }
+
+        fea += "} test;\n";
+
+        let output = compile_str(&fea);
+
+        match output.gsub.as_ref().unwrap().ttf_encode(&mut EncodeBuf::new()) {
+            Err(EncodeError::U16Overflow { .. }) => (),
+            res => panic!("expected the plain encoding to overflow, got {:?}", res.map(|_| ()))
+        }
+
+        let mut tables = EncodedTables::new(None);
+        output.merge_encoded_tables(&mut tables).unwrap();
+
+        let gsub = &tables.get_table(tag!(G,S,U,B)).unwrap().bytes;
+        let gsub_lookups = lookups(gsub);
+
+        assert_eq!(gsub_lookups.len(), 10);
+
+        for (lookup_type, subtables) in gsub_lookups {
+            assert_eq!(lookup_type, 7);
+
+            for (extension_lookup_type, target) in extension_targets(gsub, &subtables) {
+                assert_eq!(extension_lookup_type, 1);
+                assert_eq!(decode_u16_be(gsub, target), 2);
+            }
+        }
+    }
+}
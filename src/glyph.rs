@@ -11,12 +11,20 @@ use ascii::{
 
 use arrayvec::ArrayVec;
 
+use crate::parser::ParseOptions;
+
 
 #[derive(Debug, Error)]
 pub enum GlyphError {
     #[error("glyph name {0} starts with an invalid character")]
     InvalidStartingCharacter(GlyphName),
 
+    #[error("glyph name {0} contains an invalid character ({1:?})")]
+    InvalidCharacter(GlyphName, char),
+
+    #[error("glyph name is empty")]
+    EmptyGlyphName,
+
     #[error("glyph name can be a maximum of 63 characters long")]
     GlyphNameTooLong,
 
@@ -61,6 +69,9 @@ impl fmt::Display for GlyphName {
     }
 }
 
+// the one glyph name which doesn't follow the usual rules.
+pub(crate) const NOTDEF: &str = ".notdef";
+
 #[inline]
 pub(crate) fn glyph_character_valid(c: u8, first_character: bool, development_names: bool) -> bool
 {
@@ -95,7 +106,13 @@ impl GlyphRef {
         Self::CID(GlyphCID(cid))
     }
 
+    /// Validates `name` as a production glyph name, which is what the parser accepts by default.
     pub fn from_name(name: &str) -> Result<Self, GlyphError> {
+        Self::from_name_with_options(name, &ParseOptions::default())
+    }
+
+    /// Validates `name` with the same rules that the parser follows with `options`.
+    pub fn from_name_with_options(name: &str, options: &ParseOptions) -> Result<Self, GlyphError> {
         let astr = AsciiStr::from_ascii(name)?;
 
         let mut n = GlyphNameStorage::new();
@@ -103,15 +120,40 @@ impl GlyphRef {
         n.try_extend_from_slice(astr.as_slice())
             .map_err(|_| GlyphError::GlyphNameTooLong)?;
 
-        // FIXME: development names??
-        if !glyph_character_valid(astr[0].as_byte(), true, true) && astr != ".notdef" {
-            return Err(GlyphError::InvalidStartingCharacter(GlyphName(n)));
+        if astr == NOTDEF {
+            return Ok(Self::Name(GlyphName(n)));
+        }
+
+        let development_names = options.development_glyph_names;
+        let mut chars = astr.as_bytes().iter();
+
+        match chars.next() {
+            None => return Err(GlyphError::EmptyGlyphName),
+
+            Some(&c) if !glyph_character_valid(c, true, development_names) =>
+                return Err(GlyphError::InvalidStartingCharacter(GlyphName(n))),
+
+            Some(_) => ()
+        }
+
+        if let Some(&c) = chars.find(|&&c| !glyph_character_valid(c, false, development_names)) {
+            return Err(GlyphError::InvalidCharacter(GlyphName(n), c as char));
         }
 
         Ok(Self::Name(GlyphName(n)))
     }
 }
 
+// formatted as it would be written in a feature file.
+impl fmt::Display for GlyphRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GlyphRef::Name(ref name) => name.fmt(f),
+            GlyphRef::CID(ref cid) => write!(f, "\\{}", cid.0)
+        }
+    }
+}
+
 impl fmt::Debug for GlyphRef {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
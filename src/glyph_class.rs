@@ -1,9 +1,11 @@
 use std::fmt;
 use std::iter;
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::collections::HashMap;
 
 use crate::glyph_order::*;
 use crate::compile_model::*;
-use crate::compile_model::compiler_state::*;
 use crate::glyph::*;
 use crate::util::*;
 
@@ -67,79 +69,141 @@ impl GlyphClass {
         self.as_single().is_some()
     }
 
-    fn iter_glyphs_no_lookup<'a>(&'a self, glyph_order: &'a GlyphOrder)
-            -> impl Iterator<Item = Result<u16, GlyphOrderError>> + 'a {
-        use GlyphClassItem::*;
-
+    pub fn iter_glyphs<'a>(&'a self, glyph_order: &'a GlyphOrder, gc_table: &'a NamedGlyphClassTable)
+            -> impl Iterator<Item = Result<u16, CompileError>> + 'a {
         self.0.iter()
-            .flat_map(move |i: &GlyphClassItem|
-                match i {
-                    Single(glyph) => {
-                        Either2::A(iter::once(
-                            glyph_order.id_for_glyph(glyph)
-                        ))
-                    },
-
-                    Range { start, end } => {
-                        let start = match glyph_order.id_for_glyph(start) {
-                            Ok(id) => id,
-                            e => return Either2::A(iter::once(e))
-                        };
-
-                        let end = match glyph_order.id_for_glyph(end) {
-                            Ok(id) => id,
-                            e => return Either2::A(iter::once(e))
-                        };
-
-                        Either2::B((start..end+1).map(Ok))
-                    },
-
-                    ClassRef(_) => panic!()
-                }
-        )
+            .flat_map(move |i| i.iter_glyphs(glyph_order, gc_table))
     }
+}
 
-    pub fn iter_glyphs<'a>(&'a self, glyph_order: &'a GlyphOrder, gc_table: &'a NamedGlyphClassTable)
+impl GlyphClassItem {
+    fn iter_glyphs<'a>(&'a self, glyph_order: &'a GlyphOrder, gc_table: &'a NamedGlyphClassTable)
             -> impl Iterator<Item = Result<u16, CompileError>> + 'a {
         use GlyphClassItem::*;
 
-        self.0.iter()
-            .flat_map(move |i: &GlyphClassItem|
-                match i {
-                    Single(glyph) => {
-                        Either3::A(iter::once(
-                            glyph_order.id_for_glyph(glyph)
-                                .map_err(|e| e.into())
-                        ))
-                    },
-
-                    Range { start, end } => {
-                        let start = match glyph_order.id_for_glyph(start) {
-                            Ok(id) => id,
-                            Err(e) => return Either3::A(iter::once(Err(e.into())))
-                        };
-
-                        let end = match glyph_order.id_for_glyph(end) {
-                            Ok(id) => id,
-                            Err(e) => return Either3::A(iter::once(Err(e.into())))
-                        };
-
-                        Either3::B((start..end+1).map(Ok))
-                    },
-
-                    ClassRef(name) => match gc_table.get(name) {
-                        // FIXME: recursive glyph classes
-                        Some(gc) => Either3::C(
-                            gc.iter_glyphs_no_lookup(glyph_order)
-                                .map(|r| r.map_err(|e| e.into()))
-                        ),
-
-                        None => Either3::A(iter::once(Err(
-                            CompileError::UnknownGlyphClass(name.into())
-                        )))
-                    }
-                }
-        )
+        match self {
+            Single(glyph) => {
+                Either3::A(iter::once(
+                    glyph_order.id_for_glyph(glyph)
+                        .map_err(|e| e.into())
+                ))
+            },
+
+            Range { start, end } =>
+                Either3::B(range_glyphs(start, end, glyph_order)),
+
+            ClassRef(name) => match gc_table.resolve(name, glyph_order) {
+                Ok(glyphs) => Either3::C((0..glyphs.len()).map(move |i| Ok(glyphs[i]))),
+                Err(e) => Either3::A(iter::once(Err(e)))
+            }
+        }
+    }
+}
+
+/////////////////////////
+// glyph ranges
+/////////////////////////
+
+fn range_error(start: &GlyphName, end: &GlyphName, reason: &'static str) -> CompileError {
+    CompileError::InvalidGlyphRange {
+        start: start.to_string(),
+        end: end.to_string(),
+        reason
+    }
+}
+
+// expands a range of glyph names into every name it covers. as in the spec, the two names have to
+// be the same length and differ only in a single letter (A-Z or a-z, with both ends in the same
+// case), or in a run of up to three decimal digits. `[a.001 - a.010]` keeps the zero padding.
+fn expand_name_range(start: &GlyphName, end: &GlyphName) -> Result<Vec<GlyphRef>, CompileError> {
+    let (first, last) = (start.0.as_str().as_bytes(), end.0.as_str().as_bytes());
+
+    if first.len() != last.len() {
+        return Err(range_error(start, end, "the glyph names are different lengths"));
+    }
+
+    let prefix = first.iter().zip(last)
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let suffix = first[prefix..].iter().rev().zip(last[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let (first_part, last_part) =
+        (&first[prefix..first.len() - suffix], &last[prefix..last.len() - suffix]);
+
+    let backwards = || Err(range_error(start, end, "the start of the range must come before its end"));
+
+    let with_part = |part: &[u8]| {
+        let name = [&first[..prefix], part, &first[first.len() - suffix..]].concat();
+
+        // the differing part is only ever letters or digits, so this is still a valid name.
+        GlyphRef::from_name(std::str::from_utf8(&name).unwrap())
+            .map_err(|_| range_error(start, end, "the range contains an invalid glyph name"))
+    };
+
+    match (first_part, last_part) {
+        (&[a], &[b]) if a.is_ascii_alphabetic() && b.is_ascii_alphabetic() => {
+            if a.is_ascii_uppercase() != b.is_ascii_uppercase() {
+                return Err(range_error(start, end, "letter ranges can't mix upper and lower case"));
+            }
+
+            if a >= b {
+                return backwards();
+            }
+
+            (a..=b).map(|c| with_part(&[c])).collect()
+        },
+
+        (a, b) if a.len() <= 3 && a.iter().chain(b).all(u8::is_ascii_digit) => {
+            let parse = |digits: &[u8]| digits.iter()
+                .fold(0usize, |n, d| n * 10 + (d - b'0') as usize);
+
+            let (from, to) = (parse(a), parse(b));
+
+            if from >= to {
+                return backwards();
+            }
+
+            (from..=to)
+                .map(|n| with_part(format!("{:0width$}", n, width = a.len()).as_bytes()))
+                .collect()
+        },
+
+        _ if first == last => backwards(),
+
+        _ => Err(range_error(start, end,
+                "the glyph names must differ by a single letter or by up to three digits"))
+    }
+}
+
+// CID ranges cover every glyph ID between the two ends, while ranges of glyph names are expanded by
+// name.
+fn range_glyphs<'a>(start: &GlyphRef, end: &GlyphRef, glyph_order: &'a GlyphOrder)
+        -> impl Iterator<Item = Result<u16, CompileError>> + 'a {
+    let ids = |start, end| -> Result<_, CompileError> {
+        Ok((glyph_order.id_for_glyph(start)?, glyph_order.id_for_glyph(end)?))
+    };
+
+    match (start, end) {
+        (GlyphRef::CID(_), GlyphRef::CID(_)) => match ids(start, end) {
+            Ok((start, end)) => Either3::A((start..end+1).map(Ok)),
+            Err(e) => Either3::C(iter::once(Err(e)))
+        },
+
+        (GlyphRef::Name(start), GlyphRef::Name(end)) => match expand_name_range(start, end) {
+            Ok(glyphs) => Either3::B(glyphs.into_iter()
+                .map(move |glyph| glyph_order.id_for_glyph(&glyph).map_err(|e| e.into()))),
+
+            Err(e) => Either3::C(iter::once(Err(e)))
+        },
+
+        _ => Either3::C(iter::once(Err(CompileError::InvalidGlyphRange {
+            start: start.to_string(),
+            end: end.to_string(),
+            reason: "a range can't mix glyph names and CIDs"
+        })))
     }
 }
 
@@ -173,3 +237,192 @@ pub struct NamedGlyphClass {
     pub name: GlyphClassName,
     pub glyph_class: GlyphClass
 }
+
+// references to other classes are replaced by their contents as a class is defined, so they always
+// mean the class as it was at that point, and redefining a class later on doesn't change the classes
+// built from it. glyph IDs are only worked out once a class is used, and are kept around so that a
+// class which is referenced from many rules is only expanded once.
+#[derive(Default)]
+pub struct NamedGlyphClassTable {
+    classes: HashMap<GlyphClassName, GlyphClass>,
+    resolved: RefCell<HashMap<GlyphClassName, Rc<[u16]>>>
+}
+
+impl NamedGlyphClassTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn get(&self, name: &GlyphClassName) -> Option<&GlyphClass> {
+        self.classes.get(name)
+    }
+
+    pub fn define(&mut self, name: GlyphClassName, glyph_class: GlyphClass)
+            -> Result<(), CompileError> {
+        let mut items = Vec::with_capacity(glyph_class.0.len());
+
+        for item in glyph_class.0 {
+            let inner = match item {
+                GlyphClassItem::ClassRef(inner) => inner,
+                item => {
+                    items.push(item);
+                    continue;
+                }
+            };
+
+            // a class can extend its own previous definition (`@a = [@a b];`), but can't refer
+            // to itself before it has one.
+            match self.classes.get(&inner) {
+                Some(referenced) => items.extend_from_slice(&referenced.0),
+
+                None if inner == name => {
+                    let name = String::from(&name);
+                    return Err(CompileError::GlyphClassCycle(format!("@{} -> @{}", name, name)));
+                },
+
+                None => return Err(CompileError::UnknownGlyphClass((&inner).into()))
+            }
+        }
+
+        self.resolved.get_mut().remove(&name);
+        self.classes.insert(name, GlyphClass(items));
+        Ok(())
+    }
+
+    pub fn resolve(&self, name: &GlyphClassName, glyph_order: &GlyphOrder)
+            -> Result<Rc<[u16]>, CompileError> {
+        if let Some(glyphs) = self.resolved.borrow().get(name) {
+            return Ok(glyphs.clone());
+        }
+
+        let glyph_class = self.classes.get(name)
+            .ok_or_else(|| CompileError::UnknownGlyphClass(name.into()))?;
+
+        let glyphs: Rc<[u16]> = glyph_class.iter_glyphs(glyph_order, self)
+            .collect::<Result<Vec<_>, _>>()?
+            .into();
+
+        self.resolved.borrow_mut().insert(name.clone(), glyphs.clone());
+        Ok(glyphs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name(name: &str) -> GlyphName {
+        match GlyphRef::from_name(name).unwrap() {
+            GlyphRef::Name(name) => name,
+            GlyphRef::CID(_) => unreachable!()
+        }
+    }
+
+    fn expand(start: &str, end: &str) -> Vec<String> {
+        expand_name_range(&name(start), &name(end)).unwrap()
+            .iter()
+            .map(|glyph| glyph.to_string())
+            .collect()
+    }
+
+    fn expand_err(start: &str, end: &str) -> &'static str {
+        match expand_name_range(&name(start), &name(end)) {
+            Err(CompileError::InvalidGlyphRange { reason, .. }) => reason,
+            res => panic!("expected an invalid range, got {:?}", res)
+        }
+    }
+
+    #[test]
+    fn test_letter_ranges() {
+        assert_eq!(expand("a", "e"), ["a", "b", "c", "d", "e"]);
+        assert_eq!(expand("A.sc", "C.sc"), ["A.sc", "B.sc", "C.sc"]);
+        assert_eq!(expand("uniX", "uniZ"), ["uniX", "uniY", "uniZ"]);
+    }
+
+    #[test]
+    fn test_digit_ranges() {
+        assert_eq!(expand("a.001", "a.010"), [
+            "a.001", "a.002", "a.003", "a.004", "a.005",
+            "a.006", "a.007", "a.008", "a.009", "a.010"
+        ]);
+
+        assert_eq!(expand("ref8", "ref9"), ["ref8", "ref9"]);
+        assert_eq!(expand("x.98.alt", "x.99.alt"), ["x.98.alt", "x.99.alt"]);
+    }
+
+    #[test]
+    fn test_invalid_ranges() {
+        assert_eq!(expand_err("a", "C"), "letter ranges can't mix upper and lower case");
+        assert_eq!(expand_err("e", "a"), "the start of the range must come before its end");
+        assert_eq!(expand_err("a.010", "a.001"), "the start of the range must come before its end");
+        assert_eq!(expand_err("a", "a"), "the start of the range must come before its end");
+        assert_eq!(expand_err("a.1", "a.10"), "the glyph names are different lengths");
+        assert_eq!(expand_err("ab", "ba"),
+            "the glyph names must differ by a single letter or by up to three digits");
+        assert_eq!(expand_err("a.0001", "a.1000"),
+            "the glyph names must differ by a single letter or by up to three digits");
+    }
+
+    fn class_name(name: &str) -> GlyphClassName {
+        GlyphClassName(self::name(name).0)
+    }
+
+    fn class(items: &[&str]) -> GlyphClass {
+        GlyphClass(items.iter()
+            .map(|item| match item.strip_prefix('@') {
+                Some(name) => GlyphClassItem::ClassRef(class_name(name)),
+                None => GlyphClassItem::Single(GlyphRef::Name(name(item)))
+            })
+            .collect())
+    }
+
+    #[test]
+    fn test_class_references_resolve_at_definition() {
+        let glyph_order = ["a", "b", "c"].iter()
+            .map(|name| GlyphRef::from_name(name))
+            .enumerate()
+            .collect_into_glyph_order()
+            .unwrap();
+
+        let mut table = NamedGlyphClassTable::new();
+
+        table.define(class_name("A"), class(&["a"])).unwrap();
+        table.define(class_name("B"), class(&["@A"])).unwrap();
+        table.define(class_name("A"), class(&["b"])).unwrap();
+        assert_eq!(&*table.resolve(&class_name("B"), &glyph_order).unwrap(), [0]);
+
+        table.define(class_name("A"), class(&["@A", "c"])).unwrap();
+        assert_eq!(&*table.resolve(&class_name("A"), &glyph_order).unwrap(), [1, 2]);
+    }
+
+    #[test]
+    fn test_class_references_must_be_defined() {
+        let mut table = NamedGlyphClassTable::new();
+
+        match table.define(class_name("B"), class(&["@A"])) {
+            Err(CompileError::UnknownGlyphClass(name)) => assert_eq!(name, "A"),
+            res => panic!("expected an unknown glyph class, got {:?}", res)
+        }
+
+        match table.define(class_name("A"), class(&["@A", "a"])) {
+            Err(CompileError::GlyphClassCycle(_)) => (),
+            res => panic!("expected a glyph class cycle, got {:?}", res)
+        }
+    }
+
+    #[test]
+    fn test_cid_ranges_follow_glyph_ids() {
+        let glyph_order = [10, 3, 7, 5].iter()
+            .map(|cid| Ok::<_, GlyphOrderError>(GlyphRef::from_cid(*cid)))
+            .enumerate()
+            .collect_into_glyph_order()
+            .unwrap();
+
+        let glyphs: Result<Vec<_>, _> =
+            range_glyphs(&GlyphRef::from_cid(3), &GlyphRef::from_cid(5), &glyph_order)
+                .collect();
+
+        assert_eq!(glyphs.unwrap(), [1, 2, 3]);
+    }
+}
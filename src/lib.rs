@@ -11,6 +11,8 @@ pub mod parser;
 mod positioned;
 pub use positioned::*;
 
+mod parse_error;
+
 pub mod compiler;
 pub mod compile_model;
 
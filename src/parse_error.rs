@@ -0,0 +1,193 @@
+use std::fmt;
+use std::path::PathBuf;
+
+use ascii::ToAsciiChar;
+
+use combine::stream::easy;
+
+use crate::SourcePosition;
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    Syntax,
+    IncludeNotFound,
+    IncludeCycle,
+    Io
+}
+
+/// An error from parsing a feature file, or from reading the files that it includes.
+///
+/// `file` is `None` for the root file when parsing from a reader rather than a path. Lines and
+/// columns start at 1; both are 0 when there's no sensible place to point to, such as when the
+/// root file can't be opened.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+
+    pub file: Option<PathBuf>,
+    pub line: usize,
+    pub column: usize,
+
+    pub expected: Vec<String>,
+    pub unexpected: Vec<String>,
+    pub message: String
+}
+
+fn format_info<T, R>(info: easy::Info<T, R>) -> String
+    where T: ToAsciiChar + fmt::Debug,
+          R: AsRef<[u8]>
+{
+    use easy::Info;
+
+    match info {
+        Info::Token(t) => {
+            let debug = format!("{:?}", t);
+
+            t.to_ascii_char()
+                .map(|ch| format!("{:?}", ch.as_char()))
+                .unwrap_or(debug)
+        },
+
+        Info::Range(r) => format!("{:?}", String::from_utf8_lossy(r.as_ref())),
+        Info::Owned(s) => s,
+        Info::Static(s) => s.into()
+    }
+}
+
+impl ParseError {
+    pub(crate) fn new(kind: ParseErrorKind, file: Option<PathBuf>,
+        position: Option<SourcePosition>, message: String) -> Self {
+        Self {
+            kind,
+
+            file,
+            line: position.as_ref().map(|p| p.line).unwrap_or(0),
+            column: position.as_ref().map(|p| p.column).unwrap_or(0),
+
+            expected: Vec::new(),
+            unexpected: Vec::new(),
+            message
+        }
+    }
+
+    pub(crate) fn from_combine<T, R>(file: Option<PathBuf>,
+        errors: easy::Errors<T, R, SourcePosition>) -> Self
+        where T: ToAsciiChar + fmt::Debug,
+              R: AsRef<[u8]>
+    {
+        use easy::Error::*;
+
+        let mut expected = Vec::new();
+        let mut unexpected = Vec::new();
+        let mut messages = Vec::new();
+
+        for e in errors.errors {
+            match e {
+                Unexpected(info) => unexpected.push(format_info(info)),
+                Expected(info) => expected.push(format_info(info)),
+                Message(info) => messages.push(format_info(info)),
+                Other(e) => messages.push(e.to_string())
+            }
+        }
+
+        let message =
+            if !messages.is_empty() {
+                messages.join("; ")
+            } else if !unexpected.is_empty() {
+                format!("unexpected {}", unexpected.join(", "))
+            } else {
+                "syntax error".into()
+            };
+
+        Self {
+            kind: ParseErrorKind::Syntax,
+
+            file,
+            line: errors.position.line,
+            column: errors.position.column,
+
+            expected,
+            unexpected,
+            message
+        }
+    }
+
+    fn location(&self) -> String {
+        let file = self.file.as_ref()
+            .map(|f| f.display().to_string())
+            .unwrap_or_else(|| "<input>".into());
+
+        if self.line == 0 {
+            file
+        } else {
+            format!("{}:{}:{}", file, self.line, self.column)
+        }
+    }
+
+    /// Renders the error in the style of rustc's diagnostics, with the offending line of `source`
+    /// and a caret pointing at the error position.
+    ///
+    /// `source` has to be the contents of `self.file`.
+    pub fn render(&self, source: &str) -> String {
+        let mut out = format!("error: {}\n --> {}\n", self.message, self.location());
+
+        let line = self.line.checked_sub(1)
+            .and_then(|n| source.lines().nth(n));
+
+        if let Some(line) = line {
+            let number = self.line.to_string();
+            let gutter = " ".repeat(number.len());
+
+            // columns count bytes, so find the char boundary they point to. tabs in the source
+            // are kept so the caret lines up however they're displayed.
+            let prefix: String = line.char_indices()
+                .take_while(|(i, _)| *i + 1 < self.column)
+                .map(|(_, c)| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+
+            out += &format!("{} |\n", gutter);
+            out += &format!("{} | {}\n", number, line);
+            out += &format!("{} | {}^\n", gutter, prefix);
+
+            if !self.expected.is_empty() {
+                out += &format!("{} |\n", gutter);
+                out += &format!("{} = expected {}\n", gutter, self.expected.join(" or "));
+            }
+        } else if !self.expected.is_empty() {
+            out += &format!("  = expected {}\n", self.expected.join(" or "));
+        }
+
+        out
+    }
+}
+
+// for errors which are recovered from partway through a parse, and so have to be converted from
+// the stream's error type while it's still generic.
+pub(crate) trait IntoParseError {
+    fn into_parse_error(self) -> ParseError;
+}
+
+impl<T, R> IntoParseError for easy::Errors<T, R, SourcePosition>
+    where T: ToAsciiChar + fmt::Debug,
+          R: AsRef<[u8]>
+{
+    fn into_parse_error(self) -> ParseError {
+        ParseError::from_combine(None, self)
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.location(), self.message)?;
+
+        if !self.expected.is_empty() {
+            write!(f, " (expected {})", self.expected.join(" or "))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for ParseError { }
@@ -58,8 +58,10 @@ pub(crate) fn anonymous<Input>() -> impl Parser<FeaRsStream<Input>, Output = Ano
         .flat_map(|((position, opening_ident), closing_ident)| {
             let closing_ident = match closing_ident {
                 Some(i) => i,
-                None => crate::parse_bail!(Input, position,
-                    "unclosed anonymous block")
+                None => {
+                    crate::parse_bail!(Input, position,
+                        "unclosed anonymous block");
+                }
             };
 
             if opening_ident != closing_ident {
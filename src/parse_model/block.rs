@@ -4,6 +4,7 @@ use combine::{
     Parser,
     Stream,
 
+    attempt,
     look_ahead,
     optional,
 
@@ -28,6 +29,7 @@ use crate::parser::*;
 use crate::glyph_class::*;
 
 use super::util::*;
+use super::cv_parameters::*;
 use super::feature_names::*;
 use super::glyph_class::*;
 use super::lookup_flag::*;
@@ -37,13 +39,17 @@ use super::substitute::*;
 use super::position::*;
 use super::language::*;
 use super::feature::*;
+use super::include::*;
 use super::lookup::*;
 use super::script::*;
+use super::value_record::*;
 
 #[derive(Debug)]
 pub enum BlockStatement {
+    CvParameters(CvParameters),
     FeatureNames(FeatureNames),
     FeatureReference(FeatureReference),
+    Include(Include),
     Language(Language),
     Lookup(Lookup),
     LookupDefinition(LookupDefinition),
@@ -53,7 +59,9 @@ pub enum BlockStatement {
     Parameters(Parameters),
     Position(Position),
     Script(Script),
+    SizeMenuName(SizeMenuName),
     Substitute(Substitute),
+    ValueRecordDefinition(ValueRecordDefinition),
 
     Subtable
 }
@@ -64,8 +72,10 @@ macro_rules! cvt_to_statement (
     }
 );
 
+cvt_to_statement!(CvParameters);
 cvt_to_statement!(FeatureNames);
 cvt_to_statement!(FeatureReference);
+cvt_to_statement!(Include);
 cvt_to_statement!(Language);
 cvt_to_statement!(Lookup);
 cvt_to_statement!(LookupDefinition);
@@ -75,33 +85,38 @@ cvt_to_statement!(NamedGlyphClass);
 cvt_to_statement!(Parameters);
 cvt_to_statement!(Position);
 cvt_to_statement!(Script);
+cvt_to_statement!(SizeMenuName);
 cvt_to_statement!(Substitute);
+cvt_to_statement!(ValueRecordDefinition);
 
 pub(crate) fn block_statement<Input, Ident>(_: &Ident) -> FnOpaque<FeaRsStream<Input>, BlockStatement>
     where Input: Stream<Token = u8, Position = SourcePosition>,
-          Input::Error: ParseError<Input::Token, Input::Range, Input::Position>
+          Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+          Input::Error: IntoParseError
 {
     #[inline]
     fn rule<Input>() -> impl Parser<FeaRsStream<Input>, Output = BlockStatement>
         where Input: Stream<Token = u8, Position = SourcePosition>,
-              Input::Error: ParseError<Input::Token, Input::Range, Input::Position>
+              Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+              Input::Error: IntoParseError
     {
         choice((
                 // if we have a preceding "ignore" statement, we'll skip over it in a look_ahead()
                 // so that the statement for which it's relevant (substitute or position) can parse
                 // it directly.
 
-                look_ahead(
+                attempt(look_ahead(
                     literal_ignore_case("ignore")
                         .or(literal_ignore_case("enum")
                             .skip(optional(literal_ignore_case("erate"))))
                         .skip(required_whitespace())
-                        .with(keyword())),
+                        .with(keyword()))),
                 look_ahead(keyword()),
         ))
             .then(|kwd| {
                 dispatch!(&*kwd;
                     "parameters" => parameters().map(|p| p.into()),
+                    "sizemenuname" => size_menu_name().map(|n| n.into()),
                     "position" | "pos" => position().map(|p| p.into()),
                     "substitute" | "sub"
                         | "reversesub" | "rsub" => substitute().map(|s| s.into()),
@@ -118,16 +133,19 @@ pub(crate) fn block_statement<Input, Ident>(_: &Ident) -> FnOpaque<FeaRsStream<I
                     "markClass" => mark_class().map(|mc| mc.into()),
                     "script" => script().map(|s| s.into()),
                     "language" => language().map(|l| l.into()),
+                    "valueRecordDef" => value_record_definition().map(|v| v.into()),
 
                     "featureNames" => feature_names().map(|n| n.into()),
+                    "cvParameters" => cv_parameters().map(|p| p.into()),
+                    "include" => include().map(|i| i.into()),
 
                     "subtable" => literal("subtable").map(|_| BlockStatement::Subtable),
 
                     _ => combine::position().and(keyword())
-                        .flat_map(|(position, kwd)|
+                        .flat_map(|(position, kwd)| {
                             crate::parse_bail!(Input, position,
-                                format!("unexpected keyword \"{}\"", kwd))
-                        )
+                                format!("unexpected keyword \"{}\"", kwd));
+                        })
                 )
             })
     }
@@ -162,15 +180,19 @@ pub(crate) fn block_statements<Input, Statement, P>(statement_parser: P)
         -> impl Parser<FeaRsStream<Input>, Output = Vec<Statement>>
     where Input: Stream<Token = u8, Position = SourcePosition>,
           Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+          Input::Error: IntoParseError,
           P: Parser<FeaRsStream<Input>, Output = Statement>
 {
     optional_whitespace()
         .with(many(
-            optional_whitespace()
-                .with(statement_parser)
-                .skip(optional_whitespace())
-                .skip(token(b';').expected("semicolon"))
-                .skip(optional_whitespace())))
+            recovering(Resync::Block,
+                optional_whitespace()
+                    .with(statement_parser)
+                    .skip(optional_whitespace())
+                    .skip(token(b';').expected("semicolon"))
+                    .skip(optional_whitespace()))))
+
+        .map(|statements: Vec<Option<Statement>>| statements.into_iter().flatten().collect())
 }
 
 pub(crate) fn block_or_reference<Input, Ident, IF, IP, Statement, SF, SP>
@@ -178,6 +200,7 @@ pub(crate) fn block_or_reference<Input, Ident, IF, IP, Statement, SF, SP>
         -> impl Parser<FeaRsStream<Input>, Output = BlockOrReference<Ident, Statement>>
     where Input: Stream<Token = u8, Position = SourcePosition>,
           Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+          Input::Error: IntoParseError,
           IF: Fn() -> IP,
           IP: Parser<FeaRsStream<Input>, Output = Ident>,
           SF: Fn(&Ident) -> SP,
@@ -225,6 +248,7 @@ pub(crate) fn block<Input, Ident, IF, IP, Statement, SF, SP>
         -> impl Parser<FeaRsStream<Input>, Output = Block<Ident, Statement>>
     where Input: Stream<Token = u8, Position = SourcePosition>,
           Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+          Input::Error: IntoParseError,
           IF: Fn() -> IP,
           IP: Parser<FeaRsStream<Input>, Output = Ident>,
           SF: Fn(&Ident) -> SP,
@@ -236,8 +260,9 @@ pub(crate) fn block<Input, Ident, IF, IP, Statement, SF, SP>
         .flat_map(|(position, res)|
             match res {
                 BlockOrReference::Block(b) => Ok(b),
-                BlockOrReference::Reference(_) =>
+                BlockOrReference::Reference(_) => {
                     crate::parse_bail!(Input, position,
-                        "expected block")
+                        "expected block");
+                }
             })
 }
@@ -0,0 +1,90 @@
+use combine::{
+    Parser,
+    Stream,
+    error::ParseError,
+
+    attempt,
+    look_ahead,
+
+    choice,
+    dispatch
+};
+
+use crate::parser::*;
+
+use super::block::*;
+use super::feature_names::*;
+use super::name::*;
+use super::util::*;
+
+#[derive(Debug)]
+pub enum CvParameter {
+    FeatUILabelNameID(Vec<Name>),
+    FeatUITooltipTextNameID(Vec<Name>),
+    SampleTextNameID(Vec<Name>),
+    ParamUILabelNameID(Vec<Name>),
+    Character(usize)
+}
+
+#[derive(Debug)]
+pub struct CvParameters {
+    pub parameters: Vec<CvParameter>
+}
+
+fn name_block<Input>(kwd: &'static str) -> impl Parser<FeaRsStream<Input>, Output = Vec<Name>>
+    where Input: Stream<Token = u8, Position = SourcePosition>,
+          Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+          Input::Error: IntoParseError
+{
+    literal(kwd)
+        .skip(required_whitespace())
+        .with(block(no_ident, name_statement))
+        .map(|block| block.statements)
+}
+
+fn cv_parameter<Input>(_: &NoIdent) -> impl Parser<FeaRsStream<Input>, Output = CvParameter>
+    where Input: Stream<Token = u8, Position = SourcePosition>,
+          Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+          Input::Error: IntoParseError
+{
+    look_ahead(keyword())
+        .then(|kwd| {
+            dispatch!(&*kwd;
+                "FeatUILabelNameID" =>
+                    name_block("FeatUILabelNameID").map(CvParameter::FeatUILabelNameID),
+                "FeatUITooltipTextNameID" =>
+                    name_block("FeatUITooltipTextNameID").map(CvParameter::FeatUITooltipTextNameID),
+                "SampleTextNameID" =>
+                    name_block("SampleTextNameID").map(CvParameter::SampleTextNameID),
+                "ParamUILabelNameID" =>
+                    name_block("ParamUILabelNameID").map(CvParameter::ParamUILabelNameID),
+
+                "Character" => literal("Character")
+                    .skip(required_whitespace())
+                    .with(choice((
+                        attempt(hex_uint()),
+                        uinteger()
+                    )))
+                    .map(CvParameter::Character),
+
+                _ => combine::position().and(keyword())
+                    .flat_map(|(position, kwd)| {
+                        crate::parse_bail!(Input, position,
+                            format!("unexpected keyword \"{}\" in cvParameters", kwd));
+                    })
+            )
+        })
+}
+
+pub(crate) fn cv_parameters<Input>() -> impl Parser<FeaRsStream<Input>, Output = CvParameters>
+    where Input: Stream<Token = u8, Position = SourcePosition>,
+          Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+          Input::Error: IntoParseError
+{
+    literal("cvParameters")
+        .skip(required_whitespace())
+        .with(block(no_ident, cv_parameter))
+        .map(|block| CvParameters {
+            parameters: block.statements
+        })
+}
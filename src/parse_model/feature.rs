@@ -27,7 +27,8 @@ pub struct FeatureDefinition {
 
 pub(crate) fn feature_definition<Input>() -> impl Parser<FeaRsStream<Input>, Output = FeatureDefinition>
     where Input: Stream<Token = u8, Position = SourcePosition>,
-          Input::Error: ParseError<Input::Token, Input::Range, Input::Position>
+          Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+          Input::Error: IntoParseError
 {
     literal_ignore_case("feature")
         .skip(required_whitespace())
@@ -37,7 +37,7 @@ impl fmt::Display for NoIdent {
     }
 }
 
-fn no_ident<Input>() -> impl Parser<FeaRsStream<Input>, Output = NoIdent>
+pub(crate) fn no_ident<Input>() -> impl Parser<FeaRsStream<Input>, Output = NoIdent>
     where Input: Stream<Token = u8, Position = SourcePosition>,
           Input::Error: ParseError<Input::Token, Input::Range, Input::Position>
 {
@@ -46,7 +46,8 @@ fn no_ident<Input>() -> impl Parser<FeaRsStream<Input>, Output = NoIdent>
 
 pub(crate) fn feature_names<Input>() -> impl Parser<FeaRsStream<Input>, Output = FeatureNames>
     where Input: Stream<Token = u8, Position = SourcePosition>,
-          Input::Error: ParseError<Input::Token, Input::Range, Input::Position>
+          Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+          Input::Error: IntoParseError
 {
     literal_ignore_case("featureNames")
         .skip(required_whitespace())
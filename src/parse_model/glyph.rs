@@ -1,10 +1,15 @@
-use ascii::ToAsciiChar;
+use ascii::{
+    AsAsciiStr,
+    ToAsciiChar
+};
 
 use combine::{
     Parser,
     Stream,
     error::ParseError,
 
+    attempt,
+    not_followed_by,
     parser,
     satisfy,
     choice,
@@ -21,6 +26,29 @@ use super::util::*;
 pub(crate) fn glyph_name_unwrapped<Input>() -> impl Parser<FeaRsStream<Input>, Output = GlyphNameStorage>
     where Input: Stream<Token = u8, Position = SourcePosition>,
           Input::Error: ParseError<Input::Token, Input::Range, Input::Position>
+{
+    choice((
+        notdef(),
+        glyph_name_characters()
+    ))
+    .expected("glyph name")
+}
+
+// ".notdef" is the only glyph name that can start with a period.
+fn notdef<Input>() -> impl Parser<FeaRsStream<Input>, Output = GlyphNameStorage>
+    where Input: Stream<Token = u8, Position = SourcePosition>,
+          Input::Error: ParseError<Input::Token, Input::Range, Input::Position>
+{
+    attempt(literal(NOTDEF)
+        .skip(not_followed_by(satisfy(|c| glyph_character_valid(c, false, false))
+            .map(|_| "glyph name character"))))
+
+        .map(|_| NOTDEF.as_ascii_str().unwrap().chars().collect())
+}
+
+fn glyph_name_characters<Input>() -> impl Parser<FeaRsStream<Input>, Output = GlyphNameStorage>
+    where Input: Stream<Token = u8, Position = SourcePosition>,
+          Input::Error: ParseError<Input::Token, Input::Range, Input::Position>
 {
     parser(move |input: &mut FeaRsStream<Input>| {
         let mut ret = ArrayVec::new();
@@ -68,7 +96,6 @@ pub(crate) fn glyph_name_unwrapped<Input>() -> impl Parser<FeaRsStream<Input>, O
 
         parse_iter.into_result(ret)
     })
-    .expected("glyph name")
 }
 
 pub(crate) fn glyph_ref<Input>() -> impl Parser<FeaRsStream<Input>, Output = GlyphRef>
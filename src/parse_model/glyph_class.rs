@@ -3,12 +3,16 @@ use combine::{
     Stream,
     error::ParseError,
 
+    attempt,
+    not_followed_by,
     parser,
+    satisfy,
     choice,
     token
 };
 
 use crate::parser::*;
+use crate::glyph::glyph_character_valid;
 use crate::glyph_class::*;
 
 use super::class_name::*;
@@ -57,8 +61,7 @@ pub(crate) fn glyph_class<Input>() -> impl Parser<FeaRsStream<Input>, Output = G
                 let mut parse_iter = optional_whitespace()
                     .with(combine::position())
                     .and(choice((
-                        token(b'-')
-                            .skip(optional_whitespace())
+                        range_hyphen()
                             .with(glyph_ref()).map(|gr| Next::RangeSpec(gr)),
 
                         glyph_class_name()
@@ -116,6 +119,28 @@ pub(crate) fn glyph_class<Input>() -> impl Parser<FeaRsStream<Input>, Output = G
         }))
 }
 
+// with development glyph names, a hyphen directly followed by more glyph name characters starts a
+// glyph name rather than a range, so the range hyphen can't be.
+fn range_hyphen<Input>() -> impl Parser<FeaRsStream<Input>, Output = ()>
+    where Input: Stream<Token = u8, Position = SourcePosition>,
+          Input::Error: ParseError<Input::Token, Input::Range, Input::Position>
+{
+    parser(|input: &mut FeaRsStream<Input>| {
+        if input.state.development_glyph_names {
+            attempt(token(b'-')
+                .skip(not_followed_by(satisfy(|c| glyph_character_valid(c, false, true))
+                    .map(|_| "glyph name character"))))
+                .with(optional_whitespace())
+                .parse_stream(input)
+                .into_result()
+        } else {
+            token(b'-').with(optional_whitespace())
+                .parse_stream(input)
+                .into_result()
+        }
+    })
+}
+
 pub(crate) fn glyph_class_or_class_ref<Input>() -> impl Parser<FeaRsStream<Input>, Output = GlyphClass>
     where Input: Stream<Token = u8, Position = SourcePosition>,
           Input::Error: ParseError<Input::Token, Input::Range, Input::Position>
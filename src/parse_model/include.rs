@@ -0,0 +1,43 @@
+use std::path::PathBuf;
+
+use combine::{
+    Parser,
+    Stream,
+    error::ParseError,
+
+    between,
+    satisfy,
+    token,
+
+    parser::repeat::many1
+};
+
+use crate::parser::*;
+use crate::Positioned;
+
+use super::positioned::*;
+use super::util::*;
+
+// the path is kept exactly as written. includes are resolved (and replaced with the statements of
+// the included file) by the parser entry points in `crate::parser`, so the compiler never sees
+// these. the span is of the whole statement, for error reporting.
+#[derive(Debug)]
+pub struct Include(pub Positioned<PathBuf>);
+
+pub(crate) fn include<Input>() -> impl Parser<FeaRsStream<Input>, Output = Include>
+    where Input: Stream<Token = u8, Position = SourcePosition>,
+          Input::Error: ParseError<Input::Token, Input::Range, Input::Position>
+{
+    positioned(
+        literal("include")
+            .skip(optional_whitespace())
+            .with(between(
+                token(b'(').expected("'('"),
+                token(b')').expected("')'"),
+                many1(satisfy(|c| c != b')'))))
+
+            .map(|path: Vec<u8>|
+                PathBuf::from(String::from_utf8_lossy(&path).trim())))
+
+        .map(Include)
+}
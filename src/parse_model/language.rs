@@ -3,12 +3,9 @@ use combine::{
     Stream,
     error::ParseError,
 
-    look_ahead,
     attempt,
     choice,
-
-    token,
-    value
+    optional
 };
 
 use crate::parser::*;
@@ -40,26 +37,19 @@ pub(crate) fn language<Input>() -> impl Parser<FeaRsStream<Input>, Output = Lang
         .skip(required_whitespace())
         .with(language_tag())
 
-        .and(choice((
-            look_ahead(token(b';')).map(|_| None),
-            attempt(required_whitespace()
-                .with(choice((
-                    literal_ignore_case("include_dflt").map(|_| Some(true)),
-                    literal_ignore_case("exclude_dflt").map(|_| Some(false)),
-
-                    // FIXME: these are deprecated and should display a warning
-                    literal_ignore_case("includeDFLT").map(|_| Some(true)),
-                    literal_ignore_case("excludeDFLT").map(|_| Some(false)),
+        .and(optional(attempt(required_whitespace()
+            .with(choice((
+                attempt(literal_ignore_case("include_dflt")).map(|_| true),
+                attempt(literal_ignore_case("exclude_dflt")).map(|_| false),
 
-                    value(None)
-                ))))
-        )))
+                // FIXME: these are deprecated and should display a warning
+                attempt(literal_ignore_case("includeDFLT")).map(|_| true),
+                attempt(literal_ignore_case("excludeDFLT")).map(|_| false)
+            ))))))
 
-        .and(choice((
-            look_ahead(token(b';')).map(|_| false),
-            attempt(required_whitespace()
-                .with(literal_ignore_case("required").map(|_| true)))
-        )))
+        .and(optional(attempt(required_whitespace()
+            .with(literal_ignore_case("required"))))
+            .map(|required| required.is_some()))
 
         .map(|((tag, include_default), required)| {
             Language {
@@ -3,7 +3,11 @@ use std::fmt;
 use combine::{
     Parser,
     Stream,
-    error::ParseError
+    error::ParseError,
+
+    attempt,
+    look_ahead,
+    optional
 };
 
 use crate::parser::*;
@@ -47,24 +51,49 @@ pub(crate) fn lookup_block_label<Input>() -> impl Parser<FeaRsStream<Input>, Out
         .map(LookupName)
 }
 
+// `useExtension` comes between the label and the opening brace, but the label after the closing
+// brace doesn't repeat it. the label is parsed a second time to look for it, so that the block
+// itself only ever sees the label.
+fn use_extension<Input>() -> impl Parser<FeaRsStream<Input>, Output = bool>
+    where Input: Stream<Token = u8, Position = SourcePosition>,
+          Input::Error: ParseError<Input::Token, Input::Range, Input::Position>
+{
+    optional(attempt(
+        required_whitespace()
+            .with(literal("useExtension"))))
+        .map(|kwd| kwd.is_some())
+}
+
+fn lookup_block_ident<Input>() -> impl Parser<FeaRsStream<Input>, Output = LookupName>
+    where Input: Stream<Token = u8, Position = SourcePosition>,
+          Input::Error: ParseError<Input::Token, Input::Range, Input::Position>
+{
+    lookup_block_label()
+        .skip(use_extension())
+}
+
 #[derive(Debug)]
 pub struct LookupDefinition {
     pub label: LookupName,
+    pub use_extension: bool,
     pub statements: Vec<BlockStatement>
 }
 
 pub(crate) fn lookup_definition<Input>() -> impl Parser<FeaRsStream<Input>, Output = LookupDefinition>
     where Input: Stream<Token = u8, Position = SourcePosition>,
-          Input::Error: ParseError<Input::Token, Input::Range, Input::Position>
+          Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+          Input::Error: IntoParseError
 {
     literal_ignore_case("lookup")
         .with(required_whitespace())
 
-        .with(block(lookup_block_label, block_statement))
+        .with(look_ahead(lookup_block_label().with(use_extension())))
+        .and(block(lookup_block_ident, block_statement))
 
-        .map(|block|
+        .map(|(use_extension, block)|
             LookupDefinition {
                 label: block.ident,
+                use_extension,
                 statements: block.statements
             })
 }
@@ -90,16 +119,19 @@ pub enum LookupRefOrDefinition {
 impl LookupRefOrDefinition {
     pub(crate) fn parse<Input>() -> impl Parser<FeaRsStream<Input>, Output = Self>
         where Input: Stream<Token = u8, Position = SourcePosition>,
-              Input::Error: ParseError<Input::Token, Input::Range, Input::Position>
+              Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+              Input::Error: IntoParseError
     {
         literal_ignore_case("lookup")
             .skip(required_whitespace())
-            .with(block_or_reference(lookup_block_label, block_statement))
-            .map(|res| {
+            .with(look_ahead(lookup_block_label().with(use_extension())))
+            .and(block_or_reference(lookup_block_ident, block_statement))
+            .map(|(use_extension, res)| {
                 match res {
                     BlockOrReference::Block(block) =>
                         Self::Definition(LookupDefinition {
                             label: block.ident,
+                            use_extension,
                             statements: block.statements
                         }),
                     BlockOrReference::Reference(r) => Self::Reference(Lookup(r))
@@ -16,12 +16,12 @@ use super::util::*;
 
 #[derive(Debug)]
 pub struct LookupFlag {
-    right_to_left: bool,
-    ignore_base_glyphs: bool,
-    ignore_ligatures: bool,
-    ignore_marks: bool,
-    mark_attachment_type: Option<ClassName>,
-    use_mark_filtering_set: Option<ClassName>
+    pub right_to_left: bool,
+    pub ignore_base_glyphs: bool,
+    pub ignore_ligatures: bool,
+    pub ignore_marks: bool,
+    pub mark_attachment_type: Option<ClassName>,
+    pub use_mark_filtering_set: Option<ClassName>
 }
 
 impl LookupFlag {
@@ -79,19 +79,24 @@ pub(crate) fn lookup_flag<Input>() -> impl Parser<FeaRsStream<Input>, Output = L
                             let ptr =
                                 match fmap.iter_mut().find(|(iden, _)| iden == &kwd) {
                                     Some((_, ptr)) => ptr,
-                                    None => crate::parse_bail!(Input, position, "unexpected keyword")
+                                    None => {
+                                        crate::parse_bail!(Input, position, "unexpected keyword");
+                                    }
                                 };
 
                             match ptr {
-                                &mut Either2::A(ref mut bool_ref) if **bool_ref =>
-                                        crate::parse_bail!(Input, position, "duplicate flag"),
+                                &mut Either2::A(ref mut bool_ref) if **bool_ref => {
+                                    crate::parse_bail!(Input, position, "duplicate flag");
+                                },
                                 &mut Either2::A(ref mut bool_ref) =>
                                     **bool_ref = true,
 
-                                &mut Either2::B(ref mut cn_ref) if cn_ref.is_some() =>
-                                        crate::parse_bail!(Input, position, "duplicate flag"),
-                                &mut Either2::B(_) if class_name.is_none() =>
-                                        crate::parse_bail!(Input, position, "expected class name"),
+                                &mut Either2::B(ref mut cn_ref) if cn_ref.is_some() => {
+                                    crate::parse_bail!(Input, position, "duplicate flag");
+                                },
+                                &mut Either2::B(_) if class_name.is_none() => {
+                                    crate::parse_bail!(Input, position, "expected class name");
+                                },
                                 &mut Either2::B(ref mut cn_ref) =>
                                     **cn_ref = class_name,
                             }
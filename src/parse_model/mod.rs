@@ -16,6 +16,9 @@ pub use class_name::*;
 mod contour_point;
 pub use contour_point::*;
 
+mod cv_parameters;
+pub use cv_parameters::*;
+
 mod device;
 pub use device::*;
 
@@ -33,6 +36,9 @@ mod glyph_class;
 mod glyph_pattern;
 pub use glyph_pattern::*;
 
+mod include;
+pub use include::*;
+
 mod language;
 pub use language::*;
 
@@ -36,8 +36,10 @@ pub(crate) fn name<Input>() -> impl Parser<FeaRsStream<Input>, Output = Name>
                 Ok(match n {
                     1 => Platform::Mac,
                     3 => Platform::Windows,
-                    _ => crate::parse_bail!(Input, position,
-                        "expected platform id 1 or 3")
+                    _ => {
+                        crate::parse_bail!(Input, position,
+                            "expected platform id 1 or 3");
+                    }
                 })
             })
 
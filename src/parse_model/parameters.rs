@@ -1,43 +1,109 @@
+use std::str::{self, FromStr};
+use std::convert::TryFrom;
+
 use combine::{
     Parser,
     Stream,
     error::ParseError,
 
+    attempt,
     optional,
+    satisfy,
 
-    parser::byte::space,
+    parser::repeat::many1
 };
 
 use crate::parser::*;
+use super::name::*;
 use super::util::*;
 
+// sizes given as decimal numbers are in points, while integers are already in decipoints. this is
+// how both makeotf and feaLib read them, so `10.0` and `100` are the same size.
+#[derive(Debug, Clone, Copy)]
+pub enum PointSize {
+    Points(f64),
+    Decipoints(usize)
+}
+
+impl PointSize {
+    pub fn decipoints(&self) -> isize {
+        match *self {
+            PointSize::Points(points) => (points * 10.).round() as isize,
+            PointSize::Decipoints(decipoints) => isize::try_from(decipoints).unwrap_or(isize::MAX)
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Parameters {
-    pub design_size: f64,
+    pub design_size: PointSize,
     pub subfamily_id: isize,
 
-    pub range_start: Option<f64>,
-    pub range_end: Option<f64>
+    pub range_start: Option<PointSize>,
+    pub range_end: Option<PointSize>
+}
+
+#[derive(Debug)]
+pub struct SizeMenuName(pub Name);
+
+fn point_size<Input>() -> impl Parser<FeaRsStream<Input>, Output = PointSize>
+    where Input: Stream<Token = u8, Position = SourcePosition>,
+          Input::Error: ParseError<Input::Token, Input::Range, Input::Position>
+{
+    combine::position()
+        .and(many1(
+                satisfy(|t: u8| t.is_ascii_digit() || t == b'.')
+        ))
+        .flat_map(|(position, digits): (_, Vec<_>)| {
+            // unsafe is fine here. we've verified that this vec only contains valid characters
+            let as_str = unsafe { str::from_utf8_unchecked(&digits) };
+
+            let size = if digits.contains(&b'.') {
+                f64::from_str(as_str).ok().map(PointSize::Points)
+            } else {
+                usize::from_str(as_str).ok().map(PointSize::Decipoints)
+            };
+
+            size.ok_or_else(||
+                Input::Error::from_error(position,
+                    StreamErrorFor::<Input>::expected_static_message(
+                        "couldn't parse a point size")))
+        })
 }
 
 pub(crate) fn parameters<Input>() -> impl Parser<FeaRsStream<Input>, Output = Parameters>
     where Input: Stream<Token = u8, Position = SourcePosition>,
           Input::Error: ParseError<Input::Token, Input::Range, Input::Position>
 {
+    fn range<Input>() -> impl Parser<FeaRsStream<Input>, Output = (PointSize, PointSize)>
+        where Input: Stream<Token = u8, Position = SourcePosition>,
+              Input::Error: ParseError<Input::Token, Input::Range, Input::Position>
+    {
+        required_whitespace()
+            .with(point_size())
+            .skip(required_whitespace())
+            .and(point_size())
+    }
+
     literal_ignore_case("parameters")
         .skip(required_whitespace())
-        .with(decimal_number())
+        .with(point_size())
         .skip(required_whitespace())
         .and(number())
 
-        // FIXME: also need to parse these if subfamily ID is nonzero
-        .and(optional(space()
-                .skip(optional_whitespace())
-                .with(decimal_number())
-                .skip(required_whitespace())
-                .and(decimal_number())))
+        // the range is required for a non-zero subfamily ID, and optional otherwise.
+        .then(|(design_size, subfamily_id)| {
+            let range =
+                if subfamily_id != 0 {
+                    range().map(Some).left()
+                } else {
+                    optional(attempt(range())).right()
+                };
+
+            range.map(move |range| (design_size, subfamily_id, range))
+        })
 
-        .map(|((design_size, subfamily_id), range)| {
+        .map(|(design_size, subfamily_id, range)| {
             let (range_start, range_end) = match range {
                 Some((s, e)) => (Some(s), Some(e)),
                 None => (None, None)
@@ -52,3 +118,13 @@ pub(crate) fn parameters<Input>() -> impl Parser<FeaRsStream<Input>, Output = Pa
             }
         })
 }
+
+pub(crate) fn size_menu_name<Input>() -> impl Parser<FeaRsStream<Input>, Output = SizeMenuName>
+    where Input: Stream<Token = u8, Position = SourcePosition>,
+          Input::Error: ParseError<Input::Token, Input::Range, Input::Position>
+{
+    literal_ignore_case("sizemenuname")
+        .skip(required_whitespace())
+        .with(name())
+        .map(SizeMenuName)
+}
@@ -5,11 +5,14 @@ use combine::{
 
     parser,
 
+    attempt,
     look_ahead,
     optional,
+    token,
 
     parser::repeat::{
         take_until,
+        sep_by1,
         many1
     },
 
@@ -24,7 +27,9 @@ use crate::parser::*;
 use crate::glyph_class::*;
 
 use super::value_record::*;
+use super::glyph_pattern::*;
 use super::glyph_class::*;
+use super::lookup::*;
 use super::class_name::*;
 use super::mark_class::*;
 use super::anchor::*;
@@ -32,7 +37,8 @@ use super::util::*;
 
 #[derive(Debug)]
 pub struct LigatureComponent {
-    anchors: Vec<(Anchor, Option<MarkClassName>)>
+    // a `<anchor NULL>` component has a single anchor without a mark class
+    pub anchors: Vec<(Anchor, Option<MarkClassName>)>
 }
 
 // GPOS type 1
@@ -78,6 +84,29 @@ pub struct MarkToMark {
     pub marks: Vec<(Anchor, MarkClassName)>
 }
 
+#[derive(Debug)]
+pub struct ChainInput {
+    pub glyph_class: GlyphClass,
+    pub value_record: Option<ValueRecord>,
+    pub lookup: Option<LookupName>
+}
+
+// GPOS type 7/8
+//     inline value records ("pos [A V]' -40 T;") are compiled into anonymous single adjustment
+//     lookups.
+#[derive(Debug)]
+pub struct ChainContext {
+    pub prefix: Vec<GlyphClass>,
+    pub input: Vec<ChainInput>,
+    pub suffix: Vec<GlyphClass>
+}
+
+// "ignore pos", which is compiled into GPOS type 7/8 rules without any lookups
+#[derive(Debug)]
+pub struct Ignore {
+    pub contexts: Vec<ChainContext>
+}
+
 #[derive(Debug)]
 pub enum Position {
     SingleAdjustment(SingleAdjustment),
@@ -85,7 +114,9 @@ pub enum Position {
     Cursive(Cursive),
     MarkToBase(MarkToBase),
     Ligature(Ligature),
-    MarkToMark(MarkToMark)
+    MarkToMark(MarkToMark),
+    ChainContext(ChainContext),
+    Ignore(Ignore)
 }
 
 macro_rules! position_from_variant {
@@ -100,6 +131,8 @@ position_from_variant!(Cursive);
 position_from_variant!(MarkToBase);
 position_from_variant!(Ligature);
 position_from_variant!(MarkToMark);
+position_from_variant!(ChainContext);
+position_from_variant!(Ignore);
 
 fn cursive<Input>() -> impl Parser<FeaRsStream<Input>, Output = Position>
     where Input: Stream<Token = u8, Position = SourcePosition>,
@@ -283,6 +316,88 @@ fn single_or_pair<Input>() -> impl Parser<FeaRsStream<Input>, Output = Position>
         })
 }
 
+fn has_context_value_records(pattern: &GlyphPattern) -> bool {
+    pattern.prefix.iter()
+        .chain(pattern.suffix.iter())
+        .any(|item| item.value_record.is_some())
+}
+
+fn chain_context_from_pattern(pattern: GlyphPattern) -> ChainContext {
+    let into_glyphs = |items: Vec<GlyphPatternItem>| -> Vec<GlyphClass> {
+        items.into_iter().map(|item| item.class).collect()
+    };
+
+    let input = pattern.glyphs.into_iter()
+        .map(|item| ChainInput {
+            glyph_class: item.class,
+            value_record: item.value_record,
+            lookup: item.lookup.map(|l| l.0)
+        })
+        .collect();
+
+    ChainContext {
+        prefix: into_glyphs(pattern.prefix),
+        input,
+        suffix: into_glyphs(pattern.suffix)
+    }
+}
+
+fn contextual<Input>() -> impl Parser<FeaRsStream<Input>, Output = Position>
+    where Input: Stream<Token = u8, Position = SourcePosition>,
+          Input::Error: ParseError<Input::Token, Input::Range, Input::Position>
+{
+    combine::position()
+        .and(glyph_pattern())
+        .flat_map(|(position, pattern)| {
+            if !pattern.has_marks {
+                crate::parse_bail!(Input, position,
+                    "Expected a single or pair adjustment, or marked glyphs for contextual positioning");
+            }
+
+            if has_context_value_records(&pattern) {
+                crate::parse_bail!(Input, position,
+                    "Value records are only allowed on marked glyphs in contextual positioning");
+            }
+
+            Ok(chain_context_from_pattern(pattern).into())
+        })
+}
+
+fn ignore<Input>() -> impl Parser<FeaRsStream<Input>, Output = Position>
+    where Input: Stream<Token = u8, Position = SourcePosition>,
+          Input::Error: ParseError<Input::Token, Input::Range, Input::Position>
+{
+    sep_by1(
+        combine::position()
+            .and(glyph_pattern())
+            .flat_map(|(position, pattern)| {
+                if pattern.num_lookups > 0 || pattern.num_value_records > 0 {
+                    crate::parse_bail!(Input, position,
+                        "\"ignore\" statements cannot contain lookup references or value records");
+                }
+
+                Ok(chain_context_from_pattern(pattern))
+            }),
+        token(b',').skip(optional_whitespace()))
+
+        .map(|contexts| Ignore {
+            contexts
+        }.into())
+}
+
+fn single_pair_or_contextual<Input>() -> impl Parser<FeaRsStream<Input>, Output = Position>
+    where Input: Stream<Token = u8, Position = SourcePosition>,
+          Input::Error: ParseError<Input::Token, Input::Range, Input::Position>
+{
+    // contextual statements can start with exactly the same tokens as single and pair
+    // adjustments, so we only know which one we're looking at once we've seen the whole thing.
+    choice((
+        attempt(single_or_pair()
+            .skip(look_ahead(optional_whitespace().with(token(b';'))))),
+        contextual()
+    ))
+}
+
 pub(crate) fn position<Input>() -> impl Parser<FeaRsStream<Input>, Output = Position>
     where Input: Stream<Token = u8, Position = SourcePosition>,
           Input::Error: ParseError<Input::Token, Input::Range, Input::Position>
@@ -313,12 +428,13 @@ pub(crate) fn position<Input>() -> impl Parser<FeaRsStream<Input>, Output = Posi
 
         .and(look_ahead(take_until(space())))
 
-        .then(|(_mode, typ): (Mode, Vec<_>)| {
-            dispatch!(&*typ;
-                b"base" => mark_to_base(),
-                b"cursive" => cursive(),
-                b"ligature" => ligature(),
-                b"mark" => mark_to_mark(),
-                _ => single_or_pair())
+        .then(|(mode, typ): (Mode, Vec<_>)| {
+            dispatch!((mode, &*typ);
+                (Mode::Ignore, _) => ignore(),
+                (_, b"base") => mark_to_base(),
+                (_, b"cursive") => cursive(),
+                (_, b"ligature") => ligature(),
+                (_, b"mark") => mark_to_mark(),
+                _ => single_pair_or_contextual())
         })
 }
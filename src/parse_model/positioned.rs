@@ -1,44 +1,48 @@
 use combine::{
     Parser,
     Stream,
-    error::ParseError,
+    error::{
+        ParseError,
+        Commit
+    },
+
+    parser,
     position
 };
 
 use crate::parser::*;
 use crate::{
     Positioned,
-    SourceSpan
+    SourceSpan,
+    FileId
 };
 
 #[inline]
-fn positions_to_span(start: SourcePosition, end: SourcePosition) -> SourceSpan {
-    SourceSpan {
-        start: crate::SourcePosition {
-            line: start.line as usize,
-            column : start.column as usize,
-        },
-
-        end: crate::SourcePosition {
-            line: end.line as usize,
-            column: end.column as usize,
-        },
-    }
+fn current_file<Input>() -> impl Parser<FeaRsStream<Input>, Output = FileId>
+    where Input: Stream<Token = u8, Position = SourcePosition>,
+          Input::Error: ParseError<Input::Token, Input::Range, Input::Position>
+{
+    parser(|input: &mut FeaRsStream<Input>| Ok((input.state.file, Commit::Peek(()))))
 }
 
 #[inline]
-pub(crate) fn positioned<Input, T, P>(p: P) -> impl Parser<Input, Output = Positioned<T>>
+pub(crate) fn positioned<Input, T, P>(p: P) -> impl Parser<FeaRsStream<Input>, Output = Positioned<T>>
     where Input: Stream<Token = u8, Position = SourcePosition>,
           Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
-          P: Parser<Input, Output = T>
+          P: Parser<FeaRsStream<Input>, Output = T>
 {
-    position()
+    current_file()
+        .and(position())
         .and(p)
         .and(position())
 
-        .map(|((start, value), end)| Positioned {
+        .map(|(((file, start), value), end)| Positioned {
             value,
-            span: positions_to_span(start, end)
+            span: SourceSpan {
+                file,
+                start,
+                end
+            }
         })
 }
 
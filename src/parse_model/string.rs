@@ -61,8 +61,10 @@ fn string_escaped<Input, Escaped, UP, UPF, Unescape>
         .flat_map(|(position, raw): (_, Vec<_>)| {
             match String::from_utf8(raw) {
                 Ok(s) => Ok(s),
-                Err(_) => crate::parse_bail!(Input, position,
-                    "invalid UTF-8")
+                Err(_) => {
+                    crate::parse_bail!(Input, position,
+                        "invalid UTF-8");
+                }
             }
         })
 }
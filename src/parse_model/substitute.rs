@@ -6,8 +6,12 @@ use combine::{
     attempt,
     optional,
     value,
+    token,
 
-    parser::repeat::many1,
+    parser::repeat::{
+        many1,
+        sep_by1
+    },
     parser::byte::letter,
 
     choice
@@ -19,17 +23,14 @@ use crate::glyph::*;
 
 use super::glyph_pattern::*;
 use super::glyph_class::*;
+use super::lookup::*;
 use super::util::*;
 
 // GSUB type 1
 #[derive(Debug)]
 pub struct Single {
-    pub prefix: Vec<GlyphClass>,
     pub glyph_class: GlyphClass,
-    pub suffix: Vec<GlyphClass>,
-    pub replacement: GlyphClass,
-
-    pub force_chain: bool
+    pub replacement: GlyphClass
 }
 
 // GSUB type 2
@@ -46,12 +47,69 @@ pub struct Alternate {
     pub replacement: GlyphClass
 }
 
+// GSUB type 4
+#[derive(Debug)]
+pub struct Ligature {
+    pub glyphs: Vec<GlyphClass>,
+    pub replacement: GlyphRef
+}
+
+// GSUB type 8
+#[derive(Debug)]
+pub struct ReverseChain {
+    pub prefix: Vec<GlyphClass>,
+    pub glyph_class: GlyphClass,
+    pub suffix: Vec<GlyphClass>,
+    pub replacement: GlyphClass
+}
+
+#[derive(Debug)]
+pub struct ChainInput {
+    pub glyph_class: GlyphClass,
+    pub lookup: Option<LookupName>
+}
+
+// inline replacements in contextual rules, each compiled into an anonymous lookup of its own type
+#[derive(Debug)]
+pub enum ChainReplacement {
+    // "sub a b' c by d;"
+    Single(GlyphClass),
+
+    // "sub a b' by c d;"
+    Multiple(Vec<GlyphRef>),
+
+    // "sub a f' i' by f_i;"
+    Ligature(GlyphRef)
+}
+
+// GSUB type 5/6
+//     an inline replacement applies to the marked glyphs, of which only a ligature substitution
+//     can have more than one.
+#[derive(Debug)]
+pub struct ChainContext {
+    pub prefix: Vec<GlyphClass>,
+    pub input: Vec<ChainInput>,
+    pub suffix: Vec<GlyphClass>,
+
+    pub replacement: Option<ChainReplacement>
+}
+
+// "ignore sub", which is compiled into GSUB type 5/6 rules without any lookups
+#[derive(Debug)]
+pub struct Ignore {
+    pub contexts: Vec<ChainContext>
+}
+
 #[allow(dead_code)]
 #[derive(Debug)]
 pub enum Substitute {
     Single(Single),
     Multiple(Multiple),
-    Alternate(Alternate)
+    Alternate(Alternate),
+    Ligature(Ligature),
+    ChainContext(ChainContext),
+    ReverseChain(ReverseChain),
+    Ignore(Ignore)
 }
 
 macro_rules! substitute_from_variant {
@@ -63,6 +121,10 @@ macro_rules! substitute_from_variant {
 substitute_from_variant!(Single);
 substitute_from_variant!(Multiple);
 substitute_from_variant!(Alternate);
+substitute_from_variant!(Ligature);
+substitute_from_variant!(ChainContext);
+substitute_from_variant!(ReverseChain);
+substitute_from_variant!(Ignore);
 
 #[inline]
 fn into_glyphs(items: Vec<GlyphPatternItem>) -> Vec<GlyphClass>
@@ -82,17 +144,57 @@ fn into_first_glyph_class(items: Vec<GlyphPatternItem>) -> Option<GlyphClass>
     into_first(items).map(|g| g.class)
 }
 
+fn ignore<Input>() -> impl Parser<FeaRsStream<Input>, Output = Substitute>
+    where Input: Stream<Token = u8, Position = SourcePosition>,
+          Input::Error: ParseError<Input::Token, Input::Range, Input::Position>
+{
+    sep_by1(
+        combine::position()
+            .and(glyph_pattern())
+            .flat_map(|(position, pattern)| {
+                if pattern.num_lookups > 0 || pattern.num_value_records > 0 {
+                    crate::parse_bail!(Input, position,
+                        "\"ignore\" statements cannot contain lookup references or value records");
+                }
+
+                if !pattern.has_marks {
+                    crate::parse_bail!(Input, position,
+                        "Expected marked glyphs in \"ignore\" statement");
+                }
+
+                let input = pattern.glyphs.into_iter()
+                    .map(|item| ChainInput {
+                        glyph_class: item.class,
+                        lookup: None
+                    })
+                    .collect();
+
+                Ok(ChainContext {
+                    prefix: into_glyphs(pattern.prefix),
+                    input,
+                    suffix: into_glyphs(pattern.suffix),
+
+                    replacement: None
+                })
+            }),
+        token(b',').skip(optional_whitespace()))
+
+        .map(|contexts| Ignore {
+            contexts
+        }.into())
+}
+
 pub(crate) fn substitute<Input>() -> impl Parser<FeaRsStream<Input>, Output = Substitute>
     where Input: Stream<Token = u8, Position = SourcePosition>,
           Input::Error: ParseError<Input::Token, Input::Range, Input::Position>
 {
-    #[derive(PartialEq, Eq)]
+    #[derive(Clone, Copy, PartialEq, Eq)]
     enum SubKeyword {
         By,
         From
     }
 
-    #[derive(PartialEq, Eq)]
+    #[derive(Clone, Copy, PartialEq, Eq)]
     enum Subtype {
         Forward,
         Reverse,
@@ -109,110 +211,211 @@ pub(crate) fn substitute<Input>() -> impl Parser<FeaRsStream<Input>, Output = Su
         .flat_map(|((position, ignore), kwd): (_, Vec<u8>)| {
             let subtype = match &*kwd {
                 b"substitute" | b"sub" if ignore => Subtype::Ignore,
-                b"reversesub" | b"rsub" if ignore =>
+                b"reversesub" | b"rsub" if ignore => {
                     crate::parse_bail!(Input, position,
-                        "\"ignore\" is invalid with reverse substitution"),
+                        "\"ignore\" is invalid with reverse substitution");
+                },
 
                 b"substitute" | b"sub" => Subtype::Forward,
                 b"reversesub" | b"rsub" => Subtype::Reverse,
-                _ => crate::parse_bail!(Input, position, "unexpected keyword")
+                _ => {
+                    crate::parse_bail!(Input, position, "unexpected keyword");
+                }
             };
 
             Ok((position, subtype))
         })
         .skip(required_whitespace())
-        .and(glyph_pattern())
-        .and(choice((
-            literal("by").map(|_| Some(SubKeyword::By))
-                .and(many1(
-                    required_whitespace()
-                    .with(glyph_class_or_glyph()))),
-            literal("from").map(|_| Some(SubKeyword::From))
-                .skip(required_whitespace())
-                .and(glyph_class_or_class_ref().map(|gc| vec![gc])),
-            value(()).map(|_| (None, vec![]))
-        )))
-        .flat_map(|(((position, subtype), pattern), (keyword, replacement))| {
-            if pattern.num_value_records > 0 {
-                crate::parse_bail!(Input, position,
-                    "Substitution statements cannot contain value records");
+        .then(|(position, subtype)| {
+            if subtype == Subtype::Ignore {
+                return ignore().left();
             }
 
-            // GSUB lookup type 3
-            //     "substitute a from [a.1 a.2 a.3];"
-            if keyword == Some(SubKeyword::From) {
-                if subtype == Subtype::Reverse {
-                    crate::parse_bail!(Input, position,
-                        "Reverse chaining substitutions do not support \"from\"");
-                }
-
-                if pattern.glyphs.len() != 1 || !pattern.glyphs[0].class.is_single() {
-                    crate::parse_bail!(Input, position,
-                        "Expected a single glyph before \"from\"");
-                }
+            value((position, subtype))
+                .and(glyph_pattern())
+                .and(choice((
+                    literal("by").map(|_| Some(SubKeyword::By))
+                        .and(many1(
+                            required_whitespace()
+                            .with(glyph_class_or_glyph()))),
+                    literal("from").map(|_| Some(SubKeyword::From))
+                        .skip(required_whitespace())
+                        .and(glyph_class_or_class_ref().map(|gc| vec![gc])),
+                    value(()).map(|_| (None, vec![]))
+                )))
+                .flat_map(|(((position, subtype), pattern), (keyword, replacement))| {
+                    if pattern.num_value_records > 0 {
+                        crate::parse_bail!(Input, position,
+                            "Substitution statements cannot contain value records");
+                    }
+
+                    // GSUB lookup type 3
+                    //     "substitute a from [a.1 a.2 a.3];"
+                    if keyword == Some(SubKeyword::From) {
+                        if subtype == Subtype::Reverse {
+                            crate::parse_bail!(Input, position,
+                                "Reverse chaining substitutions do not support \"from\"");
+                        }
+
+                        if pattern.glyphs.len() != 1 || !pattern.glyphs[0].class.is_single() {
+                            crate::parse_bail!(Input, position,
+                                "Expected a single glyph before \"from\"");
+                        }
+
+                        if replacement.len() != 1 {
+                            crate::parse_bail!(Input, position,
+                                "Expected a single glyph class after \"from\"");
+                        }
+
+                        if pattern.prefix.len() == 0 && pattern.suffix.len() == 0 {
+                            let glyph = into_first_glyph_class(pattern.glyphs).unwrap()
+                                .into_single().unwrap();
+
+                            return Ok(Alternate {
+                                glyph,
+                                replacement: into_first(replacement).unwrap()
+                            }.into());
+                        }
+                    }
+
+                    // GSUB lookup type 8
+                    //     "reversesub [a e n] d' by d.alt;"
+                    //     "reversesub [a-c]' [x y] by [A-C];"
+                    if subtype == Subtype::Reverse {
+                        if pattern.num_lookups > 0 {
+                            crate::parse_bail!(Input, position,
+                                "Reverse chaining substitutions do not support lookup references");
+                        }
+
+                        if pattern.glyphs.len() != 1 {
+                            crate::parse_bail!(Input, position,
+                                "Expected a single glyph or glyph class to be replaced in reverse chaining substitution");
+                        }
+
+                        if keyword != Some(SubKeyword::By) || replacement.len() != 1 {
+                            crate::parse_bail!(Input, position,
+                                "Expected a single glyph or glyph class after \"by\"");
+                        }
+
+                        return Ok(ReverseChain {
+                            prefix: into_glyphs(pattern.prefix),
+                            glyph_class: into_first_glyph_class(pattern.glyphs).unwrap(),
+                            suffix: into_glyphs(pattern.suffix),
+                            replacement: into_first(replacement).unwrap()
+                        }.into());
+                    }
+
+                    // GSUB lookup type 6
+                    //     "substitute [a e n] d' by d.alt;"
+                    //     "substitute a b' by b c;"
+                    //     "substitute a f' i' by f_i;"
+                    //     "substitute a' lookup SUB_A b' lookup SUB_B;"
+                    if subtype == Subtype::Forward && pattern.has_marks {
+                        if keyword.is_some() && pattern.num_lookups > 0 {
+                            crate::parse_bail!(Input, position,
+                                "Lookup references cannot be combined with an inline substitution");
+                        }
+
+                        let marked = pattern.glyphs.len();
+
+                        let replacement = match keyword {
+                            None => None,
+
+                            Some(SubKeyword::By) if marked == 1 && replacement.len() == 1 =>
+                                Some(ChainReplacement::Single(into_first(replacement).unwrap())),
+
+                            Some(SubKeyword::By) if marked == 1
+                                    && pattern.glyphs[0].class.is_single()
+                                    && replacement.iter().all(|cls| cls.is_single()) =>
+                                Some(ChainReplacement::Multiple(replacement.into_iter()
+                                    .map(|cls| cls.into_single().unwrap())
+                                    .collect())),
+
+                            Some(SubKeyword::By) if marked > 1
+                                    && replacement.len() == 1 && replacement[0].is_single() =>
+                                Some(ChainReplacement::Ligature(into_first(replacement).unwrap()
+                                    .into_single().unwrap())),
+
+                            _ => {
+                                crate::parse_bail!(Input, position,
+                                    "unsupported contextual substitution form");
+                            }
+                        };
+
+                        let input = pattern.glyphs.into_iter()
+                            .map(|item| ChainInput {
+                                glyph_class: item.class,
+                                lookup: item.lookup.map(|l| l.0)
+                            })
+                            .collect();
+
+                        return Ok(ChainContext {
+                            prefix: into_glyphs(pattern.prefix),
+                            input,
+                            suffix: into_glyphs(pattern.suffix),
+
+                            replacement
+                        }.into());
+                    }
+
+                    // GSUB lookup type 1
+                    //     "substitute a by a.sc;"
+                    //     "substitute [one.fitted one.oldstyle] by one;"
+                    //     "substitute [a-d] by [A.sc-D.sc];"
+                    if subtype == Subtype::Forward
+                            && !pattern.has_marks
+                            && pattern.glyphs.len() == 1 && replacement.len() == 1
+                            && pattern.num_lookups == 0 {
+
+                        return Ok(Single {
+                            glyph_class: into_first_glyph_class(pattern.glyphs).unwrap(),
+                            replacement: into_first(replacement).unwrap()
+                        }.into());
+                    }
+
+                    // GSUB lookup type 2
+                    //     "substitute f_f_i by f f i;"
+                    if subtype == Subtype::Forward
+                        && !pattern.has_marks
+                        && pattern.glyphs.len() == 1
+                        && pattern.glyphs[0].class.is_single()
+                        && replacement.len() > 1
+                        && replacement.iter().all(|cls| cls.is_single())
+                        && pattern.num_lookups == 0 {
+
+                        let glyph = into_first_glyph_class(pattern.glyphs).unwrap()
+                            .into_single().unwrap();
+
+                        let sequence = replacement.into_iter()
+                            .map(|cls| cls.into_single().unwrap())
+                            .collect();
+
+                        return Ok(Multiple {
+                            glyph,
+                            sequence,
+                        }.into());
+                    }
+
+                    // GSUB lookup type 4
+                    //     "substitute f f i by f_f_i;"
+                    //     "substitute [one one.oldstyle] slash two by onehalf;"
+                    if subtype == Subtype::Forward
+                        && !pattern.has_marks
+                        && pattern.glyphs.len() > 1
+                        && replacement.len() == 1
+                        && replacement[0].is_single()
+                        && pattern.num_lookups == 0 {
+
+                        return Ok(Ligature {
+                            glyphs: into_glyphs(pattern.glyphs),
+                            replacement: into_first(replacement).unwrap()
+                                .into_single().unwrap()
+                        }.into());
+                    }
 
-                if replacement.len() != 1 {
                     crate::parse_bail!(Input, position,
-                        "Expected a single glyph class after \"from\"");
-                }
-
-                if pattern.prefix.len() == 0 && pattern.suffix.len() == 0 {
-                    let glyph = into_first_glyph_class(pattern.glyphs).unwrap()
-                        .into_single().unwrap();
-
-                    return Ok(Alternate {
-                        glyph,
-                        replacement: into_first(replacement).unwrap()
-                    }.into());
-                }
-            }
-
-            // GSUB lookup type 1
-            //     "substitute a by a.sc;"
-            //     "substitute [one.fitted one.oldstyle] by one;"
-            //     "substitute [a-d] by [A.sc-D.sc];"
-            if subtype == Subtype::Forward
-                    && pattern.glyphs.len() == 1 && replacement.len() == 1
-                    && pattern.num_lookups == 0 {
-
-                return Ok(Single {
-                    prefix: into_glyphs(pattern.prefix),
-                    glyph_class: into_first_glyph_class(pattern.glyphs).unwrap(),
-                    suffix: into_glyphs(pattern.suffix),
-                    replacement: into_first(replacement).unwrap(),
-
-                    force_chain: pattern.has_marks
-                }.into());
-            }
-
-            // GSUB lookup type 2
-            //     "substitute f_f_i by f f i;"
-            if subtype == Subtype::Forward
-                && pattern.glyphs.len() == 1
-                && pattern.glyphs[0].class.is_single()
-                && replacement.len() > 1
-                && replacement.iter().all(|cls| cls.is_single())
-                && pattern.num_lookups == 0 {
-
-                let glyph = into_first_glyph_class(pattern.glyphs).unwrap()
-                    .into_single().unwrap();
-
-                let sequence = replacement.into_iter()
-                    .map(|cls| cls.into_single().unwrap())
-                    .collect();
-
-                return Ok(Multiple {
-                    glyph,
-                    sequence,
-                }.into());
-            }
-
-            Ok(Single {
-                prefix: Vec::new(),
-                glyph_class: GlyphClass(vec![]),
-                suffix: Vec::new(),
-                replacement: GlyphClass(vec![]),
-                force_chain: false
-            }.into())
+                        "unsupported substitution form");
+                })
+                .right()
         })
 }
@@ -18,6 +18,7 @@ use crate::parse_model::tables::gdef::*;
 use crate::parse_model::tables::head::*;
 use crate::parse_model::tables::hhea::*;
 use crate::parse_model::tables::name::*;
+use crate::parse_model::tables::os2::*;
 use crate::parse_model::tables::vhea::*;
 
 #[derive(Debug, PartialEq)]
@@ -27,6 +28,7 @@ pub enum TableTag {
     head,
     hhea,
     name,
+    OS_2,
     vhea
 }
 
@@ -39,6 +41,7 @@ impl fmt::Display for TableTag {
             head => write!(f, "head"),
             hhea => write!(f, "hhea"),
             name => write!(f, "name"),
+            OS_2 => write!(f, "OS/2"),
             vhea => write!(f, "vhea")
         }
     }
@@ -74,6 +77,25 @@ pub enum TableStatement {
     // name
     NameId(NameId),
 
+    // OS/2
+    FSType(FSType),
+    Panose(Panose),
+    UnicodeRange(UnicodeRange),
+    CodePageRange(CodePageRange),
+    TypoAscender(TypoAscender),
+    TypoDescender(TypoDescender),
+    TypoLineGap(TypoLineGap),
+    WinAscent(WinAscent),
+    WinDescent(WinDescent),
+    XHeight(XHeight),
+    CapHeight(CapHeight),
+    WeightClass(WeightClass),
+    WidthClass(WidthClass),
+    Vendor(Vendor),
+    LowerOpSize(LowerOpSize),
+    UpperOpSize(UpperOpSize),
+    FamilyClass(FamilyClass),
+
     // vhea
     VertTypoAscender(VertTypoAscender),
     VertTypoDescender(VertTypoDescender),
@@ -90,6 +112,23 @@ cvt_to_statement!(Ascender);
 cvt_to_statement!(Descender);
 cvt_to_statement!(LineGap);
 cvt_to_statement!(NameId);
+cvt_to_statement!(FSType);
+cvt_to_statement!(Panose);
+cvt_to_statement!(UnicodeRange);
+cvt_to_statement!(CodePageRange);
+cvt_to_statement!(TypoAscender);
+cvt_to_statement!(TypoDescender);
+cvt_to_statement!(TypoLineGap);
+cvt_to_statement!(WinAscent);
+cvt_to_statement!(WinDescent);
+cvt_to_statement!(XHeight);
+cvt_to_statement!(CapHeight);
+cvt_to_statement!(WeightClass);
+cvt_to_statement!(WidthClass);
+cvt_to_statement!(Vendor);
+cvt_to_statement!(LowerOpSize);
+cvt_to_statement!(UpperOpSize);
+cvt_to_statement!(FamilyClass);
 cvt_to_statement!(VertTypoAscender);
 cvt_to_statement!(VertTypoDescender);
 cvt_to_statement!(VertTypoLineGap);
@@ -109,6 +148,7 @@ fn table_statement<Input>(tag: &TableTag) -> impl Parser<FeaRsStream<Input>, Out
         &TableTag::head => head_statement(),
         &TableTag::hhea => hhea_statement(),
         &TableTag::name => name_statement(),
+        &TableTag::OS_2 => os2_statement(),
         &TableTag::vhea => vhea_statement()
     )
 }
@@ -120,7 +160,7 @@ fn table_tag<Input>() -> impl Parser<FeaRsStream<Input>, Output = TableTag>
     fn char_valid(x: u8) -> bool {
         let x = x as char;
 
-        x.is_ascii_alphabetic() || x == '/'
+        x.is_ascii_alphanumeric() || x == '/'
     }
 
     combine::position()
@@ -136,18 +176,21 @@ fn table_tag<Input>() -> impl Parser<FeaRsStream<Input>, Output = TableTag>
                 b"head" => TableTag::head,
                 b"hhea" => TableTag::hhea,
                 b"name" => TableTag::name,
+                b"OS/2" => TableTag::OS_2,
                 b"vhea" => TableTag::vhea,
 
-                _ =>
+                _ => {
                     crate::parse_bail!(Input, position,
-                        "unknown table identifier")
+                        "unknown table identifier");
+                }
             })
         })
 }
 
 pub(crate) fn table<Input>() -> impl Parser<FeaRsStream<Input>, Output = Table>
     where Input: Stream<Token = u8, Position = SourcePosition>,
-          Input::Error: ParseError<Input::Token, Input::Range, Input::Position>
+          Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+          Input::Error: IntoParseError
 {
     literal_ignore_case("table")
         .skip(required_whitespace())
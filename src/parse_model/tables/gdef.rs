@@ -126,9 +126,10 @@ pub(crate) fn gdef_statement<Input>() -> impl Parser<FeaRsStream<Input>, Output
                     }.into()),
 
                 _ => value(position)
-                .flat_map(|position|
+                .flat_map(|position| {
                     crate::parse_bail!(Input, position,
-                        "unexpected keyword"))
+                        "unexpected keyword");
+                })
             )
         })
 }
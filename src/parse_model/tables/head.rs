@@ -28,9 +28,10 @@ pub(crate) fn head_statement<Input>() -> impl Parser<FeaRsStream<Input>, Output
                     .map(|fr| FontRevision(fr).into()),
 
                 _ => value(position)
-                .flat_map(|position|
+                .flat_map(|position| {
                     crate::parse_bail!(Input, position,
-                        "unexpected keyword"))
+                        "unexpected keyword");
+                })
             )
         })
 }
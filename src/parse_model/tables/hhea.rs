@@ -14,16 +14,16 @@ use crate::parse_model::metric::*;
 use crate::parse_model::util::*;
 
 #[derive(Debug)]
-pub struct CaretOffset(Metric);
+pub struct CaretOffset(pub Metric);
 
 #[derive(Debug)]
-pub struct Ascender(Metric);
+pub struct Ascender(pub Metric);
 
 #[derive(Debug)]
-pub struct Descender(Metric);
+pub struct Descender(pub Metric);
 
 #[derive(Debug)]
-pub struct LineGap(Metric);
+pub struct LineGap(pub Metric);
 
 pub(crate) fn hhea_statement<Input>() -> impl Parser<FeaRsStream<Input>, Output = TableStatement>
     where Input: Stream<Token = u8, Position = SourcePosition>,
@@ -40,9 +40,10 @@ pub(crate) fn hhea_statement<Input>() -> impl Parser<FeaRsStream<Input>, Output
                 "LineGap" => metric().map(|m| LineGap(m).into()),
 
                 _ => value(position)
-                .flat_map(|position|
+                .flat_map(|position| {
                     crate::parse_bail!(Input, position,
-                        "unexpected keyword"))
+                        "unexpected keyword");
+                })
             )
         })
 }
@@ -2,4 +2,5 @@ pub mod gdef;
 pub mod head;
 pub mod hhea;
 pub mod name;
+pub mod os2;
 pub mod vhea;
@@ -55,9 +55,10 @@ pub(crate) fn name_statement<Input>() -> impl Parser<FeaRsStream<Input>, Output
                 "nameid" => nameid().map(TableStatement::from),
 
                 _ => value(position)
-                .flat_map(|position|
+                .flat_map(|position| {
                     crate::parse_bail!(Input, position,
-                        "unexpected keyword"))
+                        "unexpected keyword");
+                })
             )
         })
 }
@@ -0,0 +1,160 @@
+use combine::{
+    Parser,
+    Stream,
+    error::ParseError,
+
+    dispatch,
+    value,
+    token,
+    satisfy,
+
+    parser::repeat::many1
+};
+
+use crate::parser::*;
+use crate::Tag;
+
+use crate::parse_model::table::*;
+use crate::parse_model::metric::*;
+use crate::parse_model::util::*;
+
+#[derive(Debug)]
+pub struct FSType(pub usize);
+
+#[derive(Debug)]
+pub struct Panose(pub [usize; 10]);
+
+// bit numbers, as with the spec's `UnicodeRange` statement
+#[derive(Debug)]
+pub struct UnicodeRange(pub Vec<usize>);
+
+// code page numbers (e.g. 1252), not bit numbers
+#[derive(Debug)]
+pub struct CodePageRange(pub Vec<usize>);
+
+#[derive(Debug)]
+pub struct TypoAscender(pub Metric);
+
+#[derive(Debug)]
+pub struct TypoDescender(pub Metric);
+
+#[derive(Debug)]
+pub struct TypoLineGap(pub Metric);
+
+#[derive(Debug)]
+pub struct WinAscent(pub Metric);
+
+#[derive(Debug)]
+pub struct WinDescent(pub Metric);
+
+#[derive(Debug)]
+pub struct XHeight(pub Metric);
+
+#[derive(Debug)]
+pub struct CapHeight(pub Metric);
+
+#[derive(Debug)]
+pub struct WeightClass(pub usize);
+
+#[derive(Debug)]
+pub struct WidthClass(pub usize);
+
+#[derive(Debug)]
+pub struct Vendor(pub Tag);
+
+#[derive(Debug)]
+pub struct LowerOpSize(pub usize);
+
+#[derive(Debug)]
+pub struct UpperOpSize(pub usize);
+
+#[derive(Debug)]
+pub struct FamilyClass(pub isize);
+
+fn number_list<Input>() -> impl Parser<FeaRsStream<Input>, Output = Vec<usize>>
+    where Input: Stream<Token = u8, Position = SourcePosition>,
+          Input::Error: ParseError<Input::Token, Input::Range, Input::Position>
+{
+    many1(uinteger()
+        .skip(optional_whitespace()))
+}
+
+fn panose<Input>() -> impl Parser<FeaRsStream<Input>, Output = TableStatement>
+    where Input: Stream<Token = u8, Position = SourcePosition>,
+          Input::Error: ParseError<Input::Token, Input::Range, Input::Position>
+{
+    combine::position()
+        .and(number_list())
+        .flat_map(|(position, numbers)| {
+            let mut panose = [0usize; 10];
+
+            if numbers.len() != panose.len() {
+                crate::parse_bail!(Input, position,
+                    "expected 10 panose numbers");
+            }
+
+            panose.copy_from_slice(&numbers);
+            Ok(Panose(panose).into())
+        })
+}
+
+fn vendor<Input>() -> impl Parser<FeaRsStream<Input>, Output = TableStatement>
+    where Input: Stream<Token = u8, Position = SourcePosition>,
+          Input::Error: ParseError<Input::Token, Input::Range, Input::Position>
+{
+    combine::position()
+        .skip(token(b'"'))
+        .and(many1(satisfy(|c: u8| c != b'"' && c.is_ascii() && !c.is_ascii_control())))
+        .skip(token(b'"'))
+        .flat_map(|(position, id): (_, Vec<u8>)| {
+            if id.len() > 4 {
+                crate::parse_bail!(Input, position,
+                    "vendor id can't be longer than 4 characters");
+            }
+
+            // shorter ids are padded with spaces
+            match Tag::from_bytes(&id) {
+                Ok(tag) => Ok(Vendor(tag).into()),
+                Err(_) => {
+                    crate::parse_bail!(Input, position,
+                        "invalid vendor id");
+                }
+            }
+        })
+}
+
+pub(crate) fn os2_statement<Input>() -> impl Parser<FeaRsStream<Input>, Output = TableStatement>
+    where Input: Stream<Token = u8, Position = SourcePosition>,
+          Input::Error: ParseError<Input::Token, Input::Range, Input::Position>
+{
+    combine::position()
+        .and(keyword())
+        .skip(required_whitespace())
+        .then(|(position, kwd)| {
+            dispatch!(&*kwd;
+                "FSType" => uinteger().map(|n| FSType(n).into()),
+                "Panose" => panose(),
+                "UnicodeRange" => number_list().map(|bits| UnicodeRange(bits).into()),
+                "CodePageRange" => number_list().map(|pages| CodePageRange(pages).into()),
+                "TypoAscender" => metric().map(|m| TypoAscender(m).into()),
+                "TypoDescender" => metric().map(|m| TypoDescender(m).into()),
+                "TypoLineGap" => metric().map(|m| TypoLineGap(m).into()),
+                "winAscent" => metric().map(|m| WinAscent(m).into()),
+                "winDescent" => metric().map(|m| WinDescent(m).into()),
+                "XHeight" => metric().map(|m| XHeight(m).into()),
+                "CapHeight" => metric().map(|m| CapHeight(m).into()),
+                "WeightClass" => uinteger().map(|n| WeightClass(n).into()),
+                "WidthClass" => uinteger().map(|n| WidthClass(n).into()),
+                "Vendor" => vendor(),
+                "LowerOpSize" => uinteger().map(|n| LowerOpSize(n).into()),
+                "UpperOpSize" => uinteger().map(|n| UpperOpSize(n).into()),
+                "FamilyClass" => number().map(|n| FamilyClass(n).into()),
+
+                _ => value(position)
+                .flat_map(|position| {
+                    crate::parse_bail!(Input, position,
+                        "unexpected keyword");
+                })
+            )
+        })
+}
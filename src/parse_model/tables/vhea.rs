@@ -15,13 +15,13 @@ use crate::parse_model::table::*;
 use crate::parse_model::util::*;
 
 #[derive(Debug)]
-pub struct VertTypoAscender(isize);
+pub struct VertTypoAscender(pub isize);
 
 #[derive(Debug)]
-pub struct VertTypoDescender(isize);
+pub struct VertTypoDescender(pub isize);
 
 #[derive(Debug)]
-pub struct VertTypoLineGap(isize);
+pub struct VertTypoLineGap(pub isize);
 
 pub(crate) fn vhea_statement<Input>() -> impl Parser<FeaRsStream<Input>, Output = TableStatement>
     where Input: Stream<Token = u8, Position = SourcePosition>,
@@ -37,9 +37,10 @@ pub(crate) fn vhea_statement<Input>() -> impl Parser<FeaRsStream<Input>, Output
                 "VertTypoLineGap" => number().map(|n| VertTypoLineGap(n).into()),
 
                 _ => value(position)
-                .flat_map(|position|
+                .flat_map(|position| {
                     crate::parse_bail!(Input, position,
-                        "unexpected keyword"))
+                        "unexpected keyword");
+                })
             )
         })
 }
@@ -26,6 +26,7 @@ pub enum TopLevelStatement {
     LanguageSystem(LanguageSystem),
 
     AnchorDefinition(AnchorDefinition),
+    ValueRecordDefinition(ValueRecordDefinition),
     MarkClass(MarkClass),
     NamedGlyphClass(NamedGlyphClass),
 
@@ -35,11 +36,14 @@ pub enum TopLevelStatement {
     Anonymous(Anonymous),
 
     Table(Table),
+
+    Include(Include),
 }
 
 pub(crate) fn top_level_statement<Input>() -> impl Parser<FeaRsStream<Input>, Output = TopLevelStatement>
     where Input: Stream<Token = u8, Position = SourcePosition>,
-          Input::Error: ParseError<Input::Token, Input::Range, Input::Position>
+          Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+          Input::Error: IntoParseError
 {
     look_ahead(take_until(space()))
         .then(|kwd: Vec<_>| {
@@ -68,15 +72,31 @@ pub(crate) fn top_level_statement<Input>() -> impl Parser<FeaRsStream<Input>, Ou
                     anchor_definition()
                         .map(TopLevelStatement::AnchorDefinition),
 
+                b"valueRecordDef" =>
+                    value_record_definition()
+                        .map(TopLevelStatement::ValueRecordDefinition),
+
                 b"anon" | b"anonymous" =>
                     anonymous()
                         .map(TopLevelStatement::Anonymous),
 
+                kwd if kwd.starts_with(b"include") =>
+                    include()
+                        .map(TopLevelStatement::Include),
+
                 kwd if kwd[0] == b'@' =>
                     named_glyph_class()
                         .map(TopLevelStatement::NamedGlyphClass),
 
-                _ => combine::unexpected_any("token"))
+                _ => {
+                    let kwd = String::from_utf8_lossy(&kwd).into_owned();
+
+                    combine::position()
+                        .flat_map(move |position| {
+                            crate::parse_bail!(Input, position,
+                                format!("unexpected keyword \"{}\"", kwd));
+                        })
+                })
         })
         .skip(optional_whitespace())
         .skip(token(b';'))
@@ -36,7 +36,8 @@ use combine::parser::byte::{
     newline,
     letter,
     space,
-    digit
+    digit,
+    hex_digit
 };
 
 pub(crate) use crate::util::{
@@ -132,9 +133,9 @@ pub(crate) fn hex_uint<Input>() -> impl Parser<FeaRsStream<Input>, Output = usiz
     combine::position()
         .skip(token(b'0'))
         .skip(token(b'x'))
-        .and(many1(digit()))
+        .and(many1(hex_digit()))
         .flat_map(|(position, n): (_, Vec<u8>)| {
-            // unsafe is fine here. we've verified that this vec only contains digits.
+            // unsafe is fine here. we've verified that this vec only contains hex digits.
             let as_str = unsafe { str::from_utf8_unchecked(&*n) };
 
             usize::from_str_radix(as_str, 16)
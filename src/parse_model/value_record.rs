@@ -1,18 +1,51 @@
+use std::fmt;
+
 use combine::{
     Parser,
     Stream,
     error::ParseError,
 
+    attempt,
     token,
     choice
 };
 
 use crate::parser::*;
+use crate::glyph::*;
+
 use super::metric::*;
 use super::device::*;
+use super::glyph::*;
 use super::util::*;
 
+#[derive(Clone, PartialOrd, Ord, PartialEq, Eq, Hash)]
+pub struct ValueRecordName(pub GlyphNameStorage);
+
+impl From<&ValueRecordName> for String {
+    fn from(name: &ValueRecordName) -> String {
+        name.0.as_str().into()
+    }
+}
+
+impl fmt::Debug for ValueRecordName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ValueRecordName(\"")?;
+
+        for c in &self.0 {
+            write!(f, "{}", c)?;
+        }
+
+        write!(f, "\")")
+    }
+}
+
 #[derive(Debug)]
+pub struct ValueRecordDefinition {
+    pub name: ValueRecordName,
+    pub value_record: ValueRecord
+}
+
+#[derive(Debug, Clone)]
 pub enum ValueRecord {
     // format A
     Advance(Metric),
@@ -36,7 +69,10 @@ pub enum ValueRecord {
 
     // format D
     // spec says unimplemented, but feaLib implements it
-    Null
+    Null,
+
+    // `<NAME>`, from a `valueRecordDef`
+    Named(ValueRecordName)
 }
 
 #[inline]
@@ -95,15 +131,39 @@ pub(crate) fn value_record<Input>() -> impl Parser<FeaRsStream<Input>, Output =
     choice((
         token(b'<')
             .with(choice((
-                literal("NULL")
-                    .skip(token(b'>'))
+                attempt(literal("NULL")
+                    .skip(token(b'>')))
                     .map(|_| ValueRecord::Null),
 
                 optional_whitespace()
-                    .with(placement_advance()),
+                    .with(choice((
+                        placement_advance(),
+
+                        glyph_name_unwrapped()
+                            .skip(optional_whitespace())
+                            .skip(token(b'>'))
+                            .map(|name| ValueRecord::Named(ValueRecordName(name)))
+                    )))
             ))),
 
         metric()
             .map(ValueRecord::Advance)
     ))
 }
+
+pub(crate) fn value_record_definition<Input>() -> impl Parser<FeaRsStream<Input>, Output = ValueRecordDefinition>
+    where Input: Stream<Token = u8, Position = SourcePosition>,
+          Input::Error: ParseError<Input::Token, Input::Range, Input::Position>
+{
+    literal("valueRecordDef")
+        .skip(required_whitespace())
+        .with(value_record())
+        .skip(required_whitespace())
+        .and(glyph_name_unwrapped())
+
+        .map(|(value_record, name)|
+            ValueRecordDefinition {
+                name: ValueRecordName(name),
+                value_record
+            })
+}
@@ -1,113 +1,1039 @@
+use std::io;
 use std::io::prelude::*;
+use std::iter;
+use std::fs;
+use std::path::{
+    Path,
+    PathBuf
+};
 
 use combine::{
     Parser,
+    Stream,
+    StreamOnce,
     stream,
 
     eof,
 
+    error::{
+        Commit,
+        Token,
+        ParseError as _
+    },
     parser::repeat::many,
 };
 
 use combine::stream::{
-    buffered,
-    read,
-    easy
-};
+    Positioned,
+    ResetStream,
 
-use ascii::ToAsciiChar;
+    easy,
+    position::{
+        Positioner,
+        RangePositioner
+    }
+};
 
 use crate::parse_model::*;
+use crate::FileId;
+
 
+pub use crate::SourcePosition;
 
-pub use combine::stream::position::SourcePosition;
+pub use crate::parse_error::{
+    ParseError,
+    ParseErrorKind
+};
+
+pub(crate) use crate::parse_error::IntoParseError;
 
 
+/****************************************************************************
+ * options
+ ****************************************************************************/
+
+/// Options which change what the parser accepts.
+///
+/// The parse functions in this module use the defaults. The methods here take the same arguments
+/// and return the same results, with these options applied.
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    /// Allow the extra characters `*+-:^|~` in glyph names, as in makeotf's development glyph
+    /// names. Since hyphens can then appear in glyph names, a hyphen followed directly by glyph
+    /// name characters is part of a name: `[a-z]` is a class with the single glyph `a-z`, and
+    /// ranges are written with whitespace as `[a - z]`.
+    pub development_glyph_names: bool
+}
+
 /****************************************************************************
  * parser state
  ****************************************************************************/
 
 pub(crate) struct FeaRsParserState {
-    pub development_glyph_names: bool
+    pub development_glyph_names: bool,
+
+    // which file is being parsed, for source spans.
+    pub file: FileId,
+
+    // when set, statements which fail to parse are skipped and their errors collected in
+    // `errors`, rather than failing the whole parse. each error is kept with the start of the
+    // statement it's from.
+    pub recover: bool,
+    pub errors: Vec<(SourcePosition, ParseError)>
 }
 
 pub(crate) type FeaRsStream<S> = stream::state::Stream<S, FeaRsParserState>;
 
 /****************************************************************************
- * parse func
+ * positions
+ ****************************************************************************/
+
+// lines and columns count from 1, and columns count bytes rather than chars, the same as
+// combine's own `SourcePosition`.
+impl Positioner<u8> for SourcePosition {
+    type Position = Self;
+    type Checkpoint = Self;
+
+    #[inline]
+    fn position(&self) -> Self {
+        self.clone()
+    }
+
+    #[inline]
+    fn update(&mut self, token: &u8) {
+        self.offset += 1;
+
+        if *token == b'\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+    }
+
+    #[inline]
+    fn checkpoint(&self) -> Self {
+        self.clone()
+    }
+
+    #[inline]
+    fn reset(&mut self, checkpoint: Self) {
+        *self = checkpoint;
+    }
+}
+
+impl<'a> RangePositioner<u8, &'a [u8]> for SourcePosition {
+    fn update_range(&mut self, range: &&'a [u8]) {
+        for t in range.iter() {
+            self.update(t);
+        }
+    }
+}
+
+/****************************************************************************
+ * error recovery
  ****************************************************************************/
 
-fn format_errors<T, R>(errors: combine::easy::Errors<T, R, SourcePosition>)
-where
-    T: std::fmt::Debug + ToAsciiChar,
-    R: std::fmt::Debug
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Resync {
+    // statements inside a `{ }` block. the closing brace belongs to the block, so it's left alone.
+    Block,
+    TopLevel
+}
+
+fn skip_past<Input>(input: &mut FeaRsStream<Input>, end: u8)
+    where Input: Stream<Token = u8, Position = SourcePosition>
 {
-    use combine::stream::easy::Info;
+    while let Ok(t) = input.uncons() {
+        if t == end {
+            break;
+        }
+    }
+}
 
-    macro_rules! println_with_info {
-        ($fmt: expr, $info: expr) => {
+// skips to the end of a statement which failed to parse: past the next `;` which isn't inside a
+// nested block, or up to an unmatched `}`. comments and strings are skipped whole so that any
+// punctuation in them doesn't count.
+fn skip_statement<Input>(input: &mut FeaRsStream<Input>, resync: Resync)
+    where Input: Stream<Token = u8, Position = SourcePosition>
+{
+    let mut depth = 0usize;
 
-            match $info {
-                Info::Token(t) => eprintln!(concat!($fmt, " token {:?}"),
-                    t.to_ascii_char().unwrap()),
-                Info::Range(r) => eprintln!(concat!($fmt, " range {:?}"), r),
-                Info::Owned(s) => eprintln!(concat!($fmt, " {}"), s),
-                Info::Static(s) => eprintln!(concat!($fmt, " {}"), s)
-            }
+    loop {
+        let checkpoint = input.checkpoint();
+
+        let t = match input.uncons() {
+            Ok(t) => t,
+            Err(_) => return
+        };
+
+        match t {
+            b'{' => depth += 1,
+            b'}' if depth > 0 => depth -= 1,
+
+            b'}' => {
+                if resync == Resync::Block {
+                    // we're only stepping back over a single token, which the stream has buffered.
+                    let _ = input.reset(checkpoint);
+                }
+
+                return;
+            },
+
+            b';' if depth == 0 => return,
+
+            b'#' => skip_past(input, b'\n'),
+            b'"' => skip_past(input, b'"'),
+
+            _ => ()
         }
     }
+}
 
-    eprintln!("parse error at {}:{}", errors.position.line, errors.position.column);
+fn peek<Input>(input: &mut FeaRsStream<Input>) -> Option<u8>
+    where Input: Stream<Token = u8, Position = SourcePosition>
+{
+    let checkpoint = input.checkpoint();
+    let next = input.uncons().ok();
+    let _ = input.reset(checkpoint);
 
-    for e in errors.errors {
-        use combine::stream::easy::Error::*;
+    next
+}
+
+// whether the input is at the end of the current run of statements, where failing to parse
+// another statement isn't an error.
+fn at_end<Input>(input: &mut FeaRsStream<Input>, resync: Resync) -> bool
+    where Input: Stream<Token = u8, Position = SourcePosition>
+{
+    match peek(input) {
+        None => true,
+        Some(b'}') => resync == Resync::Block,
+        Some(_) => false
+    }
+}
 
-        match e {
-            Unexpected(info) => println_with_info!("    unexpected", info),
-            Expected(info) => println_with_info!("    expected", info),
-            Message(info) => println_with_info!("   ", info),
-            _ => println!("    {:?}", e)
+// steps forward to `position`, which a failed parser has to have got to from the checkpoint it was
+// reset to.
+fn advance_to<Input>(input: &mut FeaRsStream<Input>, position: &SourcePosition)
+    where Input: Stream<Token = u8, Position = SourcePosition>
+{
+    while input.position() < *position {
+        if input.uncons().is_err() {
+            break;
         }
     }
 }
 
-pub fn parse_all<R: Read>(input: R) -> Result<Vec<TopLevelStatement>, ()> {
-    let mut parser = optional_whitespace()
-        .with(many(
-            top_level_statement()
-                .skip(optional_whitespace())
-        ))
-        .skip(eof());
+// wraps the parser for a single statement (including its trailing whitespace) for use in
+// `many()`. in recovery mode, a statement that fails to parse has its error recorded in the parser
+// state and is skipped, giving `None`. otherwise errors are passed through untouched.
+//
+// skipping starts from where the error is rather than from wherever the failed parser stopped,
+// which can be past the offending token, so that a `}` closing the enclosing block isn't
+// skipped along with the statement.
+pub(crate) fn recovering<Input, P>(resync: Resync, mut statement: P)
+        -> impl Parser<FeaRsStream<Input>, Output = Option<P::Output>>
+    where Input: Stream<Token = u8, Position = SourcePosition>,
+          Input::Error: combine::ParseError<Input::Token, Input::Range, Input::Position>,
+          Input::Error: IntoParseError,
+          P: Parser<FeaRsStream<Input>>
+{
+    combine::parser(move |input: &mut FeaRsStream<Input>| {
+        let start = input.position();
+        let checkpoint = input.checkpoint();
+
+        // errors from statements starting here or later came from an attempt at parsing this
+        // same input which was backtracked out of, and are about to be found again.
+        if input.state.recover {
+            input.state.errors.retain(|(statement_start, _)| *statement_start < start);
+        }
+
+        let (mut error, committed) = match statement.parse_stream(input).into_result() {
+            Ok((statement, commit)) => return Ok((Some(statement), commit)),
+
+            Err(e) if !input.state.recover => return Err(e),
+            Err(Commit::Peek(e)) if at_end(input, resync) => return Err(Commit::Peek(e)),
+
+            Err(Commit::Peek(e)) => (e.error, false),
+            Err(Commit::Commit(e)) => (e.error, true)
+        };
+
+        // errors which stop a statement after it's started don't say what was found instead,
+        // since that's normally added as the error bubbles up to the top of the parse.
+        if committed && input.position() == error.position() {
+            if let Some(t) = peek(input) {
+                error.add_unexpected(Token(t));
+            }
+        }
+
+        // the statement's tokens were all buffered as it was parsed, so this can't fail.
+        let _ = input.reset(checkpoint);
+        advance_to(input, &error.position());
+
+        input.state.errors.push((start, error.into_parse_error()));
+
+        skip_statement(input, resync);
+        let _ = optional_whitespace().parse_stream(input);
+
+        Ok((None, Commit::Commit(())))
+    })
+}
+
+/****************************************************************************
+ * parse func
+ ****************************************************************************/
 
-    let stream = FeaRsStream {
+type SliceStream<'a> =
+    easy::Stream<
+        stream::position::Stream<&'a [u8], SourcePosition>>;
+
+// errors which were recovered from are added to `recovered`, and recovery is only enabled when
+// that's given.
+fn parse_slice<'a, P>(input: &'a [u8], file: FileId, path: Option<&Path>, options: &ParseOptions,
+    recovered: Option<&mut Vec<ParseError>>, mut parser: P) -> Result<P::Output, ParseError>
+    where P: Parser<FeaRsStream<SliceStream<'a>>>
+{
+    let mut stream = FeaRsStream {
         stream:
             easy::Stream::from(
-                buffered::Stream::new(
-                    stream::position::Stream::with_positioner(
-                        read::Stream::new(input),
-                        SourcePosition::new()),
-                    512)),
+                stream::position::Stream::with_positioner(input, SourcePosition::default())),
 
         state: FeaRsParserState {
-            development_glyph_names: false
+            development_glyph_names: options.development_glyph_names,
+            file,
+
+            recover: recovered.is_some(),
+            errors: Vec::new()
         }
     };
 
-    match parser.parse(stream) {
-        Err(errs) => {
-            format_errors(errs);
-            Err(())
-        },
+    let res = parser.parse_stream(&mut stream).into_result()
+        .map(|(output, _)| output)
+        .map_err(|errs| ParseError::from_combine(path.map(Into::into), errs.into_inner().error));
+
+    if let Some(recovered) = recovered {
+        recovered.extend(stream.state.errors.into_iter()
+            .map(|(_, e)| ParseError { file: path.map(Into::into), ..e }));
+    }
+
+    res
+}
+
+fn parse_top_level(input: &[u8], file: FileId, path: Option<&Path>, options: &ParseOptions,
+    recovered: Option<&mut Vec<ParseError>>) -> Result<Vec<TopLevelStatement>, ParseError> {
+    let parser = optional_whitespace()
+        .with(many(
+            recovering(Resync::TopLevel,
+                top_level_statement()
+                    .skip(optional_whitespace()))
+        ))
+        .skip(eof())
+        .map(|statements: Vec<Option<_>>| statements.into_iter().flatten().collect());
+
+    parse_slice(input, file, path, options, recovered, parser)
+}
+
+// for files included from within a feature or lookup block.
+fn parse_block_statements(input: &[u8], file: FileId, path: Option<&Path>, options: &ParseOptions,
+    recovered: Option<&mut Vec<ParseError>>) -> Result<Vec<BlockStatement>, ParseError> {
+    let parser = block_statements(block_statement(&()))
+        .skip(eof());
+
+    parse_slice(input, file, path, options, recovered, parser)
+}
+
+/****************************************************************************
+ * includes
+ ****************************************************************************/
+
+fn io_error(path: &Path, e: io::Error) -> ParseError {
+    ParseError::new(ParseErrorKind::Io, Some(path.into()), None,
+        format!("couldn't read \"{}\": {}", path.display(), e))
+}
+
+struct Includes<'a> {
+    options: &'a ParseOptions,
+    search_paths: &'a [PathBuf],
+    files: Vec<PathBuf>,
+
+    // every error found so far, when parsing in recovery mode.
+    errors: Option<Vec<ParseError>>,
+
+    // canonicalised paths of the files which are currently being included, innermost last.
+    stack: Vec<PathBuf>
+}
+
+impl<'a> Includes<'a> {
+    fn new(options: &'a ParseOptions, search_paths: &'a [PathBuf], recover: bool) -> Self {
+        Self {
+            options,
+            search_paths,
+            files: Vec::new(),
+            errors: if recover { Some(Vec::new()) } else { None },
+            stack: Vec::new()
+        }
+    }
+
+    // in recovery mode, an error is kept and whatever failed is left out. otherwise it's passed on.
+    fn recover<T>(&mut self, res: Result<Vec<T>, ParseError>) -> Result<Vec<T>, ParseError> {
+        match (res, self.errors.as_mut()) {
+            (Err(e), Some(errors)) => {
+                errors.push(e);
+                Ok(Vec::new())
+            },
+
+            (res, _) => res
+        }
+    }
+
+    // paths are resolved relative to the including file first, then through the search paths
+    // in order. absolute paths are used as-is.
+    fn resolve(&self, include: &Include, dir: Option<&Path>) -> Option<PathBuf> {
+        if include.0.value.is_absolute() {
+            return Some(include.0.value.clone())
+                .filter(|path| path.is_file());
+        }
+
+        dir.into_iter()
+            .chain(self.search_paths.iter().map(PathBuf::as_path))
+            .map(|search_dir| search_dir.join(&include.0.value))
+            .find(|path| path.is_file())
+    }
+
+    // `from` is the including file, which is `None` for the root file when we don't have a path
+    // for it.
+    fn include<T, PF, EF>(&mut self, include: &Include, from: Option<&Path>, parse: PF, expand: EF)
+            -> Result<Vec<T>, ParseError>
+        where PF: FnOnce(&[u8], FileId, &Path, &ParseOptions, Option<&mut Vec<ParseError>>)
+                    -> Result<Vec<T>, ParseError>,
+              EF: FnOnce(&mut Self, Vec<T>, Option<&Path>) -> Result<Vec<T>, ParseError>
+    {
+        let error = |kind, message| ParseError::new(kind, from.map(Into::into),
+            Some(include.0.span.start.clone()), message);
+
+        // without a path for the root file there's nothing to resolve relative paths against,
+        // and the current directory would only be right by accident.
+        let dir = from.and_then(Path::parent);
+
+        if dir.is_none() && include.0.value.is_relative() {
+            return Err(error(ParseErrorKind::IncludeNotFound,
+                format!("can't include \"{}\" by a relative path without a path for the including \
+                    file", include.0.display())));
+        }
+
+        let path = self.resolve(include, dir)
+            .ok_or_else(|| error(ParseErrorKind::IncludeNotFound,
+                format!("couldn't find included file \"{}\"", include.0.display())))?;
+
+        let canonical = path.canonicalize()
+            .map_err(|e| io_error(&path, e))?;
+
+        if self.stack.contains(&canonical) {
+            let cycle: Vec<_> = self.stack.iter()
+                .skip_while(|p| **p != canonical)
+                .chain(iter::once(&canonical))
+                .map(|p| p.display().to_string())
+                .collect();
+
+            return Err(error(ParseErrorKind::IncludeCycle,
+                format!("include cycle: {}", cycle.join(" -> "))));
+        }
+
+        self.read_file(path, canonical, parse, expand)
+    }
+
+    fn read_file<T, PF, EF>(&mut self, path: PathBuf, canonical: PathBuf, parse: PF, expand: EF)
+            -> Result<Vec<T>, ParseError>
+        where PF: FnOnce(&[u8], FileId, &Path, &ParseOptions, Option<&mut Vec<ParseError>>)
+                    -> Result<Vec<T>, ParseError>,
+              EF: FnOnce(&mut Self, Vec<T>, Option<&Path>) -> Result<Vec<T>, ParseError>
+    {
+        let source = fs::read(&path)
+            .map_err(|e| io_error(&path, e))?;
+
+        let file_id = self.files.len();
+        self.files.push(path.clone());
+
+        let statements = parse(&source, file_id, &path, self.options, self.errors.as_mut());
+        let statements = self.recover(statements)?;
+
+        self.stack.push(canonical);
+        let expanded = expand(self, statements, Some(&path));
+        self.stack.pop();
+
+        expanded
+    }
+
+    fn expand_top_level(&mut self, statements: Vec<TopLevelStatement>, from: Option<&Path>)
+            -> Result<Vec<TopLevelStatement>, ParseError> {
+        let mut expanded = Vec::with_capacity(statements.len());
 
-        Ok((definitions, stream)) => {
-            let _state = stream.state;
-            Ok(definitions)
+        for statement in statements {
+            use TopLevelStatement::*;
+
+            match statement {
+                Include(include) => {
+                    let included = self.include(&include, from,
+                        |source, id, path, options, recovered|
+                            parse_top_level(source, id, Some(path), options, recovered),
+                        Self::expand_top_level);
+
+                    expanded.extend(self.recover(included)?);
+                },
+
+                FeatureDefinition(mut fd) => {
+                    fd.statements = self.expand_block(fd.statements, from)?;
+                    expanded.push(FeatureDefinition(fd));
+                },
+
+                LookupDefinition(mut ld) => {
+                    ld.statements = self.expand_block(ld.statements, from)?;
+                    expanded.push(LookupDefinition(ld));
+                },
+
+                s => expanded.push(s)
+            }
         }
+
+        Ok(expanded)
     }
+
+    fn expand_block(&mut self, statements: Vec<BlockStatement>, from: Option<&Path>)
+            -> Result<Vec<BlockStatement>, ParseError> {
+        let mut expanded = Vec::with_capacity(statements.len());
+
+        for statement in statements {
+            use BlockStatement::*;
+
+            match statement {
+                Include(include) => {
+                    let included = self.include(&include, from,
+                        |source, id, path, options, recovered|
+                            parse_block_statements(source, id, Some(path), options, recovered),
+                        Self::expand_block);
+
+                    expanded.extend(self.recover(included)?);
+                },
+
+                LookupDefinition(mut ld) => {
+                    ld.statements = self.expand_block(ld.statements, from)?;
+                    expanded.push(LookupDefinition(ld));
+                },
+
+                s => expanded.push(s)
+            }
+        }
+
+        Ok(expanded)
+    }
+}
+
+// everything parsed from a feature file and the files it includes.
+#[derive(Debug)]
+pub struct ParsedFiles {
+    // every file which was read, indexed by the `FileId` in source spans. the root file is first.
+    pub files: Vec<PathBuf>,
+    pub statements: Vec<TopLevelStatement>
+}
+
+fn parse_path_with(path: &Path, includes: &mut Includes) -> Result<Vec<TopLevelStatement>, ParseError> {
+    let canonical = path.canonicalize()
+        .map_err(|e| io_error(path, e))?;
+
+    includes.read_file(path.into(), canonical,
+        |source, id, path, options, recovered|
+            parse_top_level(source, id, Some(path), options, recovered),
+        Includes::expand_top_level)
+}
+
+fn parse_bytes_with(input: &[u8], includes: &mut Includes) -> Result<Vec<TopLevelStatement>, ParseError> {
+    includes.files.push(PathBuf::new());
+
+    let statements = parse_top_level(input, 0, None, includes.options, includes.errors.as_mut());
+    let statements = includes.recover(statements)?;
+
+    includes.expand_top_level(statements, None)
+}
+
+fn read_all<R: Read>(mut input: R) -> Result<Vec<u8>, ParseError> {
+    let mut source = Vec::new();
+
+    input.read_to_end(&mut source)
+        .map_err(|e| ParseError::new(ParseErrorKind::Io, None, None,
+            format!("couldn't read input: {}", e)))?;
+
+    Ok(source)
+}
+
+impl ParseOptions {
+    pub fn parse_path<P: AsRef<Path>>(&self, path: P, search_paths: &[PathBuf])
+            -> Result<ParsedFiles, ParseError> {
+        let mut includes = Includes::new(self, search_paths, false);
+        let statements = parse_path_with(path.as_ref(), &mut includes)?;
+
+        Ok(ParsedFiles {
+            files: includes.files,
+            statements
+        })
+    }
+
+    pub fn parse_path_recovering<P: AsRef<Path>>(&self, path: P, search_paths: &[PathBuf])
+            -> (ParsedFiles, Vec<ParseError>) {
+        let mut includes = Includes::new(self, search_paths, true);
+
+        let statements = parse_path_with(path.as_ref(), &mut includes);
+        let statements = includes.recover(statements)
+            .unwrap_or_default();
+
+        let parsed = ParsedFiles {
+            files: includes.files,
+            statements
+        };
+
+        (parsed, includes.errors.unwrap_or_default())
+    }
+
+    pub fn parse_bytes(&self, input: &[u8]) -> Result<Vec<TopLevelStatement>, ParseError> {
+        parse_bytes_with(input, &mut Includes::new(self, &[], false))
+    }
+
+    pub fn parse_bytes_recovering(&self, input: &[u8]) -> (Vec<TopLevelStatement>, Vec<ParseError>) {
+        let mut includes = Includes::new(self, &[], true);
+
+        let statements = parse_bytes_with(input, &mut includes);
+        let statements = includes.recover(statements)
+            .unwrap_or_default();
+
+        (statements, includes.errors.unwrap_or_default())
+    }
+
+    pub fn parse_str(&self, input: &str) -> Result<Vec<TopLevelStatement>, ParseError> {
+        self.parse_bytes(input.as_bytes())
+    }
+
+    pub fn parse_str_recovering(&self, input: &str) -> (Vec<TopLevelStatement>, Vec<ParseError>) {
+        self.parse_bytes_recovering(input.as_bytes())
+    }
+
+    pub fn parse_all<R: Read>(&self, input: R) -> Result<Vec<TopLevelStatement>, ParseError> {
+        self.parse_bytes(&read_all(input)?)
+    }
+
+    pub fn parse_all_recovering<R: Read>(&self, input: R) -> (Vec<TopLevelStatement>, Vec<ParseError>) {
+        match read_all(input) {
+            Ok(source) => self.parse_bytes_recovering(&source),
+            Err(e) => (Vec::new(), vec![e])
+        }
+    }
+}
+
+// parses the feature file at `path`, resolving `include()` statements relative to the including
+// file and then through `search_paths`.
+pub fn parse_path<P: AsRef<Path>>(path: P, search_paths: &[PathBuf]) -> Result<ParsedFiles, ParseError> {
+    ParseOptions::default().parse_path(path, search_paths)
+}
+
+// like `parse_path()`, but statements which fail to parse are skipped rather than stopping the
+// parse, resynchronising at the next `;` or closing `}`. everything else is still parsed, so all of
+// the errors in a file (and the files it includes) are found in one go.
+//
+// the statements are only complete when there are no errors.
+pub fn parse_path_recovering<P: AsRef<Path>>(path: P, search_paths: &[PathBuf])
+        -> (ParsedFiles, Vec<ParseError>) {
+    ParseOptions::default().parse_path_recovering(path, search_paths)
+}
+
+// parses feature code which is already in memory. spans in the parsed statements have byte offsets
+// into `input`, so they can be sliced back out of it.
+//
+// there's no path for the root file here, so only absolute paths can be included. use
+// `parse_path()` for feature files which include others by relative path.
+pub fn parse_bytes(input: &[u8]) -> Result<Vec<TopLevelStatement>, ParseError> {
+    ParseOptions::default().parse_bytes(input)
+}
+
+pub fn parse_str(input: &str) -> Result<Vec<TopLevelStatement>, ParseError> {
+    ParseOptions::default().parse_str(input)
+}
+
+// the recovering version of `parse_bytes()`, see `parse_path_recovering()`.
+pub fn parse_bytes_recovering(input: &[u8]) -> (Vec<TopLevelStatement>, Vec<ParseError>) {
+    ParseOptions::default().parse_bytes_recovering(input)
+}
+
+pub fn parse_str_recovering(input: &str) -> (Vec<TopLevelStatement>, Vec<ParseError>) {
+    ParseOptions::default().parse_str_recovering(input)
+}
+
+// reads all of `input` and then parses it as with `parse_bytes()`.
+pub fn parse_all<R: Read>(input: R) -> Result<Vec<TopLevelStatement>, ParseError> {
+    ParseOptions::default().parse_all(input)
+}
+
+// the recovering version of `parse_all()`, see `parse_path_recovering()`.
+pub fn parse_all_recovering<R: Read>(input: R) -> (Vec<TopLevelStatement>, Vec<ParseError>) {
+    ParseOptions::default().parse_all_recovering(input)
 }
 
 // helper stub function so that we're not paying the massive monomorphisation cost on every
 // recompile of client code
-pub fn parse_file(file: ::std::fs::File) -> Result<Vec<TopLevelStatement>, ()> {
+pub fn parse_file(file: ::std::fs::File) -> Result<Vec<TopLevelStatement>, ParseError> {
     parse_all(file)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::glyph::GlyphRef;
+    use crate::glyph_class::GlyphClass;
+
+    // the parser recurses deeply enough to overflow the default test thread stack in debug builds.
+    fn on_large_stack<T, F>(f: F) -> T
+        where T: Send + 'static,
+              F: FnOnce() -> T + Send + 'static
+    {
+        std::thread::Builder::new()
+            .stack_size(64 * 1024 * 1024)
+            .spawn(f)
+            .unwrap()
+            .join()
+            .unwrap()
+    }
+
+    // a scratch directory of feature files, removed again when dropped.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str, files: &[(&str, &str)]) -> Self {
+            let dir = std::env::temp_dir()
+                .join(format!("otf-fea-rs-{}-{}", name, std::process::id()));
+
+            for (path, contents) in files {
+                let path = dir.join(path);
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(path, contents).unwrap();
+            }
+
+            Self(dir)
+        }
+
+        fn path(&self, path: &str) -> PathBuf {
+            self.0.join(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn test_includes_are_resolved_relative_to_the_including_file() {
+        let dir = TempDir::new("include-resolution", &[
+            ("main.fea", "include(sub/a.fea);\ninclude(c.fea);\n"),
+            ("sub/a.fea", "include(b.fea);\nlanguagesystem DFLT dflt;\n"),
+            ("sub/b.fea", "languagesystem latn dflt;\n"),
+            ("search/c.fea", "languagesystem grek dflt;\n"),
+
+            // shadowed by `sub/b.fea`, which is next to the file including it.
+            ("search/b.fea", "languagesystem cyrl dflt;\n")
+        ]);
+
+        let main = dir.path("main.fea");
+        let search_paths = vec![dir.path("search")];
+
+        let parsed = on_large_stack(move || parse_path(main, &search_paths)).unwrap();
+
+        assert_eq!(parsed.files, [
+            dir.path("main.fea"),
+            dir.path("sub/a.fea"),
+            dir.path("sub/b.fea"),
+            dir.path("search/c.fea")
+        ]);
+
+        let scripts: Vec<_> = parsed.statements.iter()
+            .map(|statement| match statement {
+                TopLevelStatement::LanguageSystem(ls) => ls.script.to_string(),
+                s => panic!("unexpected statement {:?}", s)
+            })
+            .collect();
+
+        assert_eq!(scripts, ["latn", "DFLT", "grek"]);
+    }
+
+    #[test]
+    fn test_include_cycles_are_rejected() {
+        let dir = TempDir::new("include-cycle", &[
+            ("a.fea", "include(b.fea);\n"),
+            ("b.fea", "languagesystem DFLT dflt;\ninclude(a.fea);\n")
+        ]);
+
+        let a = dir.path("a.fea");
+        let err = on_large_stack(move || parse_path(a, &[])).unwrap_err();
+
+        assert_eq!(err.kind, ParseErrorKind::IncludeCycle);
+        assert_eq!(err.file, Some(dir.path("b.fea")));
+        assert_eq!((err.line, err.column), (2, 1));
+
+        let a = dir.path("a.fea").canonicalize().unwrap();
+        let b = dir.path("b.fea").canonicalize().unwrap();
+        assert_eq!(err.message,
+            format!("include cycle: {} -> {} -> {}", a.display(), b.display(), a.display()));
+    }
+
+    #[test]
+    fn test_missing_includes_are_reported() {
+        let dir = TempDir::new("include-not-found", &[
+            ("main.fea", "languagesystem DFLT dflt;\n  include(missing.fea);\n")
+        ]);
+
+        let main = dir.path("main.fea");
+        let err = on_large_stack(move || parse_path(main, &[])).unwrap_err();
+
+        assert_eq!(err.kind, ParseErrorKind::IncludeNotFound);
+        assert_eq!(err.file, Some(dir.path("main.fea")));
+        assert_eq!((err.line, err.column), (2, 3));
+        assert_eq!(err.message, "couldn't find included file \"missing.fea\"");
+
+        // in recovery mode the include is left out, and everything else is kept.
+        let main = dir.path("main.fea");
+        let (parsed, errors) = on_large_stack(move || parse_path_recovering(main, &[]));
+
+        assert_eq!(parsed.statements.len(), 1);
+        assert_eq!(errors, [err]);
+    }
+
+    #[test]
+    fn test_relative_includes_need_a_root_path() {
+        let dir = TempDir::new("include-without-root", &[
+            ("a.fea", "languagesystem DFLT dflt;\n")
+        ]);
+
+        let err = on_large_stack(|| parse_str("include(a.fea);\n")).unwrap_err();
+
+        assert_eq!(err.kind, ParseErrorKind::IncludeNotFound);
+        assert_eq!(err.file, None);
+        assert_eq!(err.message,
+            "can't include \"a.fea\" by a relative path without a path for the including file");
+
+        // absolute paths don't depend on where the including file is.
+        let fea = format!("include({});\n", dir.path("a.fea").display());
+        let statements = on_large_stack(move || parse_str(&fea)).unwrap();
+
+        assert_eq!(statements.len(), 1);
+    }
+
+    #[test]
+    fn test_errors_render_with_the_offending_line() {
+        let source = "languagesystem DFLT dflt;\n\tlanguagesystem latn;\n";
+        let err = on_large_stack(move || parse_str(source)).unwrap_err();
+
+        assert_eq!(err.to_string(), "<input>:2:21: unexpected ';' (expected space)");
+
+        // the tab is kept so that the caret lines up however it's displayed.
+        assert_eq!(err.render(source), concat!(
+            "error: unexpected ';'\n",
+            " --> <input>:2:21\n",
+            "  |\n",
+            "2 | \tlanguagesystem latn;\n",
+            "  | \t                   ^\n",
+            "  |\n",
+            "  = expected space\n"));
+
+        let source = "languagesystem DFLT dflt;\nfeature liga {\n    pos a b c;\n} liga;\n";
+        let err = on_large_stack(move || parse_str(source)).unwrap_err();
+
+        assert_eq!(err.render(source), concat!(
+            "error: Expected a single or pair adjustment, or marked glyphs for contextual positioning\n",
+            " --> <input>:3:9\n",
+            "  |\n",
+            "3 |     pos a b c;\n",
+            "  |         ^\n"));
+    }
+
+    #[test]
+    fn test_error_columns_count_bytes() {
+        let err = ParseError {
+            kind: ParseErrorKind::Syntax,
+
+            file: Some("test.fea".into()),
+            line: 10,
+            column: 10,
+
+            expected: vec!["';'".into()],
+            unexpected: Vec::new(),
+            message: "syntax error".into()
+        };
+
+        // "ä" is two bytes, but only takes up one column on screen.
+        let source = format!("{}a = \"ä\" x;\n", "\n".repeat(9));
+
+        assert_eq!(err.render(&source), concat!(
+            "error: syntax error\n",
+            " --> test.fea:10:10\n",
+            "   |\n",
+            "10 | a = \"ä\" x;\n",
+            "   |         ^\n",
+            "   |\n",
+            "   = expected ';'\n"));
+    }
+
+    #[test]
+    fn test_errors_without_a_position_render_only_the_location() {
+        let path = std::env::temp_dir().join("otf-fea-rs-does-not-exist.fea");
+        let err = parse_path(&path, &[]).unwrap_err();
+
+        assert_eq!(err.kind, ParseErrorKind::Io);
+        assert_eq!((err.line, err.column), (0, 0));
+        assert_eq!(err.render(""), format!("error: {}\n --> {}\n", err.message, path.display()));
+    }
+
+    fn error_positions(errors: &[ParseError]) -> Vec<(usize, usize)> {
+        errors.iter()
+            .map(|e| (e.line, e.column))
+            .collect()
+    }
+
+    fn block_statement_counts(statements: &[TopLevelStatement]) -> Vec<(String, usize)> {
+        statements.iter()
+            .map(|statement| match statement {
+                TopLevelStatement::FeatureDefinition(fd) => (fd.tag.to_string(), fd.statements.len()),
+                TopLevelStatement::LookupDefinition(ld) => (ld.label.to_string(), ld.statements.len()),
+                s => panic!("unexpected statement {:?}", s)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_recovery_resumes_at_the_error() {
+        // the missing `;` is only noticed at the `}`, which still has to close the block.
+        let source = "feature liga { sub a by b } liga;\nfeature calt { sub a by d; } calt;\n";
+        let (statements, errors) = on_large_stack(move || parse_str_recovering(source));
+
+        assert_eq!(block_statement_counts(&statements), [("liga".into(), 0), ("calt".into(), 1)]);
+        assert_eq!(error_positions(&errors), [(1, 27)]);
+        assert_eq!(errors[0].unexpected, ["'}'"]);
+
+        let source = "lookup a { lookup b { sub x by } b; sub y by z; } a;\nlookup c { sub x by y; } c;\n";
+        let (statements, errors) = on_large_stack(move || parse_str_recovering(source));
+
+        assert_eq!(block_statement_counts(&statements), [("a".into(), 2), ("c".into(), 1)]);
+        assert_eq!(error_positions(&errors), [(1, 32)]);
+    }
+
+    #[test]
+    fn test_recovery_finds_every_error_once() {
+        let source = concat!(
+            "languagesystem DFLT;\n",
+            "feature liga {\n",
+            "  sub a by b;\n",
+            "  subx a by c;\n",
+            "  lookup foo { sub x y by ; } foo;\n",
+            "  sub c by d;\n",
+            "  pos a -10\n",
+            "} liga;\n",
+            "bogus;\n",
+            "feature kern { pos a b 10; } kern;\n");
+
+        let (statements, errors) = on_large_stack(move || parse_str_recovering(source));
+
+        assert_eq!(block_statement_counts(&statements), [("liga".into(), 3), ("kern".into(), 1)]);
+        assert_eq!(error_positions(&errors), [(1, 20), (4, 3), (5, 27), (8, 1), (9, 1)]);
+
+        // without recovery, the first error is the only one.
+        let err = on_large_stack(move || parse_str(source)).unwrap_err();
+        assert_eq!(err, errors[0]);
+    }
+
+    #[test]
+    fn test_spans_slice_back_out_of_the_source() {
+        let source = "# ä\nvalueRecordDef <1 -20 300 4000> FOO;\n";
+        let statements = on_large_stack(move || parse_str(source)).unwrap();
+
+        let metrics = match &statements[..] {
+            [TopLevelStatement::ValueRecordDefinition(ValueRecordDefinition {
+                value_record: ValueRecord::PlacementAdvance {
+                    x_placement, y_placement, x_advance, y_advance
+                },
+                ..
+            })] => [x_placement, y_placement, x_advance, y_advance],
+
+            s => panic!("unexpected statements {:?}", s)
+        };
+
+        let slices: Vec<_> = metrics.iter()
+            .map(|m| &source[m.span.start.offset..m.span.end.offset])
+            .collect();
+
+        assert_eq!(slices, ["1", "-20", "300", "4000"]);
+
+        // columns count bytes too, so the "ä" above doesn't throw them off on later lines.
+        let positions: Vec<_> = metrics.iter()
+            .map(|m| (m.span.file, m.span.start.line, m.span.start.column))
+            .collect();
+
+        assert_eq!(positions, [(0, 2, 17), (0, 2, 19), (0, 2, 23), (0, 2, 27)]);
+    }
+
+    #[test]
+    fn test_in_memory_and_reader_parses_agree() {
+        let source = "languagesystem DFLT dflt;\nfeature liga { sub f i by f_i; } liga;\n";
+
+        let from_str = on_large_stack(move || parse_str(source)).unwrap();
+        let from_reader = on_large_stack(move || parse_all(source.as_bytes())).unwrap();
+
+        assert_eq!(format!("{:?}", from_str), format!("{:?}", from_reader));
+
+        let source = "feature liga { sub f i by ; } liga;\n";
+
+        let from_str = on_large_stack(move || parse_str(source)).unwrap_err();
+        let from_reader = on_large_stack(move || parse_all(source.as_bytes())).unwrap_err();
+
+        assert_eq!(from_str, from_reader);
+    }
+
+    fn named_class(options: ParseOptions, source: &'static str) -> Result<GlyphClass, ParseError> {
+        let statements = on_large_stack(move || options.parse_str(source))?;
+
+        match &statements[..] {
+            [TopLevelStatement::NamedGlyphClass(class)] => Ok(class.glyph_class.clone()),
+            s => panic!("unexpected statements {:?}", s)
+        }
+    }
+
+    #[test]
+    fn test_development_glyph_names() {
+        use crate::glyph_class::GlyphClassItem::*;
+
+        let production = ParseOptions::default;
+        let development = || ParseOptions { development_glyph_names: true };
+
+        let glyph = |name| GlyphRef::from_name_with_options(name, &development()).unwrap();
+
+        let range = GlyphClass(vec![Range { start: glyph("a"), end: glyph("z") }]);
+
+        // a hyphen is always a range in production names, but only with whitespace around it in
+        // development names.
+        assert_eq!(named_class(production(), "@x = [a-z];\n").unwrap(), range);
+        assert_eq!(named_class(development(), "@x = [a-z];\n").unwrap(),
+            GlyphClass(vec![Single(glyph("a-z"))]));
+        assert_eq!(named_class(development(), "@x = [a - z];\n").unwrap(), range);
+
+        assert_eq!(named_class(development(), "@x = [a:b c^d e|f g~h i*j k+l];\n").unwrap().0.len(), 6);
+
+        let err = named_class(production(), "@x = [a:b];\n").unwrap_err();
+        assert_eq!((err.line, err.column, err.unexpected.as_slice()), (1, 8, &["':'".to_string()][..]));
+
+        // `GlyphRef::from_name()` follows the same rules as the parser.
+        assert!(GlyphRef::from_name("a:b").is_err());
+        assert!(GlyphRef::from_name_with_options("a:b", &development()).is_ok());
+        assert!(GlyphRef::from_name("-a").is_err());
+
+        for options in &[production(), development()] {
+            assert!(GlyphRef::from_name_with_options(".notdef", options).is_ok());
+            assert!(GlyphRef::from_name_with_options("1a", options).is_err());
+            assert!(GlyphRef::from_name_with_options(&"a".repeat(64), options).is_err());
+        }
+    }
+}
@@ -2,10 +2,23 @@ use std::ops::Deref;
 use std::fmt;
 
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct SourcePosition {
     pub line: usize,
-    pub column: usize
+    pub column: usize,
+
+    // byte offset from the start of the file, for slicing spans back out of the source.
+    pub offset: usize
+}
+
+impl Default for SourcePosition {
+    fn default() -> Self {
+        Self {
+            line: 1,
+            column: 1,
+            offset: 0
+        }
+    }
 }
 
 impl fmt::Display for SourcePosition {
@@ -14,8 +27,12 @@ impl fmt::Display for SourcePosition {
     }
 }
 
+/// Index of a source file in the list of files read by the parser. The root file is always 0.
+pub type FileId = usize;
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct SourceSpan {
+    pub file: FileId,
     pub start: SourcePosition,
     pub end: SourcePosition
 }